@@ -0,0 +1,74 @@
+//! Shows a scheduler's stacks live in the terminal while it executes.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use scheduler::{Result, Scheduler};
+use scheduler_cli::run::parse_tasks;
+use scheduler_cli::tui::{App, draw};
+
+/// How long to wait for input before executing the steps that are due.
+const FRAME_TIME: Duration = Duration::from_millis(16);
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Watch a scheduler's stacks live")]
+struct Args {
+    /// Root tasks as JSON, or `@path` to read them from a file; the first task runs first
+    tasks: Vec<String>,
+
+    /// Start from a snapshot file instead of an empty scheduler
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+
+    /// Playback speed in steps per second
+    #[arg(long, default_value_t = 10)]
+    speed: u32,
+}
+
+fn main() -> ExitCode {
+    let result = load(&Args::parse()).and_then(|app| {
+        let mut terminal = ratatui::init();
+        let result = run(&mut terminal, app);
+        ratatui::restore();
+        result
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn load(args: &Args) -> Result<App> {
+    let mut scheduler = match &args.snapshot {
+        Some(path) => Scheduler::load_snapshot(path)?,
+        None => Scheduler::new(),
+    };
+    for task in parse_tasks(&args.tasks)?.into_iter().rev() {
+        scheduler.push_task(task)?;
+    }
+
+    Ok(App::new(scheduler, args.speed))
+}
+
+fn run(terminal: &mut DefaultTerminal, mut app: App) -> Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, &app, Instant::now()))?;
+
+        if event::poll(FRAME_TIME)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.handle_key(key.code, Instant::now()) {
+                    return Ok(());
+                }
+            }
+        }
+        app.tick(Instant::now());
+    }
+}
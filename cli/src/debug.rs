@@ -0,0 +1,386 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use clap::Args;
+use scheduler::inspect::{describe_frame, describe_value};
+use scheduler::{Error, Result, Scheduler, SchedulerTask, task_type};
+
+use crate::run::parse_tasks;
+
+#[derive(Debug, Args)]
+pub struct DebugArgs {
+    /// Root tasks as JSON, or `@path` to read them from a file; the first task runs first
+    pub tasks: Vec<String>,
+
+    /// Start from a snapshot file instead of an empty scheduler
+    #[arg(long)]
+    pub snapshot: Option<PathBuf>,
+}
+
+const HELP: &str = "\
+commands:
+  step [n]            execute the next n tasks (default 1)
+  continue            execute until a breakpoint, an error or no tasks are left
+  break <type>        stop before executing a task of this type
+  delete <type>       remove a breakpoint
+  breakpoints         list breakpoints
+  watch               toggle printing the top of the data stack after every step
+  tasks               print the pending tasks, next first
+  data                print the data stack, top first
+  push-task <json>    push a task, e.g. {\"type\":\"Fib\",\"n\":5}
+  edit-task <json>    replace the next task
+  push-data <json>    push a JSON value onto the data stack
+  pop-data            pop and print the top of the data stack
+  edit-data <json>    replace the top of the data stack
+  save <path>         save a snapshot
+  load <path>         replace the scheduler with a saved snapshot
+  help                print this help
+  quit                leave the debugger";
+
+pub fn debug(args: &DebugArgs) -> Result<()> {
+    let mut scheduler = match &args.snapshot {
+        Some(path) => Scheduler::load_snapshot(path)?,
+        None => Scheduler::new(),
+    };
+    for task in parse_tasks(&args.tasks)?.into_iter().rev() {
+        scheduler.push_task(task)?;
+    }
+
+    let mut debugger = Debugger::new(scheduler);
+    let mut stdout = io::stdout();
+    let mut lines = io::stdin().lock().lines();
+
+    loop {
+        write!(stdout, "(debug) ")?;
+        stdout.flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            writeln!(stdout)?;
+            return Ok(());
+        };
+        if !debugger.command(&line, &mut stdout)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Interactive debugger wrapping a [`Scheduler`].
+///
+/// Scheduler errors are printed rather than returned, so a failing step
+/// leaves the session open for looking at what went wrong.
+#[derive(Debug)]
+pub struct Debugger {
+    pub scheduler: Scheduler,
+    /// Task types to stop before.
+    pub breakpoints: BTreeSet<String>,
+    /// Whether to print the top of the data stack after every step.
+    pub watch: bool,
+    /// Number of steps executed in this session.
+    pub steps: u64,
+}
+
+impl Debugger {
+    /// Wraps `scheduler`, turning on task backtraces for failed steps.
+    pub fn new(mut scheduler: Scheduler) -> Self {
+        scheduler.set_backtraces(true);
+        Self {
+            scheduler,
+            breakpoints: BTreeSet::new(),
+            watch: false,
+            steps: 0,
+        }
+    }
+
+    /// Runs one command line, returning `false` once the session should end.
+    pub fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let line = line.trim();
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let argument = argument.trim();
+
+        let result = match command {
+            "" => Ok(()),
+            "step" | "s" => match argument {
+                "" => self.step(out, 1),
+                count => match count.parse() {
+                    Ok(count) => self.step(out, count),
+                    Err(_) => writeln!(out, "expected a number of steps").map_err(Into::into),
+                },
+            },
+            "continue" | "c" => self.resume(out),
+            "break" | "b" if !argument.is_empty() => {
+                self.breakpoints.insert(argument.to_string());
+                Ok(())
+            }
+            "delete" | "d" if !argument.is_empty() => {
+                if !self.breakpoints.remove(argument) {
+                    writeln!(out, "no breakpoint on {}", argument)?;
+                }
+                Ok(())
+            }
+            "breakpoints" => {
+                for breakpoint in &self.breakpoints {
+                    writeln!(out, "  {}", breakpoint)?;
+                }
+                Ok(())
+            }
+            "watch" | "w" => {
+                self.watch = !self.watch;
+                writeln!(out, "watch {}", if self.watch { "on" } else { "off" })?;
+                Ok(())
+            }
+            "tasks" | "t" => self.print_tasks(out),
+            "data" => self.print_data(out),
+            "push-task" if !argument.is_empty() => self.push_task(argument),
+            "edit-task" if !argument.is_empty() => self.edit_task(argument),
+            "push-data" if !argument.is_empty() => self.push_data(argument),
+            "pop-data" => self.pop_data(out),
+            "edit-data" if !argument.is_empty() => self.edit_data(argument),
+            "save" if !argument.is_empty() => self.scheduler.save_snapshot(argument),
+            "load" if !argument.is_empty() => {
+                Scheduler::load_snapshot(argument).map(|mut scheduler| {
+                    scheduler.set_backtraces(true);
+                    self.scheduler = scheduler;
+                })
+            }
+            "help" | "h" => writeln!(out, "{}", HELP).map_err(Into::into),
+            "quit" | "q" => return Ok(false),
+            _ => writeln!(out, "unknown command {:?}; try `help`", line).map_err(Into::into),
+        };
+
+        if let Err(e) = result {
+            writeln!(out, "error: {}", e)?;
+        }
+        Ok(true)
+    }
+
+    /// Executes up to `count` steps.
+    fn step(&mut self, out: &mut impl Write, count: u64) -> Result<()> {
+        for _ in 0..count {
+            if self.scheduler.is_empty() {
+                writeln!(out, "no tasks left")?;
+                break;
+            }
+            self.execute(out, true)?;
+        }
+        Ok(())
+    }
+
+    /// Executes steps until a breakpoint is hit or no tasks are left.
+    ///
+    /// Steps are only printed while watching.
+    fn resume(&mut self, out: &mut impl Write) -> Result<()> {
+        let start = self.steps;
+
+        while !self.scheduler.is_empty() {
+            if self.steps > start {
+                if let Some(next) = self.next_task_type() {
+                    if self.breakpoints.contains(&next) {
+                        writeln!(out, "breakpoint before {}", self.next_task())?;
+                        return Ok(());
+                    }
+                }
+            }
+            self.execute(out, self.watch)?;
+        }
+
+        writeln!(out, "no tasks left after {} steps", self.steps - start)?;
+        Ok(())
+    }
+
+    fn execute(&mut self, out: &mut impl Write, print: bool) -> Result<()> {
+        self.steps += 1;
+        if print {
+            writeln!(out, "step {}: {}", self.steps, self.next_task())?;
+        }
+
+        self.scheduler.execute()?;
+
+        if self.watch {
+            let top = self.scheduler.stack().front_frames()?.pop();
+            let top = top.map_or("<empty>".to_string(), |frame| describe_frame(&frame));
+            writeln!(out, "  data top: {}", top)?;
+        }
+        Ok(())
+    }
+
+    fn next_task(&self) -> String {
+        let frames = self.scheduler.stack().back_frames().unwrap_or_default();
+        frames
+            .last()
+            .map_or("<none>".to_string(), |frame| describe_frame(frame))
+    }
+
+    fn next_task_type(&self) -> Option<String> {
+        let frames = self.scheduler.stack().back_frames().ok()?;
+        task_type(frames.last()?).ok()
+    }
+
+    fn print_tasks(&self, out: &mut impl Write) -> Result<()> {
+        let frames = self.scheduler.stack().back_frames()?;
+        writeln!(out, "{} pending tasks, next first:", frames.len())?;
+        for (i, frame) in frames.iter().rev().enumerate() {
+            writeln!(out, "  {}: {}", i, describe_frame(frame))?;
+        }
+        Ok(())
+    }
+
+    fn print_data(&self, out: &mut impl Write) -> Result<()> {
+        let frames = self.scheduler.stack().front_frames()?;
+        writeln!(out, "{} data frames, top first:", frames.len())?;
+        for (i, frame) in frames.iter().rev().enumerate() {
+            writeln!(out, "  {}: {}", i, describe_frame(frame))?;
+        }
+        Ok(())
+    }
+
+    fn push_task(&mut self, json: &str) -> Result<()> {
+        let task = parse_task(json)?;
+        self.scheduler.push_task(task)
+    }
+
+    /// Replaces the next task, leaving it untouched if `json` is invalid.
+    fn edit_task(&mut self, json: &str) -> Result<()> {
+        let task = parse_task(json)?;
+        self.scheduler.pop_task()?;
+        self.scheduler.push_task(task)
+    }
+
+    fn push_data(&mut self, json: &str) -> Result<()> {
+        let value = parse_data(json)?;
+        self.scheduler.push_data(&value)
+    }
+
+    /// Replaces the top of the data stack, leaving it untouched if `json` is invalid.
+    fn edit_data(&mut self, json: &str) -> Result<()> {
+        let value = parse_data(json)?;
+        self.scheduler.pop_data::<ciborium::Value>()?;
+        self.scheduler.push_data(&value)
+    }
+
+    fn pop_data(&mut self, out: &mut impl Write) -> Result<()> {
+        let value = self.scheduler.pop_data::<ciborium::Value>()?;
+        writeln!(out, "{}", describe_value(&value))?;
+        Ok(())
+    }
+}
+
+fn parse_task(json: &str) -> Result<Box<dyn SchedulerTask>> {
+    parse_tasks(&[json.to_string()])?
+        .pop()
+        .ok_or_else(|| Error::InvalidData("no task given".to_string()))
+}
+
+fn parse_data(json: &str) -> Result<serde_json::Value> {
+    serde_json::from_str(json).map_err(|e| Error::InvalidData(format!("{}: {}", json, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(debugger: &mut Debugger, commands: &[&str]) -> String {
+        let mut out = Vec::new();
+        for command in commands {
+            assert!(debugger.command(command, &mut out).unwrap());
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    fn debugger() -> Debugger {
+        let mut debugger = Debugger::new(Scheduler::new());
+        run(&mut debugger, &[r#"push-task {"type":"Fib","n":4}"#]);
+        debugger
+    }
+
+    #[test]
+    fn test_step_and_watch() {
+        let mut debugger = debugger();
+
+        let output = run(&mut debugger, &["step", "watch", "step 2", "tasks"]);
+        assert!(output.contains("step 1: Fib {n: 4}"));
+        assert!(output.contains("step 2: Fib {n: 3}"));
+        assert!(output.contains("  data top: <empty>"));
+        assert!(output.contains("pending tasks, next first:\n  0: Fib {n: 1}"));
+        assert_eq!(debugger.steps, 3);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = debugger();
+
+        let output = run(&mut debugger, &["break FibCombiner", "continue"]);
+        assert!(output.ends_with("breakpoint before FibCombiner {}\n"));
+
+        let output = run(&mut debugger, &["delete FibCombiner", "continue", "data"]);
+        assert!(output.contains("no tasks left"));
+        assert!(output.ends_with("1 data frames, top first:\n  0: 3\n"));
+    }
+
+    #[test]
+    fn test_edit_data() {
+        let mut debugger = Debugger::new(Scheduler::new());
+
+        let output = run(
+            &mut debugger,
+            &[
+                "push-data 2",
+                "push-data 3",
+                "edit-data 40",
+                r#"push-task {"type":"Add","x":0,"y":0}"#,
+                "pop-data",
+            ],
+        );
+        assert_eq!(output, "40\n");
+        assert!(run(&mut debugger, &["pop-data", "pop-data"]).starts_with("2\nerror: "));
+
+        // Invalid JSON leaves the top of the data stack in place
+        let output = run(&mut debugger, &["push-data 7", "edit-data {", "pop-data"]);
+        assert!(output.starts_with("error: "));
+        assert!(output.ends_with("7\n"));
+    }
+
+    #[test]
+    fn test_edit_task() {
+        let mut debugger = debugger();
+
+        let output = run(
+            &mut debugger,
+            &[r#"edit-task {"type":"Fib","n":6}"#, "continue", "pop-data"],
+        );
+        assert!(output.ends_with("8\n"));
+
+        // Invalid JSON leaves the next task in place
+        let output = run(
+            &mut debugger,
+            &[r#"push-task {"type":"Fib","n":5}"#, "edit-task {", "tasks"],
+        );
+        assert!(output.starts_with("error: "));
+        assert!(output.ends_with("  0: Fib {n: 5}\n"));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("session.snapshot");
+        let path = path.to_str().unwrap();
+
+        let mut debugger = debugger();
+        run(&mut debugger, &[&format!("save {}", path), "continue"]);
+        assert!(debugger.scheduler.is_empty());
+
+        run(&mut debugger, &[&format!("load {}", path)]);
+        assert!(!debugger.scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_errors_keep_the_session_open() {
+        let mut debugger = Debugger::new(Scheduler::new());
+
+        let output = run(&mut debugger, &["push-task {}", "bogus", "step"]);
+        assert!(output.starts_with("error: "));
+        assert!(output.contains("unknown command \"bogus\""));
+        assert!(output.contains("no tasks left"));
+        assert!(!debugger.command("quit", &mut Vec::new()).unwrap());
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use clap::Args;
+use scheduler::inspect::describe_frame;
+use scheduler::schema::schema_version;
+use scheduler::snapshot::read_snapshot;
+use scheduler::validate::{check_data_frame, check_task_frame};
+use scheduler::{Error, Result};
+
+/// Size of the length header preceding every frame.
+const LENGTH_SIZE: usize = 2;
+
+#[derive(Debug, Args)]
+pub struct InspectArgs {
+    /// Snapshot file written by `Scheduler::save_snapshot`
+    pub snapshot: PathBuf,
+}
+
+pub fn inspect(args: &InspectArgs) -> Result<()> {
+    let snapshot = fs::read(&args.snapshot)?;
+    let inspection = Inspection::new(&snapshot)?;
+    print!("{}", inspection);
+
+    match inspection.problems() {
+        0 => Ok(()),
+        problems => Err(Error::InvalidSnapshot(format!(
+            "{} integrity problems",
+            problems
+        ))),
+    }
+}
+
+/// Contents of a snapshot, frame by frame.
+#[derive(Debug)]
+pub struct Inspection {
+    /// Size of the whole snapshot in bytes.
+    pub size: usize,
+    /// Size of the stack buffer in bytes.
+    pub capacity: usize,
+    pub front_index: usize,
+    pub back_index: usize,
+    /// Recorded schema version of every task type on the stack.
+    pub schema: Vec<(String, u32)>,
+    /// Data frames, bottom of the stack first.
+    pub data: Vec<Frame>,
+    /// Task frames, bottom of the stack first; the last one runs next.
+    pub tasks: Vec<Frame>,
+    /// Problems with the stack layout itself.
+    pub layout: Vec<String>,
+}
+
+/// A frame of an inspected snapshot.
+#[derive(Debug)]
+pub struct Frame {
+    /// Offset of the frame's first byte in the stack buffer.
+    pub offset: usize,
+    pub length: usize,
+    pub description: String,
+    /// Why the frame can't be loaded, if it can't.
+    pub problem: Option<String>,
+}
+
+impl Inspection {
+    /// Reads a snapshot and checks the integrity of every frame.
+    ///
+    /// Fails only if the snapshot header can't be read; broken frames are
+    /// reported as problems instead.
+    pub fn new(snapshot: &[u8]) -> Result<Self> {
+        let (stack, schema) = read_snapshot(snapshot)?;
+        let capacity = stack.as_bytes().len();
+        let mut layout = Vec::new();
+
+        let data = match stack.front_frames() {
+            Ok(frames) => {
+                let mut offset = 0;
+                frames
+                    .iter()
+                    .map(|frame| {
+                        let described = describe(offset, frame, check_data_frame(frame));
+                        offset += frame.len() + LENGTH_SIZE;
+                        described
+                    })
+                    .collect()
+            }
+            Err(e) => {
+                layout.push(format!("data stack: {}", e));
+                Vec::new()
+            }
+        };
+
+        let tasks = match stack.back_frames() {
+            Ok(frames) => {
+                let mut end = capacity;
+                frames
+                    .iter()
+                    .map(|frame| {
+                        end -= frame.len();
+                        let described = describe(end, frame, check_task_frame(frame));
+                        end -= LENGTH_SIZE;
+                        described
+                    })
+                    .collect()
+            }
+            Err(e) => {
+                layout.push(format!("task stack: {}", e));
+                Vec::new()
+            }
+        };
+
+        Ok(Self {
+            size: snapshot.len(),
+            capacity,
+            front_index: stack.front_index(),
+            back_index: stack.back_index(),
+            schema,
+            data,
+            tasks,
+            layout,
+        })
+    }
+
+    /// Returns the number of layout and frame problems found.
+    pub fn problems(&self) -> usize {
+        let frames = self.data.iter().chain(&self.tasks);
+        self.layout.len() + frames.filter(|frame| frame.problem.is_some()).count()
+    }
+}
+
+fn describe(offset: usize, frame: &[u8], problem: Option<String>) -> Frame {
+    Frame {
+        offset,
+        length: frame.len(),
+        description: describe_frame(frame),
+        problem,
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let free = self.back_index.saturating_sub(self.front_index);
+        writeln!(f, "snapshot: {} bytes", self.size)?;
+        writeln!(
+            f,
+            "stack: {} bytes, front index {}, back index {}, {} free",
+            self.capacity, self.front_index, self.back_index, free
+        )?;
+
+        write!(f, "schema:")?;
+        for (i, (task_type, version)) in self.schema.iter().enumerate() {
+            let separator = if i > 0 { "," } else { "" };
+            write!(f, "{} {} v{}", separator, task_type, version)?;
+            let current = schema_version(task_type);
+            if current != *version {
+                write!(f, " (current v{})", current)?;
+            }
+        }
+        writeln!(f)?;
+
+        writeln!(f, "data frames ({}, top last):", self.data.len())?;
+        for frame in &self.data {
+            writeln!(f, "{}", frame)?;
+        }
+        writeln!(f, "task frames ({}, next last):", self.tasks.len())?;
+        for frame in &self.tasks {
+            writeln!(f, "{}", frame)?;
+        }
+
+        for problem in &self.layout {
+            writeln!(f, "! {}", problem)?;
+        }
+        match self.problems() {
+            0 => writeln!(f, "integrity: ok"),
+            problems => writeln!(f, "integrity: {} problems", problems),
+        }
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "  @{:<6} {:>5} bytes  {}",
+            self.offset, self.length, self.description
+        )?;
+        if let Some(problem) = &self.problem {
+            write!(f, "\n           ! {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scheduler::Scheduler;
+    use tasks::fib::Fib;
+
+    fn snapshot() -> Vec<u8> {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_data(&"bottom").unwrap();
+        scheduler.push_data(&7_u8).unwrap();
+        scheduler.push_task(Box::new(Fib::new(3))).unwrap();
+        scheduler.execute().unwrap();
+        scheduler.snapshot()
+    }
+
+    #[test]
+    fn test_lists_frames_with_offsets() {
+        let inspection = Inspection::new(&snapshot()).unwrap();
+
+        let data: Vec<_> = inspection
+            .data
+            .iter()
+            .map(|frame| (frame.offset, frame.description.as_str()))
+            .collect();
+        assert_eq!(data, [(0, "\"bottom\""), (9, "7")]);
+
+        let tasks: Vec<_> = inspection
+            .tasks
+            .iter()
+            .map(|frame| frame.description.as_str())
+            .collect();
+        assert_eq!(tasks, ["FibCombiner {}", "Fib {n: 1}", "Fib {n: 2}"]);
+        assert_eq!(
+            inspection.tasks[0].offset + inspection.tasks[0].length,
+            inspection.capacity
+        );
+        assert_eq!(
+            inspection.tasks[2].offset - LENGTH_SIZE,
+            inspection.back_index
+        );
+
+        assert_eq!(inspection.problems(), 0);
+        assert!(inspection.to_string().contains("integrity: ok"));
+    }
+
+    #[test]
+    fn test_reports_unknown_tasks() {
+        let mut snapshot = snapshot();
+
+        // Task frames are stored reversed; rename `FibCombiner` to `GibCombiner`
+        let name = b"FibCombiner".iter().rev().copied().collect::<Vec<_>>();
+        let position = snapshot
+            .windows(name.len())
+            .position(|window| window == name)
+            .unwrap();
+        snapshot[position + name.len() - 1] = b'G';
+
+        let inspection = Inspection::new(&snapshot).unwrap();
+        assert_eq!(inspection.problems(), 1);
+        assert_eq!(inspection.tasks[0].description, "GibCombiner {}");
+        assert!(inspection.tasks[0].problem.is_some());
+    }
+}
@@ -0,0 +1,19 @@
+//! Command-line tools for running and examining scheduler task graphs.
+//!
+//! Tasks are given as JSON using their typetag `"type"` tag, e.g.
+//! `{"type":"Fib","n":20}`. Every task type of the `tasks` crate is available.
+
+/// Interactive step debugger
+pub mod debug;
+
+/// Lists and checks the frames of a snapshot
+pub mod inspect;
+
+/// Runs root tasks and prints the resulting data stack
+pub mod run;
+
+/// Terminal UI showing both stacks live
+pub mod tui;
+
+// Link the example tasks so their types are registered with typetag
+use tasks as _;
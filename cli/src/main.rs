@@ -0,0 +1,39 @@
+//! Runs and examines scheduler task graphs from the command line.
+
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use scheduler_cli::{debug, inspect, run};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Run and examine scheduler task graphs")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run root tasks to completion and print the resulting data stack
+    Run(run::RunArgs),
+    /// List the frames of a snapshot file and check their integrity
+    Inspect(inspect::InspectArgs),
+    /// Step through tasks interactively
+    Debug(debug::DebugArgs),
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Run(args) => run::run(&args),
+        Command::Inspect(args) => inspect::inspect(&args),
+        Command::Debug(args) => debug::debug(&args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
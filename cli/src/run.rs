@@ -0,0 +1,267 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ciborium::Value;
+use clap::Args;
+use scheduler::inspect::describe_value;
+use scheduler::observer::{Event, Observer};
+use scheduler::stack::StackError;
+use scheduler::{
+    Error, Metrics, Result, SCHEDULER_CAPACITY, Scheduler, SchedulerStack, SchedulerTask,
+};
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// Root tasks as JSON, or `@path` to read them from a file; the first task runs first
+    #[arg(required = true)]
+    pub tasks: Vec<String>,
+
+    /// Stop after this many steps, leaving the remaining tasks pending
+    #[arg(long)]
+    pub max_steps: Option<u64>,
+
+    /// Fail once tasks and data take more than this many bytes of the stack
+    #[arg(long, default_value_t = SCHEDULER_CAPACITY)]
+    pub capacity: usize,
+
+    /// Save a snapshot of the scheduler to this file once the run stops
+    #[arg(long)]
+    pub save_snapshot: Option<PathBuf>,
+
+    /// Print execution metrics per task type once the run stops
+    #[arg(long)]
+    pub metrics: bool,
+}
+
+pub fn run(args: &RunArgs) -> Result<()> {
+    let mut scheduler = Scheduler::new();
+    scheduler.set_backtraces(true);
+    scheduler.add_observer(Usage::new(args.capacity));
+    if args.metrics {
+        scheduler.add_observer(Metrics::new(&scheduler));
+    }
+
+    let tasks = parse_tasks(&args.tasks)?;
+    let report = execute(
+        &mut scheduler,
+        tasks,
+        args.max_steps,
+        args.save_snapshot.as_deref(),
+    );
+    print!("{}", report);
+    if let Some(metrics) = scheduler.observer::<Metrics>() {
+        print!("{}", metrics);
+    }
+
+    report.error.map_or(Ok(()), Err)
+}
+
+/// Parses root tasks given as JSON or as `@path` references to JSON files.
+///
+/// A file may hold a single task or an array of tasks.
+pub fn parse_tasks(arguments: &[String]) -> Result<Vec<Box<dyn SchedulerTask>>> {
+    let mut tasks = Vec::new();
+
+    for argument in arguments {
+        let json = match argument.strip_prefix('@') {
+            Some(path) => fs::read_to_string(path)?,
+            None => argument.clone(),
+        };
+
+        let invalid = |e: serde_json::Error| Error::InvalidData(format!("{}: {}", argument, e));
+        if json.trim_start().starts_with('[') {
+            tasks.extend(
+                serde_json::from_str::<Vec<Box<dyn SchedulerTask>>>(&json).map_err(invalid)?,
+            );
+        } else {
+            tasks.push(serde_json::from_str(&json).map_err(invalid)?);
+        }
+    }
+
+    Ok(tasks)
+}
+
+/// Pushes the root tasks and executes them until they're done, a step fails
+/// or `max_steps` steps have run, then saves a snapshot to `save_snapshot`.
+pub fn execute(
+    scheduler: &mut Scheduler,
+    tasks: Vec<Box<dyn SchedulerTask>>,
+    max_steps: Option<u64>,
+    save_snapshot: Option<&Path>,
+) -> Report {
+    let mut error = None;
+
+    // The task stack runs the last pushed task first
+    for task in tasks.into_iter().rev() {
+        if let Err(e) = scheduler.push_task(task) {
+            error = Some(e);
+            break;
+        }
+    }
+
+    while error.is_none() && !scheduler.is_empty() {
+        let steps = scheduler.observer::<Usage>().map_or(0, |usage| usage.steps);
+        if max_steps.is_some_and(|max_steps| steps >= max_steps) {
+            break;
+        }
+        if let Err(e) = scheduler.execute() {
+            error = Some(e);
+        }
+    }
+
+    if let Some(path) = save_snapshot {
+        if let Err(e) = scheduler.save_snapshot(path) {
+            error.get_or_insert(e);
+        }
+    }
+
+    let usage = scheduler.observer::<Usage>().cloned().unwrap_or_default();
+    let mut pending = 0;
+    while !scheduler.is_empty() && scheduler.pop_task().is_ok() {
+        pending += 1;
+    }
+
+    let mut data = Vec::new();
+    while !scheduler.is_empty_data() {
+        match scheduler.pop_data::<Value>() {
+            Ok(value) => data.push(describe_value(&value)),
+            Err(e) => {
+                error.get_or_insert(e);
+                break;
+            }
+        }
+    }
+    data.reverse();
+
+    Report {
+        data,
+        pending,
+        usage,
+        error,
+    }
+}
+
+/// Outcome of a run.
+#[derive(Debug)]
+pub struct Report {
+    /// Descriptions of the data frames left on the stack, bottom first.
+    pub data: Vec<String>,
+    /// Number of tasks left pending.
+    pub pending: usize,
+    /// Steps run and peak stack usage.
+    pub usage: Usage,
+    /// The error that ended the run early, if any.
+    pub error: Option<Error>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "data stack ({} frames, top last):", self.data.len())?;
+        for (i, data) in self.data.iter().enumerate() {
+            writeln!(f, "  {}: {}", i, data)?;
+        }
+
+        writeln!(f, "steps: {}", self.usage.steps)?;
+        if self.pending > 0 {
+            writeln!(f, "pending tasks: {}", self.pending)?;
+        }
+        writeln!(
+            f,
+            "peak stack usage: {} of {} bytes",
+            self.usage.peak, self.usage.capacity
+        )
+    }
+}
+
+/// Observer counting steps and tracking the peak number of bytes in use.
+///
+/// Fails any push that takes the stack past `capacity` bytes.
+#[derive(Debug, Clone, Default)]
+pub struct Usage {
+    /// Number of steps run, including a failed one.
+    pub steps: u64,
+    /// Largest number of bytes taken by tasks and data together.
+    pub peak: usize,
+    /// Number of bytes tasks and data may take together.
+    pub capacity: usize,
+}
+
+impl Usage {
+    /// Creates an observer enforcing `capacity`.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+}
+
+impl Observer for Usage {
+    fn on_event(&mut self, event: &Event<'_>, stack: &SchedulerStack) -> Result<()> {
+        if matches!(event, Event::StepFinished | Event::StepFailed) {
+            self.steps += 1;
+        }
+
+        let used = SCHEDULER_CAPACITY - stack.available_capacity();
+        self.peak = self.peak.max(used);
+        if used > self.capacity {
+            return Err(Error::StackCapacity(StackError::InsufficientCapacity));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_json(json: &str, max_steps: Option<u64>, capacity: usize) -> Report {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_backtraces(true);
+        scheduler.add_observer(Usage::new(capacity));
+
+        let tasks = parse_tasks(&[json.to_string()]).unwrap();
+        execute(&mut scheduler, tasks, max_steps, None)
+    }
+
+    #[test]
+    fn test_runs_json_tasks_in_order() {
+        let report = run_json(
+            r#"[{"type":"Fib","n":10},{"type":"Add","x":1,"y":2}]"#,
+            None,
+            SCHEDULER_CAPACITY,
+        );
+
+        assert!(report.error.is_none());
+        assert_eq!(report.data, ["55", "3"]);
+        assert!(report.usage.steps > 100);
+        assert!(report.usage.peak > 0);
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let report = run_json(r#"{"type":"Fib","n":10}"#, Some(5), SCHEDULER_CAPACITY);
+
+        assert!(report.error.is_none());
+        assert_eq!(report.usage.steps, 5);
+        assert!(report.pending > 0);
+        assert!(report.to_string().contains("pending tasks"));
+    }
+
+    #[test]
+    fn test_capacity_limit() {
+        let report = run_json(r#"{"type":"Fib","n":20}"#, None, 64);
+
+        // The push that took the stack past the limit failed the step
+        let error = report.error.unwrap();
+        assert!(matches!(error, Error::TaskFailed { .. }));
+        assert!(matches!(error.without_backtrace(), Error::StackCapacity(_)));
+        assert!(report.usage.peak > 64);
+    }
+
+    #[test]
+    fn test_rejects_unknown_task() {
+        assert!(parse_tasks(&[r#"{"type":"Missing"}"#.to_string()]).is_err());
+    }
+}
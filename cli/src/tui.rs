@@ -0,0 +1,376 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use ratatui::Frame;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, Paragraph};
+use scheduler::inspect::describe_frame;
+use scheduler::{Scheduler, SchedulerStack, SpawnRecorder};
+
+/// Fastest playback speed in steps per second.
+const MAX_SPEED: u32 = 100_000;
+
+/// State of the terminal UI: the scheduler and the playback controls.
+#[derive(Debug)]
+pub struct App {
+    pub scheduler: Scheduler,
+    /// Whether steps are executed continuously.
+    pub playing: bool,
+    /// Playback speed in steps per second.
+    pub speed: u32,
+    /// Number of steps executed so far.
+    pub steps: u64,
+    /// The error of the step that failed, if any; playback stops on errors.
+    pub error: Option<String>,
+    /// When the steps of the last second were executed.
+    step_times: VecDeque<Instant>,
+    last_tick: Option<Instant>,
+    /// Steps owed to the playback speed since the last tick.
+    budget: f64,
+}
+
+impl App {
+    /// Wraps `scheduler`, attaching a [`SpawnRecorder`] to it.
+    pub fn new(mut scheduler: Scheduler, speed: u32) -> Self {
+        scheduler.add_observer(SpawnRecorder::new(&scheduler));
+
+        Self {
+            scheduler,
+            playing: false,
+            speed: speed.clamp(1, MAX_SPEED),
+            steps: 0,
+            error: None,
+            step_times: VecDeque::new(),
+            last_tick: None,
+            budget: 0.0,
+        }
+    }
+
+    /// Executes a single step, stopping playback once no tasks are left or the step fails.
+    pub fn step(&mut self, now: Instant) {
+        if self.scheduler.is_empty() {
+            self.playing = false;
+            return;
+        }
+
+        match self.scheduler.execute() {
+            Ok(()) => {
+                self.steps += 1;
+                self.step_times.push_back(now);
+                while self
+                    .step_times
+                    .front()
+                    .is_some_and(|time| now.duration_since(*time) > Duration::from_secs(1))
+                {
+                    self.step_times.pop_front();
+                }
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                self.playing = false;
+            }
+        }
+    }
+
+    /// Executes the steps due at `now` according to the playback speed.
+    pub fn tick(&mut self, now: Instant) {
+        let elapsed = self
+            .last_tick
+            .map_or(Duration::ZERO, |last| now.duration_since(last));
+        self.last_tick = Some(now);
+
+        if !self.playing {
+            self.budget = 0.0;
+            return;
+        }
+
+        // Never catch up on more than a second's worth of steps
+        let speed = f64::from(self.speed);
+        self.budget = (self.budget + elapsed.as_secs_f64() * speed).min(speed);
+        while self.playing && self.budget >= 1.0 {
+            self.budget -= 1.0;
+            self.step(now);
+        }
+    }
+
+    /// Handles a key press, returning `false` once the UI should close.
+    pub fn handle_key(&mut self, key: KeyCode, now: Instant) -> bool {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char(' ') | KeyCode::Char('p') => {
+                self.playing = !self.playing && !self.scheduler.is_empty() && self.error.is_none();
+            }
+            KeyCode::Char('s') | KeyCode::Right => {
+                self.playing = false;
+                self.step(now);
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                self.speed = self.speed.saturating_mul(2).min(MAX_SPEED);
+            }
+            KeyCode::Char('-') => self.speed = (self.speed / 2).max(1),
+            _ => {}
+        }
+        true
+    }
+
+    /// Returns the number of steps executed during the second before `now`.
+    pub fn rate(&self, now: Instant) -> usize {
+        self.step_times
+            .iter()
+            .filter(|time| now.duration_since(**time) <= Duration::from_secs(1))
+            .count()
+    }
+}
+
+/// A line of the rendered spawn tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeLine {
+    pub depth: usize,
+    pub label: String,
+    /// Whether the task is still waiting to execute.
+    pub pending: bool,
+}
+
+/// Returns the pending tasks and the tasks that spawned them, depth first in
+/// execution order.
+pub fn spawn_tree(recorder: &SpawnRecorder) -> Vec<TreeLine> {
+    let nodes = recorder.nodes();
+    let pending: BTreeSet<usize> = recorder.pending().iter().copied().collect();
+    let mut live = BTreeSet::new();
+    for id in &pending {
+        let mut node = Some(*id);
+        while let Some(id) = node {
+            if !live.insert(id) {
+                break;
+            }
+            node = nodes[id].parent;
+        }
+    }
+
+    // Tasks pushed later execute earlier, so visit the newest first
+    let mut lines = Vec::new();
+    let mut stack: Vec<(usize, usize)> = live
+        .iter()
+        .filter(|id| nodes[**id].parent.is_none())
+        .map(|id| (*id, 0))
+        .collect();
+    while let Some((id, depth)) = stack.pop() {
+        lines.push(TreeLine {
+            depth,
+            label: describe_frame(&nodes[id].frame),
+            pending: pending.contains(&id),
+        });
+        stack.extend(
+            nodes[id]
+                .children
+                .iter()
+                .filter(|child| live.contains(child))
+                .map(|child| (*child, depth + 1)),
+        );
+    }
+    lines
+}
+
+/// Draws the whole UI.
+pub fn draw(frame: &mut Frame, app: &App, now: Instant) {
+    let [header, stack, body, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(4),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let state = if app.playing {
+        "▶ playing"
+    } else {
+        "⏸ paused"
+    };
+    frame.render_widget(
+        Paragraph::new(format!(
+            " step {}  {}  {} steps/s (target {})",
+            app.steps,
+            state,
+            app.rate(now),
+            app.speed
+        ))
+        .bold(),
+        header,
+    );
+
+    frame.render_widget(stack_bar(app.scheduler.stack(), stack.width), stack);
+
+    let [data, tree, tasks] = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(45),
+        Constraint::Percentage(30),
+    ])
+    .areas(body);
+
+    let stack = app.scheduler.stack();
+    let data_frames = stack.front_frames().unwrap_or_default();
+    frame.render_widget(
+        List::new(data_frames.iter().rev().map(|data| describe_frame(data)))
+            .block(Block::bordered().title(format!(" Data ({}, top first) ", data_frames.len()))),
+        data,
+    );
+
+    let lines = app
+        .scheduler
+        .observer::<SpawnRecorder>()
+        .map(spawn_tree)
+        .unwrap_or_default();
+    let tree_lines = lines.iter().map(|line| {
+        let text = format!("{}{}", "  ".repeat(line.depth), line.label);
+        if line.pending {
+            Line::styled(text, Style::new().fg(Color::Cyan))
+        } else {
+            Line::styled(text, Style::new().fg(Color::DarkGray))
+        }
+    });
+    frame.render_widget(
+        List::new(tree_lines).block(Block::bordered().title(" Spawn tree ")),
+        tree,
+    );
+
+    let task_frames = stack.back_frames().unwrap_or_default();
+    frame.render_widget(
+        List::new(task_frames.iter().rev().map(|task| describe_frame(task)))
+            .block(Block::bordered().title(format!(" Tasks ({}, next first) ", task_frames.len()))),
+        tasks,
+    );
+
+    let help = " space play/pause  s step  +/- speed  q quit";
+    let footer_line = match &app.error {
+        Some(error) => Line::from(vec![
+            Span::styled(format!(" error: {} ", error), Style::new().fg(Color::Red)),
+            Span::raw(help),
+        ]),
+        None if app.scheduler.is_empty() => Line::from(format!(" no tasks left {}", help)),
+        None => Line::from(help),
+    };
+    frame.render_widget(Paragraph::new(footer_line), footer);
+}
+
+/// Shows the stack buffer as the data region growing from the left, the task
+/// region growing from the right and the free space between them.
+fn stack_bar(stack: &SchedulerStack, width: u16) -> Paragraph<'static> {
+    let capacity = stack.as_bytes().len();
+    let data = stack.front_index();
+    let tasks = capacity - stack.back_index();
+    let free = capacity - data - tasks;
+
+    // Used regions get at least one cell so they stay visible in a large buffer
+    let width = usize::from(width.saturating_sub(2)).max(3);
+    let cells = |bytes: usize| match bytes {
+        0 => 0,
+        bytes => (bytes * width).div_ceil(capacity).max(1),
+    };
+    let data_cells = cells(data);
+    let task_cells = cells(tasks);
+    let free_cells = width.saturating_sub(data_cells + task_cells);
+
+    let bar = Line::from(vec![
+        Span::styled("█".repeat(data_cells), Style::new().fg(Color::Green)),
+        Span::styled("░".repeat(free_cells), Style::new().fg(Color::DarkGray)),
+        Span::styled("█".repeat(task_cells), Style::new().fg(Color::Cyan)),
+    ]);
+    let legend = Line::from(vec![
+        Span::styled(format!("data {} B", data), Style::new().fg(Color::Green)),
+        Span::raw(format!("  ·  free {} B  ·  ", free)),
+        Span::styled(format!("tasks {} B", tasks), Style::new().fg(Color::Cyan)),
+    ]);
+
+    Paragraph::new(vec![bar, legend]).block(Block::bordered().title(format!(
+        " Stack: front {}, back {} of {} ",
+        stack.front_index(),
+        stack.back_index(),
+        capacity
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+    use tasks::fib::Fib;
+
+    fn app() -> App {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_task(Box::new(Fib::new(3))).unwrap();
+        App::new(scheduler, 10)
+    }
+
+    #[test]
+    fn test_spawn_tree() {
+        let mut app = app();
+        let now = Instant::now();
+        app.step(now);
+        app.step(now);
+
+        let recorder = app.scheduler.observer::<SpawnRecorder>().unwrap();
+        let lines: Vec<_> = spawn_tree(recorder)
+            .into_iter()
+            .map(|line| (line.depth, line.label, line.pending))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (0, "Fib {n: 3}".to_string(), false),
+                (1, "Fib {n: 2}".to_string(), false),
+                (2, "Fib {n: 1}".to_string(), true),
+                (2, "Fib {n: 0}".to_string(), true),
+                (2, "FibCombiner {}".to_string(), true),
+                (1, "Fib {n: 1}".to_string(), true),
+                (1, "FibCombiner {}".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_playback() {
+        let mut app = app();
+        let start = Instant::now();
+
+        app.tick(start);
+        assert!(app.handle_key(KeyCode::Char(' '), start));
+        app.tick(start + Duration::from_millis(250));
+        assert_eq!(app.steps, 2);
+        assert_eq!(app.rate(start + Duration::from_millis(250)), 2);
+
+        assert!(app.handle_key(KeyCode::Char('+'), start));
+        app.tick(start + Duration::from_secs(10));
+        assert!(!app.playing);
+        assert!(app.scheduler.is_empty());
+        assert_eq!(app.scheduler.pop_data::<u128>().unwrap(), 2);
+
+        assert!(!app.handle_key(KeyCode::Char('q'), start));
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut app = app();
+        app.step(Instant::now());
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal
+            .draw(|frame| draw(frame, &app, Instant::now()))
+            .unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("step 1"));
+        assert!(screen.contains("free 65486 B"));
+        assert!(screen.contains("Tasks (3, next first)"));
+        assert!(screen.contains("FibCombiner {}"));
+    }
+}
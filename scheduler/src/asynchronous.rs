@@ -0,0 +1,210 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, Scheduler, SchedulerTask, task_type};
+
+/// Future returned by [`AsyncSchedulerTask::execute`].
+pub type TaskFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<Box<dyn SchedulerTask>>>> + Send + 'a>>;
+
+/// Trait for tasks whose execution can await, e.g. on I/O.
+///
+/// Async tasks live on the same task stack as regular tasks, wrapped in an
+/// [`AsyncTask`], and can only be executed through
+/// [`Scheduler::execute_all_async`].
+#[typetag::serde(tag = "type")]
+pub trait AsyncSchedulerTask: Send + Sync {
+    /// Execute the task and return new tasks to be pushed onto the scheduler.
+    ///
+    /// The scheduler is provided for pushing/popping data during execution.
+    fn execute<'a>(&'a mut self, scheduler: &'a mut Scheduler) -> TaskFuture<'a>;
+
+    fn push_self(&mut self) -> bool {
+        false
+    }
+}
+
+/// A regular task frame carrying an [`AsyncSchedulerTask`].
+#[derive(Serialize, Deserialize)]
+pub struct AsyncTask {
+    /// The wrapped async task
+    pub task: Box<dyn AsyncSchedulerTask>,
+}
+
+impl AsyncTask {
+    /// Wraps an async task so it can be pushed onto the task stack.
+    pub fn new(task: Box<dyn AsyncSchedulerTask>) -> Self {
+        Self { task }
+    }
+}
+
+#[typetag::serde]
+impl SchedulerTask for AsyncTask {
+    fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+        Err(Error::Task(
+            "Async task can only be executed with execute_all_async".to_string(),
+        ))
+    }
+
+    fn push_self(&mut self) -> bool {
+        self.task.push_self()
+    }
+
+    fn as_async(&mut self) -> Option<&mut AsyncTask> {
+        Some(self)
+    }
+}
+
+impl Scheduler {
+    /// Executes the next task in the scheduler, awaiting it if it is async.
+    ///
+    /// Returns an error if there are no tasks or if execution fails.
+    pub async fn execute_async(&mut self) -> Result<()> {
+        self.start_step()?;
+
+        let result = self.execute_step_async().await;
+        self.end_step(result)
+    }
+
+    /// Fails if the next task is async, leaving it pending for
+    /// [`Scheduler::execute_async`].
+    pub(crate) fn check_next_is_sync(&self) -> Result<()> {
+        let frame = self.stack.peek_back()?;
+        if task_type(&frame).is_ok_and(|name| name == "AsyncTask") {
+            return Err(Error::Task(
+                "Async task can only be executed with execute_all_async".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn execute_step_async(&mut self) -> Result<()> {
+        self.select()?;
+        let mut task = self.pop_task()?;
+
+        let tasks = match task.as_async() {
+            Some(async_task) => async_task.task.execute(self).await,
+            None => task.execute(self),
+        }
+        .map_err(|e| Error::Execution(format!("Task execution failed: {}", e)))?;
+
+        self.schedule(task, tasks)
+    }
+
+    /// Executes all tasks in the scheduler until there are no more.
+    ///
+    /// Control is handed back to the tokio runtime every `yield_every` steps,
+    /// so long computations don't starve other tasks on the same runtime.
+    pub async fn execute_all_async(&mut self, yield_every: usize) -> Result<()> {
+        let yield_every = yield_every.max(1);
+        let mut steps = 0_usize;
+
+        while !self.is_empty() {
+            self.execute_async().await?;
+
+            steps += 1;
+            if steps % yield_every == 0 {
+                tokio::task::yield_now().await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Countdown {
+        n: u64,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Countdown {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            self.n -= 1;
+            if self.n == 0 {
+                scheduler.push_data(&"done")?;
+            }
+            Ok(vec![])
+        }
+
+        fn push_self(&mut self) -> bool {
+            self.n > 0
+        }
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Fetch {
+        value: u64,
+    }
+
+    #[typetag::serde]
+    impl AsyncSchedulerTask for Fetch {
+        fn execute<'a>(&'a mut self, scheduler: &'a mut Scheduler) -> TaskFuture<'a> {
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                scheduler.push_data(&self.value)?;
+                Ok(vec![Box::new(Countdown { n: 3 }) as Box<dyn SchedulerTask>])
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_task_spawns_regular_task() {
+        let mut scheduler = Scheduler::new();
+
+        scheduler
+            .push_task(Box::new(AsyncTask::new(Box::new(Fetch { value: 7 }))))
+            .unwrap();
+        scheduler.execute_all_async(16).await.unwrap();
+
+        let done: String = scheduler.pop_data().unwrap();
+        let value: u64 = scheduler.pop_data().unwrap();
+        assert_eq!(done, "done");
+        assert_eq!(value, 7);
+    }
+
+    #[tokio::test]
+    async fn test_async_task_rejected_by_execute() {
+        let mut scheduler = Scheduler::new();
+
+        scheduler
+            .push_task(Box::new(AsyncTask::new(Box::new(Fetch { value: 7 }))))
+            .unwrap();
+
+        assert!(matches!(scheduler.execute(), Err(Error::Task(_))));
+        assert!(scheduler.execute_all().is_err());
+
+        // The task is still pending and runs once executed asynchronously
+        assert!(!scheduler.is_empty());
+        scheduler.execute_all_async(16).await.unwrap();
+        assert_eq!(scheduler.pop_data::<String>().unwrap(), "done");
+    }
+
+    #[tokio::test]
+    async fn test_execute_all_async_yields_to_runtime() {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counter = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                    tokio::task::yield_now().await;
+                }
+            }
+        });
+
+        let mut scheduler = Scheduler::new();
+        scheduler.push_task(Box::new(Countdown { n: 100 })).unwrap();
+        scheduler.execute_all_async(10).await.unwrap();
+        counter.abort();
+
+        assert!(ticks.load(Ordering::Relaxed) >= 5);
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::inspect::describe_frame;
+use crate::{Scheduler, SchedulerStack};
+
+/// Most spawning tasks shown when a [`TaskBacktrace`] is displayed.
+const MAX_SHOWN_TASKS: usize = 16;
+
+/// Most pending tasks recorded in a [`TaskBacktrace`].
+const MAX_NEXT_TASKS: usize = 5;
+
+/// Descriptions are cut to this many characters so large fields stay readable.
+const MAX_DESCRIPTION_LEN: usize = 80;
+
+/// Where a failed task came from and what was left to do.
+///
+/// Attached to the errors of failed steps with [`Scheduler::set_backtraces`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskBacktrace {
+    /// The failed task followed by the chain of tasks that spawned it, root
+    /// last unless the chain was cut.
+    pub tasks: Vec<String>,
+    /// Number of spawning tasks above the last one in `tasks` that weren't kept.
+    pub omitted: usize,
+    /// Number of tasks still pending after the failure.
+    pub pending: usize,
+    /// The next pending tasks, next first.
+    pub next: Vec<String>,
+}
+
+impl TaskBacktrace {
+    fn new(link: &Link, stack: &SchedulerStack) -> Self {
+        let depth = link.depth;
+        let mut tasks = Vec::new();
+        let mut link = Some(link);
+        while let Some(current) = link {
+            tasks.push(describe(&current.frame));
+            link = current.parent.as_deref();
+        }
+
+        let frames = stack.back_frames().unwrap_or_default();
+        let next = frames
+            .iter()
+            .rev()
+            .take(MAX_NEXT_TASKS)
+            .map(|frame| describe(frame))
+            .collect();
+
+        Self {
+            omitted: depth - tasks.len(),
+            tasks,
+            pending: frames.len(),
+            next,
+        }
+    }
+}
+
+impl fmt::Display for TaskBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "task backtrace (failed task first):")?;
+        for (i, task) in self.tasks.iter().take(MAX_SHOWN_TASKS).enumerate() {
+            writeln!(f, "  {}: {}", i, task)?;
+        }
+        let more = self.tasks.len().saturating_sub(MAX_SHOWN_TASKS) + self.omitted;
+        if more > 0 {
+            writeln!(f, "  ... {} more", more)?;
+        }
+
+        write!(f, "pending tasks: {}", self.pending)?;
+        if !self.next.is_empty() {
+            write!(f, ", next {}", self.next.join(", "))?;
+        }
+        if self.pending > self.next.len() {
+            write!(f, ", ...")?;
+        }
+        Ok(())
+    }
+}
+
+fn describe(frame: &[u8]) -> String {
+    let description = describe_frame(frame);
+    match description.char_indices().nth(MAX_DESCRIPTION_LEN) {
+        Some((end, _)) => format!("{}...", &description[..end]),
+        None => description,
+    }
+}
+
+/// An executed task and the task that spawned it.
+///
+/// Chains are cut after [`MAX_SHOWN_TASKS`] links, so deep recursion doesn't
+/// keep every ancestor alive.
+#[derive(Debug)]
+struct Link {
+    frame: Arc<[u8]>,
+    parent: Option<Arc<Link>>,
+    /// Length of the whole chain, including the links that were cut.
+    depth: usize,
+    /// Number of links kept in the chain.
+    kept: usize,
+}
+
+impl Link {
+    fn new(frame: Arc<[u8]>, parent: Option<Arc<Link>>) -> Self {
+        let parent = parent.map(|parent| parent.truncate(MAX_SHOWN_TASKS - 1));
+        Self {
+            frame,
+            depth: parent.as_ref().map_or(1, |parent| parent.depth + 1),
+            kept: parent.as_ref().map_or(1, |parent| parent.kept + 1),
+            parent,
+        }
+    }
+
+    /// Returns the chain with at most `kept` links, copying the links that stay.
+    fn truncate(self: Arc<Self>, kept: usize) -> Arc<Self> {
+        if self.kept <= kept {
+            return self;
+        }
+        let parent = match &self.parent {
+            Some(parent) if kept > 1 => Some(parent.clone().truncate(kept - 1)),
+            _ => None,
+        };
+        Arc::new(Self {
+            frame: self.frame.clone(),
+            depth: self.depth,
+            kept: parent.as_ref().map_or(1, |parent| parent.kept + 1),
+            parent,
+        })
+    }
+}
+
+impl Scheduler {
+    /// Sets whether errors of failed steps are wrapped in
+    /// [`Error::TaskFailed`](crate::Error::TaskFailed) with a [`TaskBacktrace`].
+    ///
+    /// Backtraces are off by default, as tracking the spawning tasks costs an
+    /// allocation per step. Tasks already pending when they're turned on
+    /// count as roots.
+    pub fn set_backtraces(&mut self, enabled: bool) {
+        if self.ancestry.enabled != enabled {
+            self.ancestry = Ancestry {
+                enabled,
+                ..Ancestry::default()
+            };
+        }
+    }
+
+    /// Returns true if errors of failed steps carry a [`TaskBacktrace`].
+    pub fn backtraces(&self) -> bool {
+        self.ancestry.enabled
+    }
+}
+
+/// Spawn ancestry of the pending tasks and of the task executing right now.
+///
+/// Only executed tasks get a [`Link`]; a pending task just keeps its spawner
+/// alive. Below [`MAX_SHOWN_TASKS`] levels of spawning, tracking costs one
+/// allocation per step.
+#[derive(Debug, Default)]
+pub(crate) struct Ancestry {
+    /// Spawner of every pending task this scheduler pushed, next task last.
+    ///
+    /// Tasks that were already on the stack, e.g. after a restore, sit below
+    /// these and count as roots.
+    pending: Vec<Option<Arc<Link>>>,
+    current: Option<Arc<Link>>,
+    in_step: bool,
+    rescheduling: bool,
+    enabled: bool,
+}
+
+impl Ancestry {
+    pub(crate) fn start_step(&mut self) {
+        self.in_step = true;
+    }
+
+    /// Ends the step, returning a backtrace of the task it executed.
+    pub(crate) fn end_step(&mut self, stack: &SchedulerStack) -> Option<TaskBacktrace> {
+        let backtrace = self
+            .current
+            .as_deref()
+            .map(|link| TaskBacktrace::new(link, stack));
+        self.current = None;
+        self.in_step = false;
+        self.rescheduling = false;
+        backtrace
+    }
+
+    /// Makes the next push reschedule the executing task on behalf of its spawner.
+    pub(crate) fn reschedule(&mut self) {
+        self.rescheduling = true;
+    }
+
+    pub(crate) fn pushed(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let spawner = match &self.current {
+            Some(current) if std::mem::take(&mut self.rescheduling) => current.parent.clone(),
+            current => current.clone(),
+        };
+        self.pending.push(spawner);
+    }
+
+    pub(crate) fn popped(&mut self, frame: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+        let parent = self.pending.pop().flatten();
+        if self.in_step && self.current.is_none() {
+            self.current = Some(Arc::new(Link::new(frame.into(), parent)));
+        }
+    }
+
+    /// Moves the spawner of the pending task `depth` tasks below the top to the top.
+    pub(crate) fn selected(&mut self, depth: usize) {
+        if !self.enabled {
+            return;
+        }
+        // Tasks below the tracked ones are roots
+        let spawner = match self.pending.len().checked_sub(depth + 1) {
+            Some(index) => self.pending.remove(index),
+            None => None,
+        };
+        self.pending.push(spawner);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_truncates() {
+        let backtrace = TaskBacktrace {
+            tasks: (0..20).map(|i| format!("Task {{n: {}}}", i)).collect(),
+            omitted: 3,
+            pending: 7,
+            next: vec!["Next {}".to_string()],
+        };
+
+        let text = backtrace.to_string();
+        assert!(text.starts_with("task backtrace (failed task first):\n  0: Task {n: 0}\n"));
+        assert!(text.contains("  15: Task {n: 15}\n  ... 7 more\n"));
+        assert!(text.ends_with("pending tasks: 7, next Next {}, ..."));
+    }
+
+    #[test]
+    fn test_deep_chains_are_cut() {
+        let mut link: Option<Arc<Link>> = None;
+        for i in 0..100_u32 {
+            let mut frame = Vec::new();
+            ciborium::ser::into_writer(&i, &mut frame).unwrap();
+            link = Some(Arc::new(Link::new(frame.into(), link)));
+        }
+        let link = link.unwrap();
+        assert_eq!((link.depth, link.kept), (100, MAX_SHOWN_TASKS));
+
+        let backtrace = TaskBacktrace::new(&link, &SchedulerStack::new());
+        assert_eq!(backtrace.tasks.len(), MAX_SHOWN_TASKS);
+        assert_eq!(backtrace.tasks[0], "99");
+        assert_eq!(backtrace.tasks[MAX_SHOWN_TASKS - 1], "84");
+        assert_eq!(backtrace.omitted, 100 - MAX_SHOWN_TASKS);
+    }
+
+    #[test]
+    fn test_long_descriptions_are_cut() {
+        let mut frame = Vec::new();
+        ciborium::ser::into_writer(&"x".repeat(200), &mut frame).unwrap();
+        assert_eq!(describe(&frame).len(), MAX_DESCRIPTION_LEN + 3);
+    }
+}
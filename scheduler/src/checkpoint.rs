@@ -0,0 +1,325 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::observer::{Event, Observer};
+use crate::snapshot::{encode, write_atomic};
+use crate::{DecodeLimits, Error, Result, Scheduler, SchedulerStack};
+
+/// When the [`Checkpointer`] writes a new checkpoint.
+///
+/// A checkpoint is written after a step as soon as any of the configured
+/// triggers fires. Checkpoints are only ever taken between steps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointPolicy {
+    /// Checkpoint every `n` steps.
+    pub every_steps: Option<u64>,
+    /// Checkpoint once this much time has passed since the last checkpoint.
+    pub every: Option<Duration>,
+    /// Checkpoint once the number of used stack bytes has changed by at least
+    /// this much since the last checkpoint.
+    pub data_change: Option<usize>,
+    /// Number of most recent checkpoints kept on disk.
+    pub keep: usize,
+}
+
+impl Default for CheckpointPolicy {
+    fn default() -> Self {
+        Self {
+            every_steps: None,
+            every: None,
+            data_change: None,
+            keep: 3,
+        }
+    }
+}
+
+impl CheckpointPolicy {
+    /// Creates a policy with no triggers that keeps the last 3 checkpoints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checkpoints every `steps` steps.
+    pub fn every_steps(mut self, steps: u64) -> Self {
+        self.every_steps = Some(steps.max(1));
+        self
+    }
+
+    /// Checkpoints once `interval` has passed since the last checkpoint.
+    pub fn every(mut self, interval: Duration) -> Self {
+        self.every = Some(interval);
+        self
+    }
+
+    /// Checkpoints once the used stack size has changed by `bytes`.
+    pub fn on_data_change(mut self, bytes: usize) -> Self {
+        self.data_change = Some(bytes.max(1));
+        self
+    }
+
+    /// Keeps the `count` most recent checkpoints.
+    pub fn keep(mut self, count: usize) -> Self {
+        self.keep = count.max(1);
+        self
+    }
+}
+
+/// Observer that periodically writes snapshots of the scheduler into a directory.
+///
+/// Checkpoints are written atomically as numbered snapshot files, and only the
+/// most recent ones are kept. [`Scheduler::resume_latest`] restores the newest
+/// checkpoint of a directory.
+#[derive(Debug)]
+pub struct Checkpointer {
+    dir: PathBuf,
+    policy: CheckpointPolicy,
+    /// Sequence number of the next checkpoint
+    sequence: u64,
+    steps: u64,
+    last_time: Instant,
+    /// Used stack bytes at the last checkpoint, or when first observed
+    last_used: Option<usize>,
+}
+
+impl Checkpointer {
+    /// Creates a checkpointer writing into `dir`.
+    ///
+    /// Numbering continues after checkpoints already present in `dir`.
+    pub fn new(dir: impl AsRef<Path>, policy: CheckpointPolicy) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let sequence = list_checkpoints(&dir)?
+            .last()
+            .map_or(0, |(sequence, _)| sequence + 1);
+
+        Ok(Self {
+            dir,
+            policy,
+            sequence,
+            steps: 0,
+            last_time: Instant::now(),
+            last_used: None,
+        })
+    }
+
+    /// Returns the paths of the checkpoints currently on disk, oldest first.
+    pub fn checkpoints(&self) -> Result<Vec<PathBuf>> {
+        Ok(list_checkpoints(&self.dir)?
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect())
+    }
+
+    /// Writes a checkpoint of `stack` and removes checkpoints beyond the kept count.
+    fn write(&mut self, stack: &SchedulerStack) -> Result<()> {
+        let path = self
+            .dir
+            .join(format!("checkpoint-{:020}.bin", self.sequence));
+        write_atomic(&path, &encode(stack))?;
+        self.sequence += 1;
+
+        self.steps = 0;
+        self.last_time = Instant::now();
+        self.last_used = Some(used_bytes(stack));
+
+        let checkpoints = list_checkpoints(&self.dir)?;
+        let excess = checkpoints.len().saturating_sub(self.policy.keep);
+        for (_, path) in &checkpoints[..excess] {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_due(&self, stack: &SchedulerStack) -> bool {
+        let steps = self.policy.every_steps.is_some_and(|n| self.steps >= n);
+        let time = self
+            .policy
+            .every
+            .is_some_and(|interval| self.last_time.elapsed() >= interval);
+        let data = self.policy.data_change.is_some_and(|bytes| {
+            self.last_used
+                .is_some_and(|last_used| used_bytes(stack).abs_diff(last_used) >= bytes)
+        });
+
+        steps || time || data
+    }
+}
+
+impl Observer for Checkpointer {
+    fn on_event(&mut self, event: &Event<'_>, stack: &SchedulerStack) -> Result<()> {
+        if self.last_used.is_none() {
+            self.last_used = Some(used_bytes(stack));
+        }
+        if *event != Event::StepFinished {
+            return Ok(());
+        }
+
+        self.steps += 1;
+        if self.is_due(stack) {
+            self.write(stack)?;
+        }
+        Ok(())
+    }
+}
+
+impl Scheduler {
+    /// Restores the newest readable checkpoint written by a [`Checkpointer`] into `dir`.
+    ///
+    /// Checkpoints whose frames exceed `limits`, if given, are skipped like
+    /// unreadable ones, and the limits stay enforced on the restored scheduler.
+    pub fn resume_latest(dir: impl AsRef<Path>, limits: Option<&DecodeLimits>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut last_error = None;
+
+        for (_, path) in list_checkpoints(dir)?.into_iter().rev() {
+            match fs::read(&path)
+                .map_err(Error::from)
+                .and_then(|snapshot| Self::restore_limited(&snapshot, limits))
+            {
+                Ok(scheduler) => return Ok(scheduler),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            Error::InvalidSnapshot(format!("no checkpoint found in {}", dir.display()))
+        }))
+    }
+}
+
+/// Number of bytes used by tasks and data together.
+fn used_bytes(stack: &SchedulerStack) -> usize {
+    stack.as_bytes().len() - stack.available_capacity()
+}
+
+/// Returns the checkpoints in `dir` with their sequence numbers, oldest first.
+fn list_checkpoints(dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let mut checkpoints = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let sequence = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix("checkpoint-"))
+            .and_then(|name| name.strip_suffix(".bin"))
+            .and_then(|sequence| sequence.parse::<u64>().ok());
+
+        if let Some(sequence) = sequence {
+            checkpoints.push((sequence, entry.path()));
+        }
+    }
+    checkpoints.sort();
+
+    Ok(checkpoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Fill {
+        remaining: u32,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Fill {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            scheduler.push_data(&self.remaining)?;
+            self.remaining -= 1;
+            Ok(vec![])
+        }
+
+        fn push_self(&mut self) -> bool {
+            self.remaining > 0
+        }
+    }
+
+    #[test]
+    fn test_every_steps_with_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Fill { remaining: 10 }))
+            .unwrap();
+
+        let policy = CheckpointPolicy::new().every_steps(2).keep(2);
+        scheduler.add_observer(Checkpointer::new(dir.path(), policy).unwrap());
+        scheduler.execute_all().unwrap();
+
+        let checkpointer = scheduler.observer::<Checkpointer>().unwrap();
+        let checkpoints = checkpointer.checkpoints().unwrap();
+        assert_eq!(checkpoints.len(), 2);
+        assert!(checkpoints[1].ends_with("checkpoint-00000000000000000004.bin"));
+
+        let resumed = Scheduler::resume_latest(dir.path(), None).unwrap();
+        assert_eq!(resumed.snapshot(), scheduler.snapshot());
+    }
+
+    #[test]
+    fn test_data_change_trigger() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Fill { remaining: 6 }))
+            .unwrap();
+
+        // Every step pushes a 3 byte frame: 1 byte of CBOR and 2 length bytes
+        let policy = CheckpointPolicy::new().on_data_change(6).keep(10);
+        scheduler.add_observer(Checkpointer::new(dir.path(), policy).unwrap());
+        scheduler.execute_all().unwrap();
+
+        let checkpointer = scheduler.observer::<Checkpointer>().unwrap();
+        assert_eq!(checkpointer.checkpoints().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_resume_latest_enforces_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Fill { remaining: 1 }))
+            .unwrap();
+        scheduler
+            .save_snapshot(dir.path().join("checkpoint-00000000000000000000.bin"))
+            .unwrap();
+
+        let limits = DecodeLimits::new().allow_task("Fork");
+        assert!(matches!(
+            Scheduler::resume_latest(dir.path(), Some(&limits)),
+            Err(Error::TaskNotAllowed(name)) if name == "Fill"
+        ));
+
+        let limits = DecodeLimits::new().allow_task("Fill");
+        let resumed = Scheduler::resume_latest(dir.path(), Some(&limits)).unwrap();
+        assert_eq!(resumed.decode_limits(), Some(&limits));
+    }
+
+    #[test]
+    fn test_resume_latest_skips_corrupt_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut scheduler = Scheduler::new();
+        scheduler.push_data(&1_u8).unwrap();
+        scheduler
+            .save_snapshot(dir.path().join("checkpoint-00000000000000000000.bin"))
+            .unwrap();
+        fs::write(
+            dir.path().join("checkpoint-00000000000000000001.bin"),
+            b"junk",
+        )
+        .unwrap();
+
+        let mut resumed = Scheduler::resume_latest(dir.path(), None).unwrap();
+        assert_eq!(resumed.pop_data::<u8>().unwrap(), 1);
+
+        let empty = tempfile::tempdir().unwrap();
+        assert!(Scheduler::resume_latest(empty.path(), None).is_err());
+    }
+}
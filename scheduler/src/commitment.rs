@@ -0,0 +1,178 @@
+use std::fmt::Debug;
+
+use sha2::{Digest, Sha256};
+
+use crate::observer::{Event, Observer};
+use crate::{Result, Scheduler, SchedulerStack};
+
+/// Hash function used to chain state commitments.
+pub trait StateHasher: Debug + Send + 'static {
+    /// Hashes the concatenation of `parts`.
+    fn hash(&mut self, parts: &[&[u8]]) -> Vec<u8>;
+}
+
+/// SHA-256 [`StateHasher`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256Hasher;
+
+impl StateHasher for Sha256Hasher {
+    fn hash(&mut self, parts: &[&[u8]]) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().to_vec()
+    }
+}
+
+/// Observer that commits to the stack state after every step.
+///
+/// Each digest hashes the previous digest followed by the stack indices and
+/// the used parts of the stack, so the last digest commits to the initial
+/// state and every state after it. The first digest, for the state the chain
+/// was created from, is chained with an empty previous digest.
+#[derive(Debug)]
+pub struct HashChain<H: StateHasher = Sha256Hasher> {
+    hasher: H,
+    /// Initial digest followed by one digest per step
+    digests: Vec<Vec<u8>>,
+}
+
+impl HashChain {
+    /// Starts a SHA-256 chain from the current state of `scheduler`.
+    pub fn new(scheduler: &Scheduler) -> Self {
+        Self::with_hasher(scheduler, Sha256Hasher)
+    }
+}
+
+impl<H: StateHasher> HashChain<H> {
+    /// Starts a chain using `hasher` from the current state of `scheduler`.
+    ///
+    /// The chain must be attached with [`Scheduler::add_observer`].
+    pub fn with_hasher(scheduler: &Scheduler, mut hasher: H) -> Self {
+        let initial = state_digest(&mut hasher, &[], &scheduler.stack);
+
+        Self {
+            hasher,
+            digests: vec![initial],
+        }
+    }
+
+    /// Returns the digest committing to the whole execution so far.
+    pub fn commitment(&self) -> &[u8] {
+        self.digests.last().map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the initial digest followed by the digest after every step.
+    pub fn digests(&self) -> &[Vec<u8>] {
+        &self.digests
+    }
+}
+
+impl<H: StateHasher> Observer for HashChain<H> {
+    fn on_event(&mut self, event: &Event<'_>, stack: &SchedulerStack) -> Result<()> {
+        if matches!(event, Event::StepFinished | Event::StepFailed) {
+            let previous = self.digests.last().map_or(&[][..], Vec::as_slice);
+            let digest = state_digest(&mut self.hasher, previous, stack);
+            self.digests.push(digest);
+        }
+        Ok(())
+    }
+}
+
+/// Hashes `previous` with the indices and used regions of `stack`.
+fn state_digest(hasher: &mut impl StateHasher, previous: &[u8], stack: &SchedulerStack) -> Vec<u8> {
+    let (front_index, back_index) = (stack.front_index(), stack.back_index());
+    let bytes = stack.as_bytes();
+
+    hasher.hash(&[
+        previous,
+        &(front_index as u64).to_le_bytes(),
+        &(back_index as u64).to_le_bytes(),
+        &bytes[..front_index],
+        &bytes[back_index..],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Tick {
+        remaining: u8,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Tick {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            scheduler.push_data(&self.remaining)?;
+            self.remaining -= 1;
+            Ok(vec![])
+        }
+
+        fn push_self(&mut self) -> bool {
+            self.remaining > 0
+        }
+    }
+
+    fn run(remaining: u8) -> HashChain {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_task(Box::new(Tick { remaining })).unwrap();
+        scheduler.add_observer(HashChain::new(&scheduler));
+        scheduler.execute_all().unwrap();
+        scheduler.remove_observer::<HashChain>().unwrap()
+    }
+
+    #[test]
+    fn test_chain_is_deterministic() {
+        let chain = run(3);
+
+        assert_eq!(chain.digests().len(), 4);
+        assert!(chain.digests().iter().all(|digest| digest.len() == 32));
+        assert_eq!(chain.commitment(), run(3).commitment());
+        assert_ne!(chain.commitment(), run(4).commitment());
+    }
+
+    #[test]
+    fn test_chain_links_previous_digest() {
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Tick { remaining: 2 }))
+            .unwrap();
+        scheduler.add_observer(HashChain::new(&scheduler));
+        scheduler.execute_all().unwrap();
+
+        // Recompute the last digest from the final state
+        let digests = scheduler.observer::<HashChain>().unwrap().digests();
+        let expected = state_digest(&mut Sha256Hasher, &digests[1], &scheduler.stack);
+        assert_eq!(digests[2], expected);
+    }
+
+    #[derive(Debug, Default)]
+    struct Length;
+
+    impl StateHasher for Length {
+        fn hash(&mut self, parts: &[&[u8]]) -> Vec<u8> {
+            let length: usize = parts.iter().map(|part| part.len()).sum();
+            length.to_le_bytes().to_vec()
+        }
+    }
+
+    #[test]
+    fn test_custom_hasher() {
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Tick { remaining: 1 }))
+            .unwrap();
+        scheduler.add_observer(HashChain::with_hasher(&scheduler, Length));
+        scheduler.execute_all().unwrap();
+
+        let chain = scheduler.observer::<HashChain<Length>>().unwrap();
+        assert_eq!(chain.digests().len(), 2);
+        assert_eq!(chain.commitment().len(), 8);
+    }
+}
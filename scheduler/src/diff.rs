@@ -0,0 +1,390 @@
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::inspect::describe_frame;
+use crate::snapshot::{decode, encode_parts, read_snapshot};
+use crate::{Error, Result, Scheduler};
+
+/// Magic bytes at the start of every encoded snapshot diff.
+const MAGIC: &[u8; 4] = b"SCHI";
+
+/// Current snapshot diff format version.
+const VERSION: u8 = 1;
+
+/// Unchanged gaps shorter than this are folded into the surrounding ranges,
+/// as every range costs 8 bytes of offset and length.
+const MERGE_GAP: usize = 8;
+
+/// Changes between a base snapshot and a later snapshot of the same stack.
+///
+/// Only the changed byte ranges of the stack buffer and the new stack indices
+/// are stored, so a diff is usually much smaller than a full snapshot. A diff
+/// can only be applied to the exact base snapshot it was computed against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// SHA-256 of the base snapshot
+    base_digest: [u8; 32],
+    /// Front and back indices of the base snapshot
+    pub base_indices: (usize, usize),
+    /// Front and back indices of the target snapshot
+    pub indices: (usize, usize),
+    /// Offsets into the stack buffer with the bytes stored there
+    pub ranges: Vec<(usize, Vec<u8>)>,
+    /// Schema versions of the task types in the target snapshot
+    schema: Vec<(String, u32)>,
+}
+
+impl SnapshotDiff {
+    /// Computes the changes from `base` to `target`.
+    pub fn between(base: &[u8], target: &[u8]) -> Result<Self> {
+        let base_parts = decode(base)?;
+        let parts = decode(target)?;
+        let (base_buffer, buffer) = (base_parts.buffer, parts.buffer);
+        if base_buffer.len() != buffer.len() {
+            return Err(Error::InvalidSnapshot(format!(
+                "can't diff a {} byte stack against a {} byte stack",
+                buffer.len(),
+                base_buffer.len()
+            )));
+        }
+
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (offset, _) in base_buffer
+            .iter()
+            .zip(buffer)
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+        {
+            match ranges.last_mut() {
+                Some((_, end)) if offset - *end < MERGE_GAP => *end = offset + 1,
+                _ => ranges.push((offset, offset + 1)),
+            }
+        }
+
+        Ok(Self {
+            base_digest: Sha256::digest(base).into(),
+            base_indices: (base_parts.front_index, base_parts.back_index),
+            indices: (parts.front_index, parts.back_index),
+            ranges: ranges
+                .into_iter()
+                .map(|(start, end)| (start, buffer[start..end].to_vec()))
+                .collect(),
+            schema: parts.schema,
+        })
+    }
+
+    /// Rebuilds the target snapshot from the `base` the diff was computed against.
+    pub fn apply(&self, base: &[u8]) -> Result<Vec<u8>> {
+        let base_digest: [u8; 32] = Sha256::digest(base).into();
+        if base_digest != self.base_digest {
+            return Err(Error::InvalidSnapshot(
+                "diff was computed against a different base snapshot".to_string(),
+            ));
+        }
+
+        let mut buffer = decode(base)?.buffer.to_vec();
+        for (offset, bytes) in &self.ranges {
+            let range = offset
+                .checked_add(bytes.len())
+                .filter(|end| *end <= buffer.len())
+                .map(|end| *offset..end)
+                .ok_or_else(|| {
+                    Error::InvalidSnapshot("diff range outside the stack".to_string())
+                })?;
+            buffer[range].copy_from_slice(bytes);
+        }
+
+        Ok(encode_parts(
+            self.indices.0,
+            self.indices.1,
+            &buffer,
+            &self.schema,
+        ))
+    }
+
+    /// Restores a scheduler from `base` with the diff applied.
+    pub fn restore(&self, base: &[u8]) -> Result<Scheduler> {
+        Scheduler::restore(&self.apply(base)?)
+    }
+
+    /// Number of changed buffer bytes stored in the diff.
+    pub fn changed_bytes(&self) -> usize {
+        self.ranges.iter().map(|(_, bytes)| bytes.len()).sum()
+    }
+
+    /// Encodes the diff into a compact binary format.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(61 + self.ranges.len() * 8 + self.changed_bytes());
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.base_digest);
+        for index in [
+            self.base_indices.0,
+            self.base_indices.1,
+            self.indices.0,
+            self.indices.1,
+            self.ranges.len(),
+        ] {
+            bytes.extend_from_slice(&encode_u32(index));
+        }
+        for (offset, range) in &self.ranges {
+            bytes.extend_from_slice(&encode_u32(*offset));
+            bytes.extend_from_slice(&encode_u32(range.len()));
+            bytes.extend_from_slice(range);
+        }
+        bytes.extend_from_slice(&encode_u32(self.schema.len()));
+        for (task_type, version) in &self.schema {
+            bytes.extend_from_slice(&encode_u32(task_type.len()));
+            bytes.extend_from_slice(task_type.as_bytes());
+            bytes.extend_from_slice(&version.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Decodes a diff produced by [`SnapshotDiff::encode`].
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let truncated = || Error::InvalidSnapshot("truncated snapshot diff".to_string());
+        let mut rest = bytes;
+        let mut take = |length: usize| {
+            if length > rest.len() {
+                return Err(truncated());
+            }
+            let (taken, remaining) = rest.split_at(length);
+            rest = remaining;
+            Ok(taken)
+        };
+
+        if take(4)? != MAGIC {
+            return Err(Error::InvalidSnapshot(
+                "missing snapshot diff header".to_string(),
+            ));
+        }
+        let version = take(1)?[0];
+        if version != VERSION {
+            return Err(Error::InvalidSnapshot(format!(
+                "unsupported diff version {}",
+                version
+            )));
+        }
+
+        let mut base_digest = [0; 32];
+        base_digest.copy_from_slice(take(32)?);
+        let mut header = [0; 5];
+        for value in &mut header {
+            *value = decode_u32(take(4)?);
+        }
+
+        let [base_front, base_back, front, back, count] = header;
+        let mut ranges = Vec::new();
+        for _ in 0..count {
+            let offset = decode_u32(take(4)?);
+            let length = decode_u32(take(4)?);
+            ranges.push((offset, take(length)?.to_vec()));
+        }
+        let mut schema = Vec::new();
+        for _ in 0..decode_u32(take(4)?) {
+            let length = decode_u32(take(4)?);
+            let task_type = String::from_utf8(take(length)?.to_vec())
+                .map_err(|_| Error::InvalidSnapshot("task type is not UTF-8".to_string()))?;
+            schema.push((task_type, decode_u32(take(4)?) as u32));
+        }
+        if !rest.is_empty() {
+            return Err(Error::InvalidSnapshot(
+                "unexpected bytes after the snapshot diff".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            base_digest,
+            base_indices: (base_front, base_back),
+            indices: (front, back),
+            ranges,
+            schema,
+        })
+    }
+}
+
+/// Frame-level changes to one side of the stack.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StackDiff {
+    /// Number of frames at the bottom of the stack present in both snapshots
+    pub unchanged: usize,
+    /// Frames of the base snapshot above the unchanged ones
+    pub removed: Vec<Vec<u8>>,
+    /// Frames of the target snapshot above the unchanged ones
+    pub added: Vec<Vec<u8>>,
+}
+
+impl StackDiff {
+    fn between(base: Vec<Vec<u8>>, target: Vec<Vec<u8>>) -> Self {
+        let unchanged = base
+            .iter()
+            .zip(&target)
+            .take_while(|(old, new)| old == new)
+            .count();
+
+        Self {
+            unchanged,
+            removed: base[unchanged..].to_vec(),
+            added: target[unchanged..].to_vec(),
+        }
+    }
+
+    /// Returns true if both snapshots hold the same frames.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+/// Human-readable diff of the decoded task and data frames of two snapshots.
+///
+/// Frames are compared bottom up: the frames both stacks share at the bottom
+/// are unchanged, everything above them was popped from the base or pushed
+/// since. Formatting the diff with `{}` prints one line per changed frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameDiff {
+    /// Front and back indices of the base snapshot
+    pub base_indices: (usize, usize),
+    /// Front and back indices of the target snapshot
+    pub indices: (usize, usize),
+    /// Changes to the data stack
+    pub data: StackDiff,
+    /// Changes to the task stack
+    pub tasks: StackDiff,
+}
+
+impl FrameDiff {
+    /// Computes the frame changes from `base` to `target`.
+    ///
+    /// Frames are compared as stored, without schema migrations, so snapshots
+    /// holding tasks this binary can't load are diffed as well.
+    pub fn between(base: &[u8], target: &[u8]) -> Result<Self> {
+        let (base, _) = read_snapshot(base)?;
+        let (target, _) = read_snapshot(target)?;
+
+        Ok(Self {
+            base_indices: (base.front_index(), base.back_index()),
+            indices: (target.front_index(), target.back_index()),
+            data: StackDiff::between(base.front_frames()?, target.front_frames()?),
+            tasks: StackDiff::between(base.back_frames()?, target.back_frames()?),
+        })
+    }
+
+    /// Returns true if both snapshots hold the same tasks and data.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.tasks.is_empty()
+    }
+}
+
+impl fmt::Display for FrameDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "front {} -> {}, back {} -> {}",
+            self.base_indices.0, self.indices.0, self.base_indices.1, self.indices.1
+        )?;
+
+        for (name, diff) in [("data", &self.data), ("tasks", &self.tasks)] {
+            writeln!(f, "{}: {} unchanged", name, diff.unchanged)?;
+            for frame in &diff.removed {
+                writeln!(f, "- {}", describe_frame(frame))?;
+            }
+            for frame in &diff.added {
+                writeln!(f, "+ {}", describe_frame(frame))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn encode_u32(value: usize) -> [u8; 4] {
+    u32::try_from(value).unwrap_or(u32::MAX).to_le_bytes()
+}
+
+fn decode_u32(bytes: &[u8]) -> usize {
+    let mut value = [0; 4];
+    value.copy_from_slice(bytes);
+    u32::from_le_bytes(value) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler_with(values: &[u64]) -> Scheduler {
+        let mut scheduler = Scheduler::new();
+        for value in values {
+            scheduler.push_data(value).unwrap();
+        }
+        scheduler
+    }
+
+    #[test]
+    fn test_diff_roundtrip() {
+        let base = scheduler_with(&[1, 2, 3]).snapshot();
+        let mut scheduler = Scheduler::restore(&base).unwrap();
+        scheduler.pop_data::<u64>().unwrap();
+        scheduler.push_data(&300_u64).unwrap();
+        let target = scheduler.snapshot();
+
+        let diff = SnapshotDiff::between(&base, &target).unwrap();
+        assert_eq!(diff.ranges.len(), 1);
+        assert!(diff.changed_bytes() <= 4);
+        assert_eq!(diff.apply(&base).unwrap(), target);
+
+        let decoded = SnapshotDiff::decode(&diff.encode()).unwrap();
+        assert_eq!(decoded, diff);
+        assert_eq!(decoded.restore(&base).unwrap().snapshot(), target);
+    }
+
+    #[test]
+    fn test_apply_rejects_other_base() {
+        let base = scheduler_with(&[1]).snapshot();
+        let target = scheduler_with(&[1, 2]).snapshot();
+        let diff = SnapshotDiff::between(&base, &target).unwrap();
+
+        assert!(matches!(
+            diff.apply(&target),
+            Err(Error::InvalidSnapshot(_))
+        ));
+        assert!(SnapshotDiff::decode(&diff.encode()[..40]).is_err());
+    }
+
+    #[test]
+    fn test_frame_diff() {
+        let base = scheduler_with(&[1, 2, 3]).snapshot();
+        let target = scheduler_with(&[1, 5]).snapshot();
+
+        let diff = FrameDiff::between(&base, &target).unwrap();
+        assert_eq!(diff.data.unchanged, 1);
+        assert_eq!(diff.data.removed.len(), 2);
+        assert_eq!(diff.data.added.len(), 1);
+        assert!(diff.tasks.is_empty());
+
+        let text = diff.to_string();
+        assert!(text.contains("- 3\n"), "{}", text);
+        assert!(text.contains("+ 5\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_frame_diff_of_unknown_tasks() {
+        let base = scheduler_with(&[1]).snapshot();
+        let mut scheduler = scheduler_with(&[1]);
+        let mut frame = Vec::new();
+        ciborium::ser::into_writer(
+            &ciborium::Value::Map(vec![("type".into(), "Missing".into())]),
+            &mut frame,
+        )
+        .unwrap();
+        scheduler.push_task_frame(&frame).unwrap();
+        let target = scheduler.snapshot();
+        assert!(Scheduler::restore(&target).is_err());
+
+        let diff = FrameDiff::between(&base, &target).unwrap();
+        assert!(diff.data.is_empty());
+        assert_eq!(diff.tasks.added, [frame]);
+    }
+}
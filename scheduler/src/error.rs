@@ -1,3 +1,5 @@
+use crate::backtrace::TaskBacktrace;
+use crate::jobs::JobId;
 use crate::stack::StackError;
 use std::io;
 use thiserror::Error;
@@ -32,6 +34,16 @@ pub enum Error {
     #[error("Execution error: {0}")]
     Execution(String),
 
+    /// A step failed after its task was popped; the backtrace shows the
+    /// tasks that spawned it. Only returned once enabled with
+    /// [`Scheduler::set_backtraces`](crate::Scheduler::set_backtraces).
+    #[error("{error}\n{backtrace}")]
+    TaskFailed {
+        #[source]
+        error: Box<Error>,
+        backtrace: TaskBacktrace,
+    },
+
     /// Error in task implementation.
     #[error("Task error: {0}")]
     Task(String),
@@ -40,7 +52,64 @@ pub enum Error {
     #[error("Invalid data: {0}")]
     InvalidData(String),
 
+    /// No job with the given id is known to the job manager.
+    #[error("Unknown job {0}")]
+    UnknownJob(JobId),
+
+    /// The job still has pending tasks.
+    #[error("Job {0} has not finished yet")]
+    JobPending(JobId),
+
+    /// The job failed during execution.
+    #[error("Job {0} failed: {1}")]
+    JobFailed(JobId, String),
+
+    /// The snapshot is malformed or was written by an incompatible version.
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+
+    /// A signed or encrypted snapshot was modified or sealed with another key.
+    #[error(
+        "Snapshot authentication failed - the snapshot was modified or sealed with another key"
+    )]
+    Authentication,
+
+    /// A frame exceeds the scheduler's decoding limits.
+    #[error("Decoding limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    /// A task's type is not in the scheduler's allowlist.
+    #[error("Task type {0} is not allowed")]
+    TaskNotAllowed(String),
+
+    /// Tasks in a snapshot are of unknown types or can't be migrated to the
+    /// current schema version of their type.
+    #[error("Incompatible tasks in snapshot: {}", .0.join(", "))]
+    IncompatibleTasks(Vec<String>),
+
+    /// A memory-mapped stack file is malformed or doesn't match the requested capacity.
+    #[error("Invalid stack file: {0}")]
+    StackFile(String),
+
+    /// The journal is malformed or can't be used.
+    #[error("Journal error: {0}")]
+    Journal(String),
+
+    /// The execution trace is malformed or doesn't match the registered tasks.
+    #[error("Invalid trace: {0}")]
+    InvalidTrace(String),
+
     /// General IO error.
     #[error(transparent)]
     Io(#[from] io::Error),
 }
+
+impl Error {
+    /// Returns the error without the [`Error::TaskFailed`] backtrace, if any.
+    pub fn without_backtrace(&self) -> &Error {
+        match self {
+            Error::TaskFailed { error, .. } => error.without_backtrace(),
+            error => error,
+        }
+    }
+}
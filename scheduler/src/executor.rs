@@ -0,0 +1,245 @@
+use std::collections::VecDeque;
+use std::io::Cursor;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::de::DeserializeOwned;
+
+use crate::{Error, Result, Scheduler, SchedulerTask};
+
+/// A root job waiting in a worker queue: its submission index and task frame.
+type QueuedJob = (usize, Vec<u8>);
+
+/// Data left on the data stack by a single root job.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct JobOutput {
+    /// Serialized data frames, bottom of the data stack first.
+    frames: Vec<Vec<u8>>,
+}
+
+impl JobOutput {
+    /// Pops the most recently pushed value, like [`Scheduler::pop_data`].
+    pub fn pop<T: DeserializeOwned>(&mut self) -> Result<T> {
+        let frame = self.frames.pop().ok_or(Error::EmptyStack)?;
+
+        let mut cursor = Cursor::new(&frame);
+        let result = ciborium::de::from_reader(&mut cursor).map_err(Error::Deserialization)?;
+
+        Ok(result)
+    }
+
+    /// Returns the number of data frames left by the job.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns true if the job left no data.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Returns the raw data frames, bottom of the data stack first.
+    pub fn into_frames(self) -> Vec<Vec<u8>> {
+        self.frames
+    }
+}
+
+/// Multi-threaded executor for batches of unrelated root tasks.
+///
+/// Every worker owns a [`Scheduler`] and a queue of serialized root jobs. A
+/// worker takes jobs from the front of its own queue and, once that queue is
+/// empty, steals whole jobs from the back of the other workers' queues. Each
+/// job runs to completion on a freshly cleared scheduler, so jobs never observe
+/// each other's data.
+#[derive(Debug, Clone)]
+pub struct Executor {
+    workers: usize,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+impl Executor {
+    /// Creates a new executor with the given number of worker threads.
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers: workers.max(1),
+        }
+    }
+
+    /// Returns the number of worker threads.
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Executes every job and returns their outputs in submission order.
+    ///
+    /// A failing job doesn't affect the others; its error is reported at its
+    /// own position in the result.
+    ///
+    /// A job that panics fails with [`Error::Execution`]; its worker carries
+    /// on with the next job on a fresh scheduler.
+    pub fn run(&self, jobs: Vec<Box<dyn SchedulerTask>>) -> Vec<Result<JobOutput>> {
+        let mut results: Vec<Option<Result<JobOutput>>> = Vec::with_capacity(jobs.len());
+        let queues: Vec<Mutex<VecDeque<QueuedJob>>> =
+            (0..self.workers).map(|_| Mutex::default()).collect();
+
+        // Distribute jobs round-robin across workers
+        for (index, job) in jobs.iter().enumerate() {
+            let mut frame = Vec::new();
+            match ciborium::ser::into_writer(job, &mut frame) {
+                Ok(()) => {
+                    queues[index % self.workers]
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push_back((index, frame));
+                    results.push(None);
+                }
+                Err(e) => results.push(Some(Err(Error::Serialization(e)))),
+            }
+        }
+
+        // Workers store every result as soon as its job finishes
+        let results = Mutex::new(results);
+        thread::scope(|scope| {
+            for id in 0..self.workers {
+                let (queues, results) = (&queues, &results);
+                scope.spawn(move || run_worker(id, queues, results));
+            }
+        });
+
+        results
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| Err(Error::Execution("Worker panicked".to_string())))
+            })
+            .collect()
+    }
+}
+
+/// Runs jobs until neither the worker's own queue nor any other queue has work
+/// left, storing each result at its job's index.
+fn run_worker(
+    id: usize,
+    queues: &[Mutex<VecDeque<QueuedJob>>],
+    results: &Mutex<Vec<Option<Result<JobOutput>>>>,
+) {
+    let mut scheduler = Box::new(Scheduler::new());
+
+    while let Some((index, frame)) = next_job(id, queues) {
+        scheduler.clear();
+        let result = match panic::catch_unwind(AssertUnwindSafe(|| run_job(&mut scheduler, &frame)))
+        {
+            Ok(result) => result,
+            Err(_) => {
+                // The panic may have left the scheduler in the middle of a step
+                scheduler = Box::new(Scheduler::new());
+                Err(Error::Execution("Task panicked".to_string()))
+            }
+        };
+        results.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(result);
+    }
+}
+
+/// Takes the next job from the worker's own queue, or steals one from another worker.
+fn next_job(id: usize, queues: &[Mutex<VecDeque<QueuedJob>>]) -> Option<QueuedJob> {
+    let own = queues[id]
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .pop_front();
+    if own.is_some() {
+        return own;
+    }
+
+    (1..queues.len()).find_map(|offset| {
+        queues[(id + offset) % queues.len()]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_back()
+    })
+}
+
+fn run_job(scheduler: &mut Scheduler, frame: &[u8]) -> Result<JobOutput> {
+    scheduler.push_task_frame(frame)?;
+    scheduler.execute_all()?;
+
+    Ok(JobOutput {
+        frames: scheduler.take_data_frames()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Square {
+        x: u64,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Square {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            if self.x == 0 {
+                return Err(Error::Task("zero".to_string()));
+            }
+            scheduler.push_data(&(self.x * self.x))?;
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn test_results_in_submission_order() {
+        let jobs: Vec<Box<dyn SchedulerTask>> = (0..50)
+            .map(|x| Box::new(Square { x }) as Box<dyn SchedulerTask>)
+            .collect();
+
+        let results = Executor::new(4).run(jobs);
+
+        assert_eq!(results.len(), 50);
+        assert!(results[0].is_err());
+        for (x, result) in results.into_iter().enumerate().skip(1) {
+            let mut output = result.unwrap();
+            assert_eq!(output.len(), 1);
+            assert_eq!(output.pop::<u64>().unwrap(), (x * x) as u64);
+        }
+    }
+
+    /// Panics instead of returning an error.
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Crash;
+
+    #[typetag::serde]
+    impl SchedulerTask for Crash {
+        fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            panic!("crashed");
+        }
+    }
+
+    #[test]
+    fn test_panic_only_fails_running_job() {
+        let mut jobs: Vec<Box<dyn SchedulerTask>> = (1..=5)
+            .map(|x| Box::new(Square { x }) as Box<dyn SchedulerTask>)
+            .collect();
+        jobs.insert(2, Box::new(Crash));
+
+        // A single worker runs the jobs before and after the panic on the same thread
+        let results = Executor::new(1).run(jobs);
+
+        assert!(
+            matches!(&results[2], Err(Error::Execution(message)) if message == "Task panicked")
+        );
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 5);
+        assert_eq!(
+            results[1].as_ref().unwrap().clone().pop::<u64>().unwrap(),
+            4
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{DecodeLimits, Result, Scheduler, SchedulerTask};
+
+/// A task that runs independent branches in parallel and joins their outputs.
+///
+/// Each branch is executed to completion on its own child [`Scheduler`] on the
+/// rayon thread pool. Branches start with an empty data stack, so they cannot
+/// consume data produced before the fork. Once every branch has finished, the
+/// data left on each child's data stack is pushed onto the parent's data stack
+/// in the order the branches were declared. The tasks scheduled after the fork
+/// (the join continuation) therefore see the same data layout as if the
+/// branches had run one after another on the parent.
+///
+/// The fork is serialized as a single task frame, so all branches together must
+/// fit within the task frame size limit. Child schedulers enforce the parent's
+/// [`DecodeLimits`], so branches are held to the same allowed task types.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Fork {
+    /// Independent tasks to execute in parallel
+    pub branches: Vec<Box<dyn SchedulerTask>>,
+}
+
+impl Fork {
+    /// Creates a new Fork task from the given branches.
+    pub fn new(branches: Vec<Box<dyn SchedulerTask>>) -> Self {
+        Self { branches }
+    }
+}
+
+#[typetag::serde]
+impl SchedulerTask for Fork {
+    fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+        let branches = std::mem::take(&mut self.branches);
+        let limits = scheduler.decode_limits();
+
+        let outputs = branches
+            .into_par_iter()
+            .map(|task| run_branch(task, limits))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Merge branch outputs in declared order
+        for frames in outputs {
+            for frame in frames {
+                scheduler.push_data_frame(&frame)?;
+            }
+        }
+
+        Ok(vec![])
+    }
+}
+
+/// Runs a single branch on a child scheduler and returns its data frames,
+/// bottom of the data stack first.
+fn run_branch(task: Box<dyn SchedulerTask>, limits: Option<&DecodeLimits>) -> Result<Vec<Vec<u8>>> {
+    let mut child = Box::new(Scheduler::new());
+    if let Some(limits) = limits {
+        child.set_decode_limits(limits.clone());
+    }
+    child.push_task(task)?;
+    child.execute_all()?;
+
+    child.take_data_frames()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct PushValues {
+        values: Vec<u64>,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for PushValues {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            for value in &self.values {
+                scheduler.push_data(value)?;
+            }
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn test_fork_merges_in_declared_order() {
+        let mut scheduler = Scheduler::new();
+
+        scheduler
+            .push_task(Box::new(Fork::new(vec![
+                Box::new(PushValues { values: vec![1, 2] }),
+                Box::new(PushValues { values: vec![] }),
+                Box::new(PushValues { values: vec![3] }),
+            ])))
+            .unwrap();
+        scheduler.execute_all().unwrap();
+
+        let values: Vec<u64> = (0..3).map(|_| scheduler.pop_data().unwrap()).collect();
+        assert_eq!(values, vec![3, 2, 1]);
+        assert!(scheduler.is_empty_data());
+    }
+
+    #[test]
+    fn test_branches_inherit_limits() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_decode_limits(DecodeLimits::new().allow_task("Fork"));
+
+        scheduler
+            .push_task(Box::new(Fork::new(vec![Box::new(PushValues {
+                values: vec![1],
+            })])))
+            .unwrap();
+        let error = scheduler.execute_all().unwrap_err();
+        assert!(error.to_string().contains("PushValues"));
+        assert!(scheduler.is_empty_data());
+    }
+}
@@ -0,0 +1,364 @@
+use std::fmt::Write;
+
+use ciborium::Value;
+
+use crate::limits::text;
+
+/// Deepest nesting of arrays, maps and tags shown before a frame is
+/// considered invalid.
+const MAX_DEPTH: usize = 256;
+
+/// Describes a serialized task or data frame in a compact, readable form.
+///
+/// Tasks are shown as their type name followed by their fields, e.g.
+/// `Fib {n: 5}`, with every field value in CBOR diagnostic notation. Data is
+/// shown in the diagnostic notation of RFC 8949 section 8, e.g. `42`, `1.5`,
+/// `{"a": [1, 2]}`, `h'00ff'` or `1(1700000000)`. Frames that aren't valid
+/// CBOR are shown as hex.
+pub fn describe_frame(frame: &[u8]) -> String {
+    let mut reader = Reader {
+        bytes: frame,
+        position: 0,
+    };
+    let mut description = String::new();
+    match reader.frame(&mut description) {
+        Some(()) if reader.position == frame.len() => description,
+        _ => format!("<invalid frame {}>", hex(frame)),
+    }
+}
+
+/// Describes a decoded CBOR value in diagnostic notation.
+pub fn describe_value(value: &Value) -> String {
+    let mut frame = Vec::new();
+    if ciborium::ser::into_writer(value, &mut frame).is_err() {
+        return "?".to_string();
+    }
+
+    let mut reader = Reader {
+        bytes: &frame,
+        position: 0,
+    };
+    let mut description = String::new();
+    match reader.item(&mut description, 0) {
+        Some(()) => description,
+        None => "?".to_string(),
+    }
+}
+
+/// Writes CBOR items in diagnostic notation straight from their encoding, so
+/// that simple values, undefined and the width of floats are kept.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    /// Writes a whole frame, using the task form for maps tagged with a type.
+    fn frame(&mut self, out: &mut String) -> Option<()> {
+        let start = self.position;
+        let Some((type_name, fields)) = self.task() else {
+            self.position = start;
+            return self.item(out, 0);
+        };
+
+        let _ = write!(out, "{} {{", type_name);
+        for (i, (key, value)) in fields.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            let _ = write!(out, "{}: {}", key, value);
+        }
+        out.push('}');
+        Some(())
+    }
+
+    /// Reads a map with a text `"type"` entry as a type name and its other
+    /// fields, with text keys shown bare.
+    fn task(&mut self) -> Option<(String, Vec<(String, String)>)> {
+        let (major, length) = self.header()?;
+        if major != 5 {
+            return None;
+        }
+
+        let mut type_name = None;
+        let mut fields = Vec::new();
+        let mut remaining = length;
+        while self.next_entry(&mut remaining)? {
+            let bytes = self.bytes;
+
+            let start = self.position;
+            let mut key = String::new();
+            self.item(&mut key, 1)?;
+            let key_text = text(&bytes[start..self.position]);
+
+            let start = self.position;
+            let mut value = String::new();
+            self.item(&mut value, 1)?;
+            let value_text = text(&bytes[start..self.position]);
+
+            if key_text == Some("type") && type_name.is_none() && value_text.is_some() {
+                type_name = value_text.map(str::to_string);
+                continue;
+            }
+            fields.push((key_text.map_or(key, str::to_string), value));
+        }
+
+        Some((type_name?, fields))
+    }
+
+    /// Writes one item nested inside `depth` arrays, maps or tags.
+    fn item(&mut self, out: &mut String, depth: usize) -> Option<()> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        let initial = *self.bytes.get(self.position)?;
+        let info = initial & 0x1f;
+        let (major, argument) = self.header()?;
+        match major {
+            0 => {
+                let _ = write!(out, "{}", argument?);
+            }
+            1 => {
+                let _ = write!(out, "{}", -1 - i128::from(argument?));
+            }
+            2 | 3 => match argument {
+                Some(length) => self.string(out, major, length)?,
+                None => {
+                    out.push_str("(_ ");
+                    let mut first = true;
+                    while !self.at_break()? {
+                        let (chunk_major, length) = self.header()?;
+                        if chunk_major != major {
+                            return None;
+                        }
+                        if !first {
+                            out.push_str(", ");
+                        }
+                        first = false;
+                        self.string(out, major, length?)?;
+                    }
+                    out.push(')');
+                }
+            },
+            4 | 5 => {
+                let (open, close) = if major == 4 { ('[', ']') } else { ('{', '}') };
+                out.push(open);
+                if argument.is_none() {
+                    out.push_str("_ ");
+                }
+
+                let mut remaining = argument;
+                let mut first = true;
+                while self.next_entry(&mut remaining)? {
+                    if !first {
+                        out.push_str(", ");
+                    }
+                    first = false;
+                    self.item(out, depth + 1)?;
+                    if major == 5 {
+                        out.push_str(": ");
+                        self.item(out, depth + 1)?;
+                    }
+                }
+                out.push(close);
+            }
+            6 => {
+                let _ = write!(out, "{}(", argument?);
+                self.item(out, depth + 1)?;
+                out.push(')');
+            }
+            _ => match info {
+                20 => out.push_str("false"),
+                21 => out.push_str("true"),
+                22 => out.push_str("null"),
+                23 => out.push_str("undefined"),
+                0..=19 | 24 => {
+                    let _ = write!(out, "simple({})", argument?);
+                }
+                25 => write_float(out, half_to_f64(argument? as u16), false),
+                26 => write_float(out, f64::from(f32::from_bits(argument? as u32)), true),
+                27 => write_float(out, f64::from_bits(argument?), false),
+                _ => return None,
+            },
+        }
+        Some(())
+    }
+
+    /// Reads an initial byte and its argument; the argument is `None` for
+    /// indefinite-length items.
+    fn header(&mut self) -> Option<(u8, Option<u64>)> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+
+        let size = match info {
+            0..=23 => return Some((major, Some(u64::from(info)))),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            31 if (2..=5).contains(&major) => return Some((major, None)),
+            _ => return None,
+        };
+        let argument = self
+            .take(size)?
+            .iter()
+            .fold(0_u64, |value, byte| (value << 8) | u64::from(*byte));
+        Some((major, Some(argument)))
+    }
+
+    /// Writes a definite-length byte or text string of `length` bytes.
+    fn string(&mut self, out: &mut String, major: u8, length: u64) -> Option<()> {
+        let bytes = self.take(usize::try_from(length).ok()?)?;
+        if major == 2 {
+            let _ = write!(out, "h'{}'", hex(bytes));
+        } else {
+            out.push_str(&json_string(std::str::from_utf8(bytes).ok()?));
+        }
+        Some(())
+    }
+
+    /// Returns whether another element or entry follows, counting down a
+    /// definite length or consuming the break of an indefinite one.
+    fn next_entry(&mut self, remaining: &mut Option<u64>) -> Option<bool> {
+        match remaining {
+            Some(0) => Some(false),
+            Some(count) => {
+                *count -= 1;
+                Some(true)
+            }
+            None => Some(!self.at_break()?),
+        }
+    }
+
+    /// Consumes a break stop code if one is next.
+    fn at_break(&mut self) -> Option<bool> {
+        let is_break = *self.bytes.get(self.position)? == 0xff;
+        if is_break {
+            self.position += 1;
+        }
+        Some(is_break)
+    }
+
+    fn take(&mut self, length: usize) -> Option<&[u8]> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())?;
+
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Some(taken)
+    }
+}
+
+/// Writes a float so that it always reads as one: with a decimal point or an
+/// exponent, or as `NaN`, `Infinity` or `-Infinity`.
+fn write_float(out: &mut String, value: f64, single: bool) {
+    if value.is_nan() {
+        out.push_str("NaN");
+    } else if value.is_infinite() {
+        out.push_str(if value > 0.0 { "Infinity" } else { "-Infinity" });
+    } else if single {
+        // Shortest form that reads back as the same single-precision float
+        let _ = write!(out, "{:?}", value as f32);
+    } else {
+        let _ = write!(out, "{:?}", value);
+    }
+}
+
+/// Converts an IEEE 754 half-precision float to a double.
+fn half_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((bits >> 10) & 0x1f);
+    let mantissa = f64::from(bits & 0x3ff);
+
+    sign * match exponent {
+        0 => mantissa * 2_f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2_f64.powi(exponent - 15),
+    }
+}
+
+/// Quotes `text` as a JSON string, which is also how diagnostic notation
+/// writes text strings.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats bytes as lowercase hex.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    fn frame(value: &impl Serialize) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        ciborium::ser::into_writer(value, &mut buffer).unwrap();
+        buffer
+    }
+
+    #[derive(Serialize)]
+    #[serde(tag = "type")]
+    struct Fib {
+        n: u32,
+    }
+
+    #[test]
+    fn test_describe_frame() {
+        assert_eq!(describe_frame(&frame(&42_u64)), "42");
+        assert_eq!(describe_frame(&frame(&"text")), "\"text\"");
+        assert_eq!(describe_frame(&frame(&(1, -2))), "[1, -2]");
+        assert_eq!(
+            describe_frame(&frame(&Value::Bytes(vec![0x00, 0xff]))),
+            "h'00ff'"
+        );
+        assert_eq!(describe_frame(&frame(&Fib { n: 5 })), "Fib {n: 5}");
+        assert_eq!(describe_frame(&[0xff, 0x00]), "<invalid frame ff00>");
+    }
+
+    #[test]
+    fn test_describe_data_in_diagnostic_notation() {
+        #[rustfmt::skip]
+        let frame = [
+            0xa5,
+            0x61, b'a', 0xf9, 0x3e, 0x00,
+            0x61, b'b', 0x84, 0xf0, 0xf7, 0x41, 0x01, 0xc1, 0x02,
+            0x61, b'c', 0x20,
+            0x61, b'd', 0xfb, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0,
+            0x61, b'e', 0x9f, 0xfa, 0x47, 0xc3, 0x50, 0x00, 0xf9, 0x7c, 0x00, 0xff,
+        ];
+
+        assert_eq!(
+            describe_frame(&frame),
+            r#"{"a": 1.5, "b": [simple(16), undefined, h'01', 1(2)], "c": -1, "d": 1.0, "e": [_ 100000.0, Infinity]}"#
+        );
+        assert_eq!(describe_value(&Value::Float(2.0)), "2.0");
+        assert_eq!(describe_value(&Value::Float(f64::NAN)), "NaN");
+        assert_eq!(
+            describe_frame(&[0x7f, 0x61, b'a', 0x61, b'b', 0xff]),
+            r#"(_ "a", "b")"#
+        );
+    }
+}
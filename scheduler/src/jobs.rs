@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+
+use crate::{Error, Result, Scheduler, SchedulerTask};
+
+/// Identifier of a job submitted to a [`JobManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(u64);
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// How the [`JobManager`] interleaves steps across jobs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Policy {
+    /// Every job executes one step per turn.
+    #[default]
+    RoundRobin,
+    /// Every job executes as many steps per turn as its weight.
+    Weighted,
+}
+
+/// Lifecycle state of a job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    /// The job still has pending tasks.
+    Running,
+    /// All tasks of the job have been executed.
+    Completed,
+    /// A task of the job failed with the given message.
+    Failed(String),
+}
+
+#[derive(Debug)]
+struct Job {
+    scheduler: Box<Scheduler>,
+    weight: usize,
+    status: JobStatus,
+}
+
+/// Runs several independent computations side by side.
+///
+/// Every job gets its own [`Scheduler`], so the data stacks of different jobs
+/// never mix. Jobs take turns according to the configured [`Policy`]; a failing
+/// job is marked as failed without affecting the others.
+#[derive(Debug, Default)]
+pub struct JobManager {
+    policy: Policy,
+    next_id: u64,
+    jobs: BTreeMap<JobId, Job>,
+    /// Running jobs in turn order
+    queue: VecDeque<JobId>,
+}
+
+impl JobManager {
+    /// Creates a new job manager using the round-robin policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new job manager using the given policy.
+    pub fn with_policy(policy: Policy) -> Self {
+        Self {
+            policy,
+            ..Self::default()
+        }
+    }
+
+    /// Submits a root task as a new job with weight 1.
+    pub fn submit(&mut self, task: Box<dyn SchedulerTask>) -> Result<JobId> {
+        self.submit_weighted(task, 1)
+    }
+
+    /// Submits a root task as a new job with the given weight.
+    ///
+    /// The weight is the number of steps the job executes per turn under
+    /// [`Policy::Weighted`] and is ignored by other policies.
+    pub fn submit_weighted(
+        &mut self,
+        task: Box<dyn SchedulerTask>,
+        weight: usize,
+    ) -> Result<JobId> {
+        let mut scheduler = Box::new(Scheduler::new());
+        scheduler.push_task(task)?;
+
+        let id = JobId(self.next_id);
+        self.next_id += 1;
+
+        self.jobs.insert(
+            id,
+            Job {
+                scheduler,
+                weight: weight.max(1),
+                status: JobStatus::Running,
+            },
+        );
+        self.queue.push_back(id);
+
+        Ok(id)
+    }
+
+    /// Gives the next job its turn.
+    ///
+    /// Returns the job that ran, or `None` if no job has pending tasks.
+    pub fn step(&mut self) -> Option<JobId> {
+        let id = self.queue.pop_front()?;
+        let job = self.jobs.get_mut(&id)?;
+
+        let quantum = match self.policy {
+            Policy::RoundRobin => 1,
+            Policy::Weighted => job.weight,
+        };
+
+        for _ in 0..quantum {
+            if let Err(e) = job.scheduler.execute() {
+                job.status = JobStatus::Failed(e.to_string());
+                break;
+            }
+            if job.scheduler.is_empty() {
+                job.status = JobStatus::Completed;
+                break;
+            }
+        }
+
+        if job.status == JobStatus::Running {
+            self.queue.push_back(id);
+        }
+
+        Some(id)
+    }
+
+    /// Runs jobs until none of them has pending tasks.
+    pub fn run_all(&mut self) {
+        while self.step().is_some() {}
+    }
+
+    /// Returns true if no job has pending tasks.
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Returns the status of a job.
+    pub fn status(&self, id: JobId) -> Result<JobStatus> {
+        self.job(id).map(|job| job.status.clone())
+    }
+
+    /// Pops the most recently pushed data of a completed job.
+    ///
+    /// Like [`Scheduler::pop_data`], repeated calls return successive values.
+    pub fn result<T: DeserializeOwned>(&mut self, id: JobId) -> Result<T> {
+        let job = self.jobs.get_mut(&id).ok_or(Error::UnknownJob(id))?;
+
+        match &job.status {
+            JobStatus::Running => Err(Error::JobPending(id)),
+            JobStatus::Failed(message) => Err(Error::JobFailed(id, message.clone())),
+            JobStatus::Completed => job.scheduler.pop_data(),
+        }
+    }
+
+    /// Removes a job and its stacks from the manager.
+    pub fn remove(&mut self, id: JobId) -> Result<JobStatus> {
+        let job = self.jobs.remove(&id).ok_or(Error::UnknownJob(id))?;
+        self.queue.retain(|queued| *queued != id);
+
+        Ok(job.status)
+    }
+
+    fn job(&self, id: JobId) -> Result<&Job> {
+        self.jobs.get(&id).ok_or(Error::UnknownJob(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Count {
+        remaining: u32,
+        total: u32,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Count {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            if self.remaining == 0 {
+                return Err(Error::Task("nothing to count".to_string()));
+            }
+            self.remaining -= 1;
+            self.total += 1;
+            if self.remaining == 0 {
+                scheduler.push_data(&self.total)?;
+            }
+            Ok(vec![])
+        }
+
+        fn push_self(&mut self) -> bool {
+            self.remaining > 0
+        }
+    }
+
+    fn count(remaining: u32) -> Box<dyn SchedulerTask> {
+        Box::new(Count {
+            remaining,
+            total: 0,
+        })
+    }
+
+    #[test]
+    fn test_round_robin_interleaving() {
+        let mut manager = JobManager::new();
+        let a = manager.submit(count(3)).unwrap();
+        let b = manager.submit(count(1)).unwrap();
+
+        let order: Vec<JobId> = std::iter::from_fn(|| manager.step()).collect();
+
+        assert_eq!(order, vec![a, b, a, a]);
+        assert_eq!(manager.result::<u32>(a).unwrap(), 3);
+        assert_eq!(manager.result::<u32>(b).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_weighted_interleaving() {
+        let mut manager = JobManager::with_policy(Policy::Weighted);
+        let a = manager.submit_weighted(count(4), 2).unwrap();
+        let b = manager.submit_weighted(count(2), 1).unwrap();
+
+        let order: Vec<JobId> = std::iter::from_fn(|| manager.step()).collect();
+
+        assert_eq!(order, vec![a, b, a, b]);
+    }
+
+    #[test]
+    fn test_failed_job_is_isolated() {
+        let mut manager = JobManager::new();
+        let failing = manager.submit(count(0)).unwrap();
+        let ok = manager.submit(count(2)).unwrap();
+
+        assert!(matches!(
+            manager.result::<u32>(ok),
+            Err(Error::JobPending(_))
+        ));
+
+        manager.run_all();
+
+        assert!(matches!(
+            manager.status(failing).unwrap(),
+            JobStatus::Failed(_)
+        ));
+        assert!(matches!(
+            manager.result::<u32>(failing),
+            Err(Error::JobFailed(..))
+        ));
+        assert_eq!(manager.result::<u32>(ok).unwrap(), 2);
+
+        manager.remove(ok).unwrap();
+        assert!(matches!(manager.status(ok), Err(Error::UnknownJob(_))));
+    }
+}
@@ -0,0 +1,474 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::observer::{Event, Observer};
+use crate::selection::raise;
+use crate::snapshot::write_atomic;
+use crate::{DecodeLimits, Error, Result, Scheduler, SchedulerStack};
+
+/// Magic bytes at the start of every journal file.
+const MAGIC: &[u8; 4] = b"SCHJ";
+
+/// Current journal format version.
+const VERSION: u8 = 1;
+
+/// Size of a record header: tag byte and little-endian payload length.
+const RECORD_HEADER_SIZE: usize = 5;
+
+/// When the journal forces its writes to stable storage.
+///
+/// Records are always flushed to the operating system at the end of every
+/// step, so they survive the process dying. Syncing additionally protects them
+/// against power loss.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Sync after every step and every change made outside of a step.
+    #[default]
+    Always,
+    /// Sync after every `n` steps.
+    EveryN(u64),
+    /// Never sync explicitly and leave it to the operating system.
+    Never,
+}
+
+/// Kind of a journal record; the discriminant is the on-disk tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tag {
+    StepStarted = 0,
+    TaskPopped = 1,
+    TaskPushed = 2,
+    DataPopped = 3,
+    DataPushed = 4,
+    StepFinished = 5,
+    StepFailed = 6,
+    Cleared = 7,
+    TaskSelected = 8,
+}
+
+impl Tag {
+    fn from_byte(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0 => Tag::StepStarted,
+            1 => Tag::TaskPopped,
+            2 => Tag::TaskPushed,
+            3 => Tag::DataPopped,
+            4 => Tag::DataPushed,
+            5 => Tag::StepFinished,
+            6 => Tag::StepFailed,
+            7 => Tag::Cleared,
+            8 => Tag::TaskSelected,
+            _ => return None,
+        })
+    }
+}
+
+/// Write-ahead journal of every change made to a [`Scheduler`].
+///
+/// The journal lives in a directory holding a snapshot and a log of the
+/// changes made since that snapshot. Both files carry a generation number;
+/// [`Journal::checkpoint`] writes a new snapshot, starts an empty log for the
+/// next generation and removes the previous one. [`Journal::recover`] rebuilds
+/// the scheduler from the newest snapshot by replaying its log.
+///
+/// The effects of a step are only replayed once the step is complete, so a
+/// step interrupted by a crash is executed again after recovery.
+#[derive(Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    generation: u64,
+    writer: BufWriter<File>,
+    policy: FsyncPolicy,
+    in_step: bool,
+    /// Steps written since the last sync
+    unsynced: u64,
+}
+
+impl Journal {
+    /// Starts journaling the current state of `scheduler` into `dir`.
+    ///
+    /// The returned journal must be attached with
+    /// [`Scheduler::add_observer`] to record subsequent changes.
+    pub fn create(
+        dir: impl AsRef<Path>,
+        policy: FsyncPolicy,
+        scheduler: &Scheduler,
+    ) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let generation = latest_generation(&dir)?.map_or(0, |generation| generation + 1);
+        let writer = start_generation(&dir, generation, &scheduler.snapshot())?;
+
+        Ok(Self {
+            dir,
+            generation,
+            writer,
+            policy,
+            in_step: false,
+            unsynced: 0,
+        })
+    }
+
+    /// Replaces the journal's snapshot with the current state of `scheduler`.
+    ///
+    /// This bounds both the journal size and the recovery time. Fails if no
+    /// journal is attached to the scheduler.
+    pub fn checkpoint(scheduler: &mut Scheduler) -> Result<()> {
+        let snapshot = scheduler.snapshot();
+        let journal = scheduler
+            .observer_mut::<Journal>()
+            .ok_or_else(|| Error::Journal("no journal attached to the scheduler".to_string()))?;
+
+        if journal.in_step {
+            return Err(Error::Journal(
+                "cannot checkpoint in the middle of a step".to_string(),
+            ));
+        }
+
+        let generation = journal.generation + 1;
+        journal.writer = start_generation(&journal.dir, generation, &snapshot)?;
+        journal.generation = generation;
+        journal.unsynced = 0;
+
+        remove_generation(&journal.dir, generation - 1)
+    }
+
+    /// Rebuilds a scheduler from the newest snapshot and journal in `dir`.
+    ///
+    /// Trailing records of an interrupted step or a partially written record
+    /// are ignored. The recovered scheduler has no journal attached. Both the
+    /// snapshot and the replayed frames are checked against `limits`, if
+    /// given, and the limits stay enforced on the recovered scheduler.
+    pub fn recover(dir: impl AsRef<Path>, limits: Option<&DecodeLimits>) -> Result<Scheduler> {
+        let dir = dir.as_ref();
+        let generation = latest_generation(dir)?
+            .ok_or_else(|| Error::Journal(format!("no snapshot found in {}", dir.display())))?;
+
+        let snapshot = fs::read(snapshot_path(dir, generation))?;
+        let mut scheduler = Scheduler::restore_limited(&snapshot, limits)?;
+
+        let log = match fs::read(log_path(dir, generation)) {
+            Ok(log) => log,
+            // A crash between writing the snapshot and the log leaves no log
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(scheduler),
+            Err(e) => return Err(e.into()),
+        };
+        replay(&mut scheduler.stack, &log)?;
+        scheduler.enforce_limits(limits)?;
+
+        Ok(scheduler)
+    }
+
+    /// Returns the generation of the current snapshot and log.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn write_record(&mut self, tag: Tag, payload: &[u8]) -> Result<()> {
+        let length = u32::try_from(payload.len())
+            .map_err(|_| Error::Journal("record too large".to_string()))?;
+
+        self.writer.write_all(&[tag as u8])?;
+        self.writer.write_all(&length.to_le_bytes())?;
+        self.writer.write_all(payload)?;
+
+        Ok(())
+    }
+
+    /// Makes the records written so far durable according to the policy.
+    fn commit(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.unsynced += 1;
+
+        let sync = match self.policy {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::EveryN(n) => self.unsynced >= n,
+            FsyncPolicy::Never => false,
+        };
+        if sync {
+            self.writer.get_ref().sync_data()?;
+            self.unsynced = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl Observer for Journal {
+    fn on_event(&mut self, event: &Event<'_>, _stack: &SchedulerStack) -> Result<()> {
+        let depth;
+        let (tag, payload) = match *event {
+            // Leaves the stack unchanged, so recovery doesn't need it
+            Event::Rescheduled => return Ok(()),
+            Event::StepStarted => (Tag::StepStarted, &[][..]),
+            Event::TaskPopped(frame) => (Tag::TaskPopped, frame),
+            Event::TaskPushed(frame) => (Tag::TaskPushed, frame),
+            Event::DataPopped(frame) => (Tag::DataPopped, frame),
+            Event::DataPushed(frame) => (Tag::DataPushed, frame),
+            Event::StepFinished => (Tag::StepFinished, &[][..]),
+            Event::StepFailed => (Tag::StepFailed, &[][..]),
+            Event::Cleared => (Tag::Cleared, &[][..]),
+            Event::TaskSelected(selected) => {
+                depth = (selected as u64).to_le_bytes();
+                (Tag::TaskSelected, &depth[..])
+            }
+        };
+
+        self.write_record(tag, payload)?;
+
+        match tag {
+            Tag::StepStarted => {
+                self.in_step = true;
+                Ok(())
+            }
+            Tag::StepFinished | Tag::StepFailed => {
+                self.in_step = false;
+                self.commit()
+            }
+            _ if self.in_step => Ok(()),
+            _ => self.commit(),
+        }
+    }
+}
+
+/// Applies the complete records of a log to the stack.
+fn replay(stack: &mut SchedulerStack, log: &[u8]) -> Result<()> {
+    if log.len() < MAGIC.len() + 1 || &log[..MAGIC.len()] != MAGIC {
+        return Err(Error::Journal("missing journal header".to_string()));
+    }
+    if log[MAGIC.len()] != VERSION {
+        return Err(Error::Journal(format!(
+            "unsupported journal version {}",
+            log[MAGIC.len()]
+        )));
+    }
+
+    let mut offset = MAGIC.len() + 1;
+    let mut step: Option<Vec<(Tag, &[u8])>> = None;
+
+    while log.len() - offset >= RECORD_HEADER_SIZE {
+        let tag = Tag::from_byte(log[offset]).ok_or_else(|| {
+            Error::Journal(format!(
+                "unknown record tag {} at offset {}",
+                log[offset], offset
+            ))
+        })?;
+
+        let mut length = [0; 4];
+        length.copy_from_slice(&log[offset + 1..offset + RECORD_HEADER_SIZE]);
+        let length = u32::from_le_bytes(length) as usize;
+
+        let start = offset + RECORD_HEADER_SIZE;
+        let Some(payload) = log.get(start..start + length) else {
+            // Partially written record at the end of the log
+            break;
+        };
+        offset = start + length;
+
+        match (tag, &mut step) {
+            // An unterminated step followed by a new one was interrupted
+            (Tag::StepStarted, _) => step = Some(Vec::new()),
+            (Tag::StepFinished | Tag::StepFailed, Some(records)) => {
+                for (tag, payload) in records.drain(..) {
+                    apply(stack, tag, payload)?;
+                }
+                step = None;
+            }
+            (Tag::StepFinished | Tag::StepFailed, None) => {
+                return Err(Error::Journal(format!(
+                    "end of step without a start at offset {}",
+                    start - RECORD_HEADER_SIZE
+                )));
+            }
+            (_, Some(records)) => records.push((tag, payload)),
+            (_, None) => apply(stack, tag, payload)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn apply(stack: &mut SchedulerStack, tag: Tag, payload: &[u8]) -> Result<()> {
+    let popped = match tag {
+        Tag::TaskPushed => {
+            stack.push_back(payload)?;
+            None
+        }
+        Tag::DataPushed => {
+            stack.push_front(payload)?;
+            None
+        }
+        Tag::TaskPopped => Some(stack.pop_back()?),
+        Tag::DataPopped => Some(stack.pop_front()?),
+        Tag::Cleared => {
+            stack.clear();
+            None
+        }
+        Tag::TaskSelected => {
+            let depth = <[u8; 8]>::try_from(payload)
+                .map_err(|_| Error::Journal("invalid task selection record".to_string()))?;
+            raise(stack, u64::from_le_bytes(depth) as usize, |_| {})?;
+            None
+        }
+        Tag::StepStarted | Tag::StepFinished | Tag::StepFailed => None,
+    };
+
+    match popped {
+        Some(frame) if frame != payload => Err(Error::Journal(
+            "popped frame doesn't match the journal".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn snapshot_path(dir: &Path, generation: u64) -> PathBuf {
+    dir.join(format!("snapshot-{:020}.bin", generation))
+}
+
+fn log_path(dir: &Path, generation: u64) -> PathBuf {
+    dir.join(format!("journal-{:020}.log", generation))
+}
+
+/// Returns the newest generation that has a snapshot in `dir`.
+fn latest_generation(dir: &Path) -> Result<Option<u64>> {
+    let mut latest = None;
+
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let generation = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("snapshot-"))
+            .and_then(|name| name.strip_suffix(".bin"))
+            .and_then(|generation| generation.parse::<u64>().ok());
+
+        latest = latest.max(generation);
+    }
+
+    Ok(latest)
+}
+
+/// Writes the snapshot and an empty log for a new generation.
+fn start_generation(dir: &Path, generation: u64, snapshot: &[u8]) -> Result<BufWriter<File>> {
+    write_atomic(&snapshot_path(dir, generation), snapshot)?;
+
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(log_path(dir, generation))?;
+
+    let mut writer = BufWriter::new(file);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+
+    Ok(writer)
+}
+
+fn remove_generation(dir: &Path, generation: u64) -> Result<()> {
+    for path in [snapshot_path(dir, generation), log_path(dir, generation)] {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Accumulate {
+        remaining: u32,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Accumulate {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            let total: u32 = if scheduler.is_empty_data() {
+                0
+            } else {
+                scheduler.pop_data()?
+            };
+            scheduler.push_data(&(total + self.remaining))?;
+            self.remaining -= 1;
+            Ok(vec![])
+        }
+
+        fn push_self(&mut self) -> bool {
+            self.remaining > 0
+        }
+    }
+
+    #[test]
+    fn test_recover_enforces_limits_on_replayed_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut scheduler = Scheduler::new();
+
+        let journal = Journal::create(dir.path(), FsyncPolicy::Never, &scheduler).unwrap();
+        scheduler.add_observer(journal);
+        scheduler.push_data(&"longer than allowed").unwrap();
+
+        let limits = DecodeLimits::new().max_string_len(4);
+        assert!(matches!(
+            Journal::recover(dir.path(), Some(&limits)),
+            Err(Error::LimitExceeded(_))
+        ));
+        assert!(Journal::recover(dir.path(), None).is_ok());
+    }
+
+    #[test]
+    fn test_recover_discards_interrupted_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Accumulate { remaining: 4 }))
+            .unwrap();
+
+        let journal = Journal::create(dir.path(), FsyncPolicy::Never, &scheduler).unwrap();
+        scheduler.add_observer(journal);
+        scheduler.execute().unwrap();
+        scheduler.execute().unwrap();
+
+        // Simulate a crash in the middle of the third step
+        let journal = scheduler.observer_mut::<Journal>().unwrap();
+        journal.write_record(Tag::StepStarted, &[]).unwrap();
+        journal.write_record(Tag::TaskPopped, &[1, 2, 3]).unwrap();
+        journal.writer.flush().unwrap();
+
+        let mut recovered = Journal::recover(dir.path(), None).unwrap();
+        recovered.execute_all().unwrap();
+        assert_eq!(recovered.pop_data::<u32>().unwrap(), 10);
+        assert!(recovered.is_empty_data());
+    }
+
+    #[test]
+    fn test_checkpoint_starts_new_generation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Accumulate { remaining: 3 }))
+            .unwrap();
+
+        assert!(Journal::checkpoint(&mut scheduler).is_err());
+
+        let journal = Journal::create(dir.path(), FsyncPolicy::Always, &scheduler).unwrap();
+        scheduler.add_observer(journal);
+        scheduler.execute().unwrap();
+        Journal::checkpoint(&mut scheduler).unwrap();
+        scheduler.execute().unwrap();
+
+        assert_eq!(scheduler.observer::<Journal>().unwrap().generation(), 1);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        let recovered = Journal::recover(dir.path(), None).unwrap();
+        assert_eq!(recovered.snapshot(), scheduler.snapshot());
+    }
+}
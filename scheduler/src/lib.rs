@@ -7,20 +7,139 @@
 //! - Task-based execution model
 //! - Bidirectional stack for storing tasks and data
 //! - Serialization of tasks using CBOR
+//! - Parallel fork/join of independent subtasks
+//! - Work-stealing execution of independent root jobs
+//! - Async execution on tokio (`tokio` feature)
+//! - Multiple concurrent jobs with isolated stacks
+//! - Priority and deadline-based selection of independent tasks
+//! - Snapshots and a write-ahead journal for crash recovery
+//! - Automatic periodic checkpointing with rotation
+//! - Incremental snapshot diffs and readable frame-level diffs
+//! - Per task type schema versions with snapshot migrations
+//! - HMAC-signed and ChaCha20-Poly1305 encrypted snapshots
+//! - Decoding limits and a task type allowlist for untrusted frames
+//! - Observer hooks for every change to the stacks
+//! - Task backtraces showing the spawning chain of a failed task
+//! - Memory-mapped file-backed stacks that survive restarts
+//! - Deterministic recording and byte-for-byte verified replay
+//! - Hash-chained per-step state commitments
+//! - Execution trace export (binary and CSV) with a trace checker
+//! - Spawn tree recording with Graphviz DOT export
+//! - Chrome Trace Event timelines for Perfetto
+//! - Per task type execution metrics with a Prometheus exporter
+//! - Folded-stack flame graph profiling by spawn ancestry
+//! - Stack integrity validation of layouts and frames
 //! - Error handling
 //!
 
 /// Error handling types and utilities
 pub mod error;
 
+/// Spawn ancestry backtraces of failed tasks
+pub mod backtrace;
+
+/// Automatic periodic checkpointing
+pub mod checkpoint;
+
+/// Hash-chained commitments to the stack state
+pub mod commitment;
+
+/// Incremental snapshots and readable snapshot diffs
+pub mod diff;
+
+/// Async execution on a tokio runtime
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+
+/// Multi-threaded work-stealing executor for root jobs
+pub mod executor;
+
+/// Fork/join execution of independent branches
+pub mod fork;
+
+/// Decoding limits for untrusted frames
+pub mod limits;
+
+/// Human-readable descriptions of stack frames
+pub mod inspect;
+
+/// Concurrent jobs with isolated stacks
+pub mod jobs;
+
+/// Write-ahead journal for crash recovery
+pub mod journal;
+
+/// Per task type execution metrics and peak stack usage
+pub mod metrics;
+
+/// Hooks for observing changes to the scheduler's stacks
+pub mod observer;
+
+/// Flame graph profiling of spawn ancestries
+pub mod profile;
+
+/// Deterministic recording and replay of executions
+pub mod recording;
+
+/// Signed and encrypted snapshots
+pub mod sealed;
+
+/// Schema versions and migrations of task types in snapshots
+pub mod schema;
+
+/// Priority and deadline-based selection of pending tasks
+pub mod selection;
+
+/// Snapshots of the scheduler state
+pub mod snapshot;
+
+/// Spawn tree recording and Graphviz export
+pub mod spawn;
+
+/// Timed task executions exported as Chrome Trace Event JSON
+pub mod timeline;
+
+/// Execution traces for provers
+pub mod trace;
+
 /// Bidirectional stack implementation
 pub mod stack;
 
+/// In-memory and memory-mapped file storage for the stack
+pub mod storage;
+
+/// Integrity checks of the stack and its frames
+pub mod validate;
+
 // Re-export commonly used types
+#[cfg(feature = "tokio")]
+pub use asynchronous::{AsyncSchedulerTask, AsyncTask};
+pub use backtrace::TaskBacktrace;
+pub use checkpoint::{CheckpointPolicy, Checkpointer};
+pub use commitment::{HashChain, Sha256Hasher, StateHasher};
+pub use diff::{FrameDiff, SnapshotDiff, StackDiff};
 pub use error::{Error, Result};
+pub use executor::{Executor, JobOutput};
+pub use fork::Fork;
+pub use jobs::{JobId, JobManager, JobStatus, Policy};
+pub use journal::{FsyncPolicy, Journal};
+pub use limits::DecodeLimits;
+pub use metrics::{Metrics, TaskMetrics};
+pub use observer::{Event, Observer};
+pub use profile::{Profiler, Samples, Weight};
+pub use recording::{Recorder, Recording, ReplayReport};
+pub use selection::Selection;
+pub use spawn::{DataFlow, SpawnKind, SpawnNode, SpawnRecorder};
+pub use stack::{Corruption, ValidationReport};
+pub use storage::{SCHEDULER_CAPACITY, SchedulerStorage};
+pub use timeline::{Span, Timeline};
+pub use trace::{Trace, TraceRow, Tracer};
 
+use backtrace::Ancestry;
+use selection::{Candidate, Candidates};
 use serde::{Serialize, de::DeserializeOwned};
 use stack::BidirectionalStack;
+use std::any::Any;
 use std::io::Cursor;
 
 /// Trait for tasks that can be executed by the scheduler.
@@ -36,8 +155,48 @@ pub trait SchedulerTask: Send + Sync {
     fn push_self(&mut self) -> bool {
         false
     }
+
+    /// Priority of the task; higher values run first.
+    ///
+    /// Used to pick the next pending task under [`Selection::Priority`], and
+    /// to break ties under [`Selection::EarliestDeadline`].
+    fn priority(&self) -> u32 {
+        0
+    }
+
+    /// Deadline of the task, in a unit chosen by the application; earlier
+    /// deadlines run first.
+    ///
+    /// Used to pick the next pending task under
+    /// [`Selection::EarliestDeadline`]; tasks without a deadline run last.
+    fn deadline(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns true if the task may run before the pending tasks above it.
+    ///
+    /// An independent task pops no data it didn't push itself, directly or
+    /// through the tasks it spawns, and leaves the data stack as it found it.
+    /// Only such tasks are moved ahead under a [`Selection`] other than
+    /// [`Selection::Lifo`], so task types opt in to selection by returning
+    /// true here.
+    fn independent(&self) -> bool {
+        false
+    }
+
+    /// Returns the async task wrapped by this task, if any.
+    ///
+    /// Only [`AsyncTask`] overrides this; it lets the async runner tell async
+    /// tasks apart from regular ones after popping them from the task stack.
+    #[cfg(feature = "tokio")]
+    fn as_async(&mut self) -> Option<&mut AsyncTask> {
+        None
+    }
 }
 
+/// Stack type backing a [`Scheduler`].
+pub type SchedulerStack = BidirectionalStack<SCHEDULER_CAPACITY, 2, SchedulerStorage>;
+
 /// Scheduler that manages task execution and data flow.
 ///
 /// Uses a bidirectional stack to store tasks and data.
@@ -45,7 +204,23 @@ pub trait SchedulerTask: Send + Sync {
 pub struct Scheduler {
     /// The stack used for storing tasks and data.
     /// Tasks are stored at the back, data at the front.
-    stack: BidirectionalStack<65536, 2>,
+    stack: SchedulerStack,
+
+    /// Hooks notified about every change to the stack.
+    observers: Vec<Box<dyn Observer>>,
+
+    /// Limits checked before popped frames are deserialized.
+    limits: Option<DecodeLimits>,
+
+    /// Which task spawned each pending task, for backtraces of failed steps;
+    /// only tracked once enabled.
+    ancestry: Ancestry,
+
+    /// How the next task to execute is picked.
+    selection: Selection,
+
+    /// Priority, deadline and independence of each pending task, for selection.
+    candidates: Candidates,
 }
 
 impl Scheduler {
@@ -59,10 +234,8 @@ impl Scheduler {
         let mut buffer = Vec::new();
         ciborium::ser::into_writer(&task, &mut buffer).map_err(Error::Serialization)?;
 
-        self.stack
-            .push_back(&buffer)
-            .map_err(Error::StackCapacity)?;
-
+        self.push_task_frame(&buffer)?;
+        self.candidates.read(Candidate::of(task.as_ref()));
         Ok(())
     }
 
@@ -71,16 +244,15 @@ impl Scheduler {
         let mut buffer = Vec::new();
         ciborium::ser::into_writer(data, &mut buffer).map_err(Error::Serialization)?;
 
-        self.stack
-            .push_front(&buffer)
-            .map_err(Error::StackCapacity)?;
-
-        Ok(())
+        self.push_data_frame(&buffer)
     }
 
     /// Pops a task from the scheduler's task stack.
     pub fn pop_task(&mut self) -> Result<Box<dyn SchedulerTask>> {
-        let data = self.stack.pop_back()?;
+        let data = self.pop_task_frame()?;
+        if let Some(limits) = &self.limits {
+            limits.check_task(&data)?;
+        }
 
         let mut cursor = Cursor::new(&data);
         let result = ciborium::de::from_reader(&mut cursor).map_err(Error::Deserialization)?;
@@ -90,7 +262,10 @@ impl Scheduler {
 
     /// Pops data from the scheduler's data stack.
     pub fn pop_data<T: DeserializeOwned>(&mut self) -> Result<T> {
-        let data = self.stack.pop_front()?;
+        let data = self.pop_data_frame()?;
+        if let Some(limits) = &self.limits {
+            limits.check_data(&data)?;
+        }
 
         let mut cursor = Cursor::new(&data);
         let result = ciborium::de::from_reader(&mut cursor).map_err(Error::Deserialization)?;
@@ -98,17 +273,115 @@ impl Scheduler {
         Ok(result)
     }
 
+    /// Pushes an already serialized data frame onto the data stack.
+    pub(crate) fn push_data_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.stack.push_front(frame).map_err(Error::StackCapacity)?;
+
+        self.notify(Event::DataPushed(frame))
+    }
+
+    /// Pops a data frame from the data stack without deserializing it.
+    pub(crate) fn pop_data_frame(&mut self) -> Result<Vec<u8>> {
+        let frame = self.stack.pop_front()?;
+
+        self.notify(Event::DataPopped(&frame))?;
+        Ok(frame)
+    }
+
+    /// Pushes an already serialized task frame onto the task stack.
+    pub(crate) fn push_task_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.stack.push_back(frame).map_err(Error::StackCapacity)?;
+        self.ancestry.pushed();
+        self.candidates.pushed();
+
+        self.notify(Event::TaskPushed(frame))
+    }
+
+    /// Pops a task frame from the task stack without deserializing it.
+    pub(crate) fn pop_task_frame(&mut self) -> Result<Vec<u8>> {
+        let frame = self.stack.pop_back()?;
+        self.ancestry.popped(&frame);
+        self.candidates.popped();
+
+        self.notify(Event::TaskPopped(&frame))?;
+        Ok(frame)
+    }
+
+    /// Removes every frame from the data stack, bottom of the stack first.
+    pub(crate) fn take_data_frames(&mut self) -> Result<Vec<Vec<u8>>> {
+        let mut frames = Vec::new();
+        while !self.is_empty_data() {
+            frames.push(self.pop_data_frame()?);
+        }
+        frames.reverse();
+
+        Ok(frames)
+    }
+
     /// Executes the next task in the scheduler.
     ///
     /// Returns an error if there are no tasks or if execution fails.
+    ///
+    /// Backtraces are opt-in: errors keep their original variant, e.g.
+    /// [`Error::StackCapacity`], unless [`Scheduler::set_backtraces`] was
+    /// called with `true`. Then errors raised once a task was popped are
+    /// wrapped in [`Error::TaskFailed`] with a [`TaskBacktrace`] of the task.
     pub fn execute(&mut self) -> Result<()> {
+        self.start_step()?;
+
+        let result = self.execute_step();
+        self.end_step(result)
+    }
+
+    fn execute_step(&mut self) -> Result<()> {
+        self.select()?;
+        #[cfg(feature = "tokio")]
+        self.check_next_is_sync()?;
         let mut task = self.pop_task()?;
 
         let tasks = task
             .execute(self)
             .map_err(|e| Error::Execution(format!("Task execution failed: {}", e)))?;
 
+        self.schedule(task, tasks)
+    }
+
+    /// Reports the start of a step to the observers.
+    pub(crate) fn start_step(&mut self) -> Result<()> {
+        self.ancestry.start_step();
+        self.notify(Event::StepStarted)
+    }
+
+    /// Reports the outcome of a step to the observers, attaching a backtrace
+    /// to errors of the executed task.
+    pub(crate) fn end_step(&mut self, result: Result<()>) -> Result<()> {
+        let backtrace = self.ancestry.end_step(&self.stack);
+        match result {
+            Ok(()) => self.notify(Event::StepFinished),
+            Err(e) => {
+                // The task's error is what the caller needs; an observer failing
+                // here fails again on its next event
+                let _ = self.notify(Event::StepFailed);
+                Err(match backtrace {
+                    Some(backtrace) => Error::TaskFailed {
+                        error: Box::new(e),
+                        backtrace,
+                    },
+                    None => e,
+                })
+            }
+        }
+    }
+
+    /// Pushes the executed task back if it asks for it, followed by the tasks it returned.
+    pub(crate) fn schedule(
+        &mut self,
+        mut task: Box<dyn SchedulerTask>,
+        tasks: Vec<Box<dyn SchedulerTask>>,
+    ) -> Result<()> {
         if task.push_self() {
+            self.notify(Event::Rescheduled)?;
+            self.ancestry.reschedule();
             self.push_task(task)?;
         }
 
@@ -138,8 +411,80 @@ impl Scheduler {
         self.stack.is_empty_front()
     }
 
+    /// Returns the stack holding the scheduler's tasks and data.
+    pub fn stack(&self) -> &SchedulerStack {
+        &self.stack
+    }
+
     /// Clears all tasks and data from the scheduler.
     pub fn clear(&mut self) {
         self.stack.clear();
+        self.ancestry.clear();
+        self.candidates.clear();
+
+        // Clearing is infallible; an observer failing here fails again on its next event
+        let _ = self.notify(Event::Cleared);
+    }
+
+    /// Attaches an observer that is notified about every change to the stack.
+    pub fn add_observer<O: Observer>(&mut self, observer: O) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Returns the first attached observer of the given type.
+    pub fn observer<O: Observer>(&self) -> Option<&O> {
+        self.observers
+            .iter()
+            .find_map(|observer| (observer.as_ref() as &dyn Any).downcast_ref())
+    }
+
+    /// Returns the first attached observer of the given type, mutably.
+    pub fn observer_mut<O: Observer>(&mut self) -> Option<&mut O> {
+        self.observers
+            .iter_mut()
+            .find_map(|observer| (observer.as_mut() as &mut dyn Any).downcast_mut())
+    }
+
+    /// Detaches and returns the first attached observer of the given type.
+    pub fn remove_observer<O: Observer>(&mut self) -> Option<O> {
+        let position = self
+            .observers
+            .iter()
+            .position(|observer| (observer.as_ref() as &dyn Any).is::<O>())?;
+
+        let observer: Box<dyn Any> = self.observers.remove(position);
+        observer.downcast().ok().map(|observer| *observer)
+    }
+
+    pub(crate) fn notify(&mut self, event: Event<'_>) -> Result<()> {
+        let (front_index, back_index) = (self.stack.front_index(), self.stack.back_index());
+        self.stack
+            .storage_mut()
+            .on_event(&event, front_index, back_index);
+
+        for observer in &mut self.observers {
+            observer.on_event(&event, &self.stack)?;
+        }
+        Ok(())
     }
 }
+
+/// Returns the type name of a serialized task without deserializing the task.
+///
+/// Works for task types that aren't registered in this binary.
+pub fn task_type(frame: &[u8]) -> Result<String> {
+    let mut cursor = Cursor::new(frame);
+    let value: ciborium::Value =
+        ciborium::de::from_reader(&mut cursor).map_err(Error::Deserialization)?;
+
+    value
+        .as_map()
+        .and_then(|entries| {
+            entries
+                .iter()
+                .find(|(key, _)| key.as_text() == Some("type"))
+        })
+        .and_then(|(_, name)| name.as_text())
+        .map(str::to_string)
+        .ok_or_else(|| Error::InvalidData("task frame has no type tag".to_string()))
+}
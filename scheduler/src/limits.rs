@@ -0,0 +1,507 @@
+use std::collections::BTreeSet;
+
+use crate::snapshot::read_snapshot;
+use crate::{Error, Result, Scheduler, SchedulerStack, schema, task_type};
+
+/// Limits on the frames a scheduler is willing to deserialize.
+///
+/// Frames are checked before they're handed to the deserializer, so a
+/// scheduler loaded from an untrusted source can't be made to allocate
+/// oversized collections, recurse deeply or run unexpected task types.
+/// Enable them with [`Scheduler::set_decode_limits`] or
+/// [`Scheduler::restore_with_limits`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum nesting depth of arrays, maps and tags.
+    pub max_depth: usize,
+    /// Maximum number of elements of an array, or of entries of a map.
+    pub max_collection_len: usize,
+    /// Maximum length in bytes of a text or byte string.
+    pub max_string_len: usize,
+    /// Task type names allowed to be popped; `None` allows every registered type.
+    pub allowed_tasks: Option<BTreeSet<String>>,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 32,
+            max_collection_len: 4096,
+            max_string_len: 4096,
+            allowed_tasks: None,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// Creates limits with a depth of 32, collections and strings of up to
+    /// 4096 elements or bytes, and every task type allowed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum nesting depth.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets the maximum number of elements of an array or entries of a map.
+    pub fn max_collection_len(mut self, length: usize) -> Self {
+        self.max_collection_len = length;
+        self
+    }
+
+    /// Sets the maximum length of a text or byte string.
+    pub fn max_string_len(mut self, length: usize) -> Self {
+        self.max_string_len = length;
+        self
+    }
+
+    /// Allows tasks of the given type, and only allowed types from now on.
+    pub fn allow_task(mut self, task_type: impl Into<String>) -> Self {
+        self.allowed_tasks
+            .get_or_insert_with(BTreeSet::new)
+            .insert(task_type.into());
+        self
+    }
+
+    /// Checks a serialized data frame against the limits.
+    pub fn check_data(&self, frame: &[u8]) -> Result<()> {
+        self.scan(frame, None)
+    }
+
+    /// Checks a serialized task frame against the limits and the allowed task types.
+    ///
+    /// Every `"type"` tag is checked, including those of tasks nested inside
+    /// the frame such as the one wrapped by an `AsyncTask`.
+    pub fn check_task(&self, frame: &[u8]) -> Result<()> {
+        self.scan(frame, self.allowed_tasks.as_ref())?;
+
+        if let Some(allowed) = &self.allowed_tasks {
+            let task_type = task_type(frame)?;
+            if !allowed.contains(&task_type) {
+                return Err(Error::TaskNotAllowed(task_type));
+            }
+        }
+        Ok(())
+    }
+
+    fn scan(&self, frame: &[u8], allowed_tasks: Option<&BTreeSet<String>>) -> Result<()> {
+        let mut scanner = Scanner {
+            limits: self,
+            allowed_tasks,
+            bytes: frame,
+            position: 0,
+        };
+        scanner.item(0)?;
+
+        if scanner.position != frame.len() {
+            return Err(Error::LimitExceeded(
+                "unexpected bytes after the value".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks every frame on `stack`.
+    pub(crate) fn check_stack(&self, stack: &SchedulerStack) -> Result<()> {
+        for frame in stack.front_frames()? {
+            self.check_data(&frame)?;
+        }
+        for frame in stack.back_frames()? {
+            self.check_task(&frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl Scheduler {
+    /// Enforces `limits` on every task and data frame popped from now on.
+    pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+        self.limits = Some(limits);
+    }
+
+    /// Returns the decoding limits enforced by this scheduler, if any.
+    pub fn decode_limits(&self) -> Option<&DecodeLimits> {
+        self.limits.as_ref()
+    }
+
+    /// Creates a scheduler from an untrusted snapshot.
+    ///
+    /// Every frame is checked against `limits` before any task is
+    /// deserialized, and the limits stay enforced on the restored scheduler.
+    pub fn restore_with_limits(snapshot: &[u8], limits: DecodeLimits) -> Result<Self> {
+        Self::restore_limited(snapshot, Some(&limits))
+    }
+
+    /// Restores a snapshot like [`Scheduler::restore`], or like
+    /// [`Scheduler::restore_with_limits`] if `limits` are given.
+    pub(crate) fn restore_limited(snapshot: &[u8], limits: Option<&DecodeLimits>) -> Result<Self> {
+        let (mut stack, recorded) = read_snapshot(snapshot)?;
+
+        if let Some(limits) = limits {
+            limits.check_stack(&stack)?;
+        }
+        schema::upgrade(&mut stack, &recorded)?;
+
+        let mut scheduler = Self {
+            stack,
+            ..Self::default()
+        };
+        scheduler.enforce_limits(limits)?;
+        Ok(scheduler)
+    }
+
+    /// Checks every frame already on the stack against `limits`, if given,
+    /// and enforces them from now on.
+    pub(crate) fn enforce_limits(&mut self, limits: Option<&DecodeLimits>) -> Result<()> {
+        if let Some(limits) = limits {
+            limits.check_stack(&self.stack)?;
+            self.limits = Some(limits.clone());
+        }
+        Ok(())
+    }
+}
+
+/// Walks a CBOR item without allocating, enforcing the limits on the way.
+struct Scanner<'a> {
+    limits: &'a DecodeLimits,
+    /// Task types allowed as the value of a `"type"` key, if restricted
+    allowed_tasks: Option<&'a BTreeSet<String>>,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+/// Additional information value of indefinite-length items.
+const INDEFINITE: u8 = 31;
+
+/// The "break" stop code ending indefinite-length items.
+const BREAK: u8 = 0xff;
+
+impl Scanner<'_> {
+    /// Scans one item nested inside `depth` arrays, maps or tags.
+    fn item(&mut self, depth: usize) -> Result<()> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+
+        if (4..=6).contains(&major) && depth >= self.limits.max_depth {
+            return Err(Error::LimitExceeded(format!(
+                "nesting deeper than {}",
+                self.limits.max_depth
+            )));
+        }
+
+        match major {
+            // Unsigned and negative integers
+            0 | 1 => {
+                self.argument(info)?;
+            }
+            // Byte and text strings
+            2 | 3 if info == INDEFINITE => {
+                let mut length = 0_usize;
+                while !self.at_break()? {
+                    let chunk = self.take(1)?[0];
+                    if chunk >> 5 != major || chunk & 0x1f == INDEFINITE {
+                        return Err(invalid());
+                    }
+                    let chunk_length = self.argument(chunk & 0x1f)?;
+                    length = length.saturating_add(chunk_length);
+                    self.string(length, chunk_length)?;
+                }
+            }
+            2 | 3 => {
+                let length = self.argument(info)?;
+                self.string(length, length)?;
+            }
+            // Arrays and maps
+            4 | 5 => {
+                let entry = |scanner: &mut Self| match major {
+                    4 => scanner.item(depth + 1),
+                    _ => scanner.entry(depth + 1),
+                };
+                if info == INDEFINITE {
+                    let mut length = 0;
+                    while !self.at_break()? {
+                        length += 1;
+                        self.collection(length)?;
+                        entry(self)?;
+                    }
+                } else {
+                    let length = self.argument(info)?;
+                    self.collection(length)?;
+                    for _ in 0..length {
+                        entry(self)?;
+                    }
+                }
+            }
+            // Tags
+            6 => {
+                self.argument(info)?;
+                self.item(depth + 1)?;
+            }
+            // Simple values and floats
+            _ => {
+                if info == INDEFINITE {
+                    return Err(invalid());
+                }
+                self.argument(info)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scans one key and value of a map, checking the value of a `"type"`
+    /// key against the allowed task types.
+    fn entry(&mut self, depth: usize) -> Result<()> {
+        let key = self.position;
+        self.item(depth)?;
+        let is_type = self.bytes[key..self.position] == TYPE_KEY;
+
+        let value = self.position;
+        self.item(depth)?;
+
+        if let Some(allowed) = self.allowed_tasks.filter(|_| is_type) {
+            if let Some(name) = text(&self.bytes[value..self.position]) {
+                if !allowed.contains(name) {
+                    return Err(Error::TaskNotAllowed(name.to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the argument following an initial byte.
+    fn argument(&mut self, info: u8) -> Result<usize> {
+        let size = match info {
+            0..=23 => return Ok(usize::from(info)),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(invalid()),
+        };
+
+        let value = self
+            .take(size)?
+            .iter()
+            .fold(0_u64, |value, byte| (value << 8) | u64::from(*byte));
+        Ok(usize::try_from(value).unwrap_or(usize::MAX))
+    }
+
+    /// Skips the next `chunk` string bytes after checking the string's
+    /// `total` length so far against the limit.
+    fn string(&mut self, total: usize, chunk: usize) -> Result<()> {
+        if total > self.limits.max_string_len {
+            return Err(Error::LimitExceeded(format!(
+                "string longer than {} bytes",
+                self.limits.max_string_len
+            )));
+        }
+        self.take(chunk)?;
+        Ok(())
+    }
+
+    fn collection(&self, length: usize) -> Result<()> {
+        if length > self.limits.max_collection_len {
+            return Err(Error::LimitExceeded(format!(
+                "collection longer than {} elements",
+                self.limits.max_collection_len
+            )));
+        }
+        Ok(())
+    }
+
+    /// Consumes a break stop code if one is next.
+    fn at_break(&mut self) -> Result<bool> {
+        let next = *self.bytes.get(self.position).ok_or_else(invalid)?;
+        if next == BREAK {
+            self.position += 1;
+        }
+        Ok(next == BREAK)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&[u8]> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(invalid)?;
+
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+}
+
+/// The text string `"type"` as encoded in CBOR.
+const TYPE_KEY: [u8; 5] = [0x64, b't', b'y', b'p', b'e'];
+
+/// Returns the contents of a scanned item if it's a definite-length text string.
+pub(crate) fn text(item: &[u8]) -> Option<&str> {
+    let (initial, rest) = item.split_first()?;
+    let header = match initial & 0x1f {
+        0..=23 => 0,
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return None,
+    };
+    if initial >> 5 != 3 {
+        return None;
+    }
+    std::str::from_utf8(rest.get(header..)?).ok()
+}
+
+fn invalid() -> Error {
+    Error::LimitExceeded("malformed or truncated CBOR".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    fn frame(value: &impl Serialize) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        ciborium::ser::into_writer(value, &mut buffer).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn test_check_data() {
+        let limits = DecodeLimits::new()
+            .max_depth(3)
+            .max_collection_len(4)
+            .max_string_len(5);
+
+        assert!(
+            limits
+                .check_data(&frame(&(1, "short", vec![[1.5]])))
+                .is_ok()
+        );
+        assert!(
+            limits
+                .check_data(&frame(&vec![vec![vec![vec![1]]]]))
+                .is_err()
+        );
+        assert!(limits.check_data(&frame(&[0_u8; 5].to_vec())).is_err());
+        assert!(limits.check_data(&frame(&"too long")).is_err());
+        assert!(limits.check_data(&frame(&serde_bytes(6))).is_err());
+
+        // A map declaring 2^32 entries in a few bytes
+        assert!(limits.check_data(&[0xba, 0xff, 0xff, 0xff, 0xff]).is_err());
+        // An indefinite-length array that never ends
+        assert!(limits.check_data(&[0x9f, 0x01, 0x02]).is_err());
+        assert!(limits.check_data(&[0x9f, 0x01, 0x02, 0xff]).is_ok());
+    }
+
+    #[test]
+    fn test_check_chunked_strings() {
+        let limits = DecodeLimits::new().max_string_len(5);
+
+        // "ab" + "cde" as an indefinite-length text string
+        let chunked = [0x7f, 0x62, b'a', b'b', 0x63, b'c', b'd', b'e', 0xff];
+        assert!(limits.check_data(&chunked).is_ok());
+
+        // "ab" + "cd" + "ef" is one byte over the limit
+        let too_long = [
+            0x7f, 0x62, b'a', b'b', 0x62, b'c', b'd', 0x62, b'e', b'f', 0xff,
+        ];
+        assert!(limits.check_data(&too_long).is_err());
+
+        // Chunks must be definite strings of the same major type
+        assert!(limits.check_data(&[0x7f, 0x41, 0x00, 0xff]).is_err());
+    }
+
+    fn serde_bytes(length: usize) -> ciborium::Value {
+        ciborium::Value::Bytes(vec![0; length])
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Allowed {}
+
+    #[typetag::serde]
+    impl SchedulerTask for Allowed {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            let values: Vec<u32> = scheduler.pop_data()?;
+            scheduler.push_data(&values.len())?;
+            Ok(vec![])
+        }
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Forbidden {}
+
+    #[typetag::serde]
+    impl SchedulerTask for Forbidden {
+        fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn test_pop_enforces_limits() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_decode_limits(
+            DecodeLimits::new()
+                .max_collection_len(3)
+                .allow_task("Allowed"),
+        );
+
+        scheduler.push_data(&vec![1_u32, 2, 3, 4]).unwrap();
+        scheduler.push_task(Box::new(Allowed {})).unwrap();
+        assert!(matches!(
+            scheduler.execute(),
+            Err(Error::Execution(message)) if message.contains("collection longer than 3")
+        ));
+
+        scheduler.push_task(Box::new(Forbidden {})).unwrap();
+        assert!(matches!(
+            scheduler.pop_task(),
+            Err(Error::TaskNotAllowed(name)) if name == "Forbidden"
+        ));
+    }
+
+    #[test]
+    fn test_check_task_checks_nested_types() {
+        let task = |inner: &str| {
+            frame(&ciborium::Value::Map(vec![
+                ("type".into(), "Allowed".into()),
+                (
+                    "task".into(),
+                    ciborium::Value::Map(vec![("type".into(), inner.into())]),
+                ),
+            ]))
+        };
+        let limits = DecodeLimits::new().allow_task("Allowed");
+
+        assert!(limits.check_task(&task("Allowed")).is_ok());
+        assert!(matches!(
+            limits.check_task(&task("Forbidden")),
+            Err(Error::TaskNotAllowed(name)) if name == "Forbidden"
+        ));
+        // Data frames aren't tasks, so their "type" keys are left alone
+        assert!(limits.check_data(&task("Forbidden")).is_ok());
+    }
+
+    #[test]
+    fn test_restore_with_limits() {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_task(Box::new(Forbidden {})).unwrap();
+        let snapshot = scheduler.snapshot();
+
+        let limits = DecodeLimits::new().allow_task("Allowed");
+        assert!(matches!(
+            Scheduler::restore_with_limits(&snapshot, limits.clone()),
+            Err(Error::TaskNotAllowed(_))
+        ));
+
+        let restored = Scheduler::restore_with_limits(&Scheduler::new().snapshot(), limits.clone());
+        assert_eq!(restored.unwrap().decode_limits(), Some(&limits));
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::observer::{Event, Observer};
+use crate::snapshot::write_atomic;
+use crate::{Result, Scheduler, SchedulerStack, task_type};
+
+/// Execution counters of a single task type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskMetrics {
+    pub executions: u64,
+    /// Executions that returned an error.
+    pub failures: u64,
+    /// Time spent executing tasks of this type, including (de)serialization.
+    pub total_time: Duration,
+    /// Tasks returned by executions of this type.
+    pub spawned: u64,
+    /// Times a task of this type pushed itself back via `push_self`.
+    pub reschedules: u64,
+    /// Bytes of the task and data frames pushed while executing this type.
+    pub bytes_serialized: u64,
+    pub data_pops: u64,
+    pub data_pushes: u64,
+}
+
+/// Names and help texts of the per task type Prometheus counters, in the
+/// order of [`TaskMetrics::counters`].
+const COUNTERS: [(&str, &str); 8] = [
+    ("executions_total", "Task executions"),
+    ("failures_total", "Task executions that failed"),
+    ("execution_seconds_total", "Time spent executing tasks"),
+    ("spawned_total", "Tasks spawned by executions"),
+    ("reschedules_total", "Tasks rescheduled via push_self"),
+    (
+        "serialized_bytes_total",
+        "Bytes of frames pushed by executions",
+    ),
+    ("data_pops_total", "Data frames popped by executions"),
+    ("data_pushes_total", "Data frames pushed by executions"),
+];
+
+impl TaskMetrics {
+    /// Returns the mean time per execution.
+    pub fn mean_time(&self) -> Duration {
+        match u32::try_from(self.executions) {
+            Ok(0) => Duration::ZERO,
+            Ok(executions) => self.total_time / executions,
+            Err(_) => {
+                Duration::from_secs_f64(self.total_time.as_secs_f64() / self.executions as f64)
+            }
+        }
+    }
+
+    /// Returns the values of the [`COUNTERS`].
+    fn counters(&self) -> [String; 8] {
+        [
+            self.executions.to_string(),
+            self.failures.to_string(),
+            self.total_time.as_secs_f64().to_string(),
+            self.spawned.to_string(),
+            self.reschedules.to_string(),
+            self.bytes_serialized.to_string(),
+            self.data_pops.to_string(),
+            self.data_pushes.to_string(),
+        ]
+    }
+}
+
+/// Observer collecting per task type execution metrics and peak stack usage.
+///
+/// The report is printed with [`Display`](fmt::Display) and can be exported
+/// in the Prometheus text format with [`Metrics::to_prometheus`].
+#[derive(Debug)]
+pub struct Metrics {
+    tasks: BTreeMap<String, TaskMetrics>,
+    capacity: usize,
+    front_high_water: usize,
+    back_low_water: usize,
+    peak_usage: usize,
+    /// Type of the task executing in the current step.
+    current: Option<String>,
+    step_start: Option<Instant>,
+    rescheduling: bool,
+}
+
+impl Metrics {
+    /// Creates a collector starting from the current state of `scheduler`'s stack.
+    pub fn new(scheduler: &Scheduler) -> Self {
+        let mut metrics = Self {
+            tasks: BTreeMap::new(),
+            capacity: scheduler.stack().as_bytes().len(),
+            front_high_water: 0,
+            back_low_water: usize::MAX,
+            peak_usage: 0,
+            current: None,
+            step_start: None,
+            rescheduling: false,
+        };
+        metrics.record_indices(scheduler.stack());
+        metrics
+    }
+
+    /// Returns the metrics of every task type executed so far, by type name.
+    pub fn tasks(&self) -> &BTreeMap<String, TaskMetrics> {
+        &self.tasks
+    }
+
+    /// Returns the metrics of a task type, if it was executed.
+    pub fn task(&self, task_type: &str) -> Option<&TaskMetrics> {
+        self.tasks.get(task_type)
+    }
+
+    /// Returns the highest front index seen, i.e. the peak size of the data stack.
+    pub fn front_high_water(&self) -> usize {
+        self.front_high_water
+    }
+
+    /// Returns the lowest back index seen; the task stack grows downwards, so
+    /// this is where it reached furthest.
+    pub fn back_low_water(&self) -> usize {
+        self.back_low_water
+    }
+
+    /// Returns the most bytes used by data and tasks together at any time.
+    pub fn peak_usage(&self) -> usize {
+        self.peak_usage
+    }
+
+    /// Exports the metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+
+        for (i, (name, help)) in COUNTERS.iter().enumerate() {
+            let _ = writeln!(
+                text,
+                "# HELP scheduler_task_{} {} by task type.",
+                name, help
+            );
+            let _ = writeln!(text, "# TYPE scheduler_task_{} counter", name);
+            for (task_type, metrics) in &self.tasks {
+                let _ = writeln!(
+                    text,
+                    "scheduler_task_{}{{task_type=\"{}\"}} {}",
+                    name,
+                    escape_label(task_type),
+                    metrics.counters()[i]
+                );
+            }
+        }
+
+        let gauges = [
+            (
+                "stack_capacity_bytes",
+                "Size of the stack buffer.",
+                self.capacity,
+            ),
+            (
+                "stack_front_index_high_water",
+                "Highest front index seen.",
+                self.front_high_water,
+            ),
+            (
+                "stack_back_index_low_water",
+                "Lowest back index seen.",
+                self.back_low_water,
+            ),
+            (
+                "stack_peak_usage_bytes",
+                "Most bytes used by data and tasks together.",
+                self.peak_usage,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(text, "# HELP scheduler_{} {}", name, help);
+            let _ = writeln!(text, "# TYPE scheduler_{} gauge", name);
+            let _ = writeln!(text, "scheduler_{} {}", name, value);
+        }
+        text
+    }
+
+    /// Writes the metrics in the Prometheus text exposition format to `path`.
+    pub fn save_prometheus(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), self.to_prometheus().as_bytes())
+    }
+
+    fn record_indices(&mut self, stack: &SchedulerStack) {
+        self.front_high_water = self.front_high_water.max(stack.front_index());
+        self.back_low_water = self.back_low_water.min(stack.back_index());
+        let usage = stack.front_index() + (self.capacity - stack.back_index());
+        self.peak_usage = self.peak_usage.max(usage);
+    }
+
+    fn current(&mut self) -> Option<&mut TaskMetrics> {
+        let current = self.current.as_ref()?;
+        self.tasks.get_mut(current)
+    }
+}
+
+impl Observer for Metrics {
+    fn on_event(&mut self, event: &Event<'_>, stack: &SchedulerStack) -> Result<()> {
+        self.record_indices(stack);
+
+        match *event {
+            Event::StepStarted => self.step_start = Some(Instant::now()),
+            Event::TaskPopped(frame) if self.step_start.is_some() && self.current.is_none() => {
+                let name = task_type(frame).unwrap_or_else(|_| "<unknown>".to_string());
+                self.tasks.entry(name.clone()).or_default().executions += 1;
+                self.current = Some(name);
+            }
+            Event::Rescheduled => self.rescheduling = true,
+            Event::TaskPushed(frame) => {
+                let rescheduled = std::mem::take(&mut self.rescheduling);
+                if let Some(metrics) = self.current() {
+                    metrics.bytes_serialized += frame.len() as u64;
+                    if rescheduled {
+                        metrics.reschedules += 1;
+                    } else {
+                        metrics.spawned += 1;
+                    }
+                }
+            }
+            Event::DataPushed(frame) => {
+                if let Some(metrics) = self.current() {
+                    metrics.bytes_serialized += frame.len() as u64;
+                    metrics.data_pushes += 1;
+                }
+            }
+            Event::DataPopped(_) => {
+                if let Some(metrics) = self.current() {
+                    metrics.data_pops += 1;
+                }
+            }
+            Event::StepFinished | Event::StepFailed => {
+                let elapsed = self.step_start.take().map(|start| start.elapsed());
+                if let Some(metrics) = self.current() {
+                    metrics.total_time += elapsed.unwrap_or_default();
+                    if *event == Event::StepFailed {
+                        metrics.failures += 1;
+                    }
+                }
+                self.current = None;
+                self.rescheduling = false;
+            }
+            Event::TaskPopped(_) | Event::TaskSelected(_) | Event::Cleared => {}
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<20} {:>10} {:>8} {:>12} {:>10} {:>8} {:>8} {:>10} {:>8} {:>8}",
+            "task type",
+            "executions",
+            "failures",
+            "total",
+            "mean",
+            "spawned",
+            "resched",
+            "bytes",
+            "pops",
+            "pushes"
+        )?;
+        for (task_type, metrics) in &self.tasks {
+            writeln!(
+                f,
+                "{:<20} {:>10} {:>8} {:>12} {:>10} {:>8} {:>8} {:>10} {:>8} {:>8}",
+                task_type,
+                metrics.executions,
+                metrics.failures,
+                format!("{:.3?}", metrics.total_time),
+                format!("{:.1?}", metrics.mean_time()),
+                metrics.spawned,
+                metrics.reschedules,
+                metrics.bytes_serialized,
+                metrics.data_pops,
+                metrics.data_pushes
+            )?;
+        }
+        writeln!(
+            f,
+            "stack: front index high-water {}, back index low-water {}, peak usage {} of {} bytes",
+            self.front_high_water, self.back_low_water, self.peak_usage, self.capacity
+        )
+    }
+}
+
+/// Escapes a Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    /// Pushes `n`, then reschedules itself with `n - 1` until `n` is zero and
+    /// finally spawns a [`Drain`].
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Emit {
+        n: u32,
+        #[serde(skip)]
+        again: bool,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Emit {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            scheduler.push_data(&self.n)?;
+            self.again = self.n > 0;
+            if self.again {
+                self.n -= 1;
+                return Ok(vec![]);
+            }
+            Ok(vec![Box::new(Drain)])
+        }
+
+        fn push_self(&mut self) -> bool {
+            self.again
+        }
+    }
+
+    /// Pops every data frame, failing once the data stack is empty.
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Drain;
+
+    #[typetag::serde]
+    impl SchedulerTask for Drain {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            loop {
+                scheduler.pop_data::<u32>()?;
+            }
+        }
+    }
+
+    fn metrics() -> Metrics {
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Emit { n: 2, again: false }))
+            .unwrap();
+        scheduler.add_observer(Metrics::new(&scheduler));
+        assert!(scheduler.execute_all().is_err());
+        scheduler.remove_observer().unwrap()
+    }
+
+    #[test]
+    fn test_counts_per_task_type() {
+        let metrics = metrics();
+
+        let emit = metrics.task("Emit").unwrap();
+        assert_eq!(emit.executions, 3);
+        assert_eq!(emit.reschedules, 2);
+        assert_eq!(emit.spawned, 1);
+        assert_eq!(emit.data_pushes, 3);
+        assert_eq!(emit.data_pops, 0);
+        assert_eq!(emit.failures, 0);
+        assert!(emit.bytes_serialized > 3);
+
+        let drain = metrics.task("Drain").unwrap();
+        assert_eq!(drain.executions, 1);
+        assert_eq!(drain.failures, 1);
+        assert_eq!(drain.data_pops, 3);
+        assert_eq!(drain.bytes_serialized, 0);
+    }
+
+    #[test]
+    fn test_high_water_marks() {
+        let metrics = metrics();
+
+        // Three single byte data frames with their length headers
+        assert_eq!(metrics.front_high_water(), 9);
+        assert!(metrics.back_low_water() < crate::SCHEDULER_CAPACITY);
+        assert!(metrics.peak_usage() > metrics.front_high_water());
+        assert!(metrics.to_string().contains("front index high-water 9"));
+    }
+
+    #[test]
+    fn test_prometheus_format() {
+        let text = metrics().to_prometheus();
+
+        assert!(text.contains("# TYPE scheduler_task_executions_total counter\n"));
+        assert!(text.contains("scheduler_task_executions_total{task_type=\"Emit\"} 3\n"));
+        assert!(text.contains("scheduler_task_reschedules_total{task_type=\"Emit\"} 2\n"));
+        assert!(text.contains("scheduler_stack_front_index_high_water 9\n"));
+        assert!(text.contains("# TYPE scheduler_stack_back_index_low_water gauge\n"));
+        assert_eq!(escape_label("a\"b\\"), "a\\\"b\\\\");
+    }
+}
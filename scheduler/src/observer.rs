@@ -0,0 +1,94 @@
+use std::any::Any;
+use std::fmt::Debug;
+
+use crate::{Result, SchedulerStack};
+
+/// A change to the scheduler's state, reported to every attached [`Observer`].
+///
+/// Frames are the serialized bytes exactly as stored on the stack. Pushes and
+/// pops made while a task executes are reported between [`Event::StepStarted`]
+/// and the matching [`Event::StepFinished`] or [`Event::StepFailed`]; pushes and
+/// pops made directly on the scheduler are reported on their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// A call to [`Scheduler::execute`](crate::Scheduler::execute) started.
+    StepStarted,
+    /// A task frame was popped from the task stack.
+    TaskPopped(&'a [u8]),
+    /// A task frame was pushed onto the task stack.
+    TaskPushed(&'a [u8]),
+    /// The pending task this many frames below the top of the task stack was
+    /// moved to the top to run next, see [`Selection`](crate::Selection).
+    TaskSelected(usize),
+    /// The executing task asked to run again; the next [`Event::TaskPushed`]
+    /// pushes the task itself.
+    Rescheduled,
+    /// A data frame was popped from the data stack.
+    DataPopped(&'a [u8]),
+    /// A data frame was pushed onto the data stack.
+    DataPushed(&'a [u8]),
+    /// The step completed successfully.
+    StepFinished,
+    /// The step returned an error; its effects so far remain applied.
+    StepFailed,
+    /// All tasks and data were removed from the stack.
+    Cleared,
+}
+
+/// Hook notified about every change to a [`Scheduler`](crate::Scheduler)'s stacks.
+///
+/// Observers are attached with
+/// [`Scheduler::add_observer`](crate::Scheduler::add_observer) and can be
+/// retrieved by type afterwards to read what they collected. The stack is
+/// passed in the state it has right after the event.
+pub trait Observer: Any + Debug + Send {
+    /// Called for every event, in the order the events happen.
+    ///
+    /// Returning an error fails the operation that triggered the event. Errors
+    /// returned for [`Event::StepFailed`] are ignored, so the step fails with
+    /// the error of its task.
+    fn on_event(&mut self, event: &Event<'_>, stack: &SchedulerStack) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Error, Scheduler, SchedulerTask};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Fail;
+
+    #[typetag::serde]
+    impl SchedulerTask for Fail {
+        fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            Err(Error::Task("broken".to_string()))
+        }
+    }
+
+    /// Fails on every failed step.
+    #[derive(Debug)]
+    struct Strict;
+
+    impl Observer for Strict {
+        fn on_event(&mut self, event: &Event<'_>, _stack: &SchedulerStack) -> Result<()> {
+            match event {
+                Event::StepFailed => Err(Error::InvalidData("step failed".to_string())),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_step_keeps_task_error() {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_task(Box::new(Fail)).unwrap();
+        scheduler.add_observer(Strict);
+
+        assert!(matches!(
+            scheduler.execute(),
+            Err(Error::Execution(message)) if message.contains("broken")
+        ));
+    }
+}
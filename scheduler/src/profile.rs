@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::observer::{Event, Observer};
+use crate::snapshot::write_atomic;
+use crate::{Result, Scheduler, SchedulerStack, task_type};
+
+/// What the counts of a folded stack measure.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Weight {
+    /// Number of steps executed with the stack.
+    #[default]
+    Steps,
+    /// Nanoseconds spent in steps executed with the stack.
+    Nanos,
+}
+
+/// Samples of a single ancestry chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Samples {
+    pub steps: u64,
+    pub time: Duration,
+}
+
+/// Observer sampling the spawn ancestry of every executed task, for flame graphs.
+///
+/// Every step adds a sample to the chain of task types that led to the
+/// executing task, root first. [`Profiler::to_folded`] writes the samples in
+/// the folded-stack format read by `inferno-flamegraph` and `flamegraph.pl`.
+///
+/// Only the chains of pending tasks are kept besides the samples, so memory
+/// doesn't grow with the number of steps.
+#[derive(Debug)]
+pub struct Profiler {
+    /// Chain of every frame on the task stack, next task last.
+    pending: Vec<Chain>,
+    /// Chain of the task executing in the current step.
+    current: Option<Chain>,
+    in_step: bool,
+    rescheduling: bool,
+    samples: BTreeMap<String, Samples>,
+    step_start: Option<Instant>,
+}
+
+/// The ancestry of a pending or executing task.
+#[derive(Debug, Clone)]
+struct Chain {
+    /// Chain of the task that spawned this one, shared by its reschedules.
+    spawner: Option<Arc<str>>,
+    /// Task types from the root to this task, joined with `;`.
+    stack: Arc<str>,
+}
+
+impl Chain {
+    fn new(spawner: Option<Arc<str>>, frame: &[u8]) -> Self {
+        let name = task_type(frame).unwrap_or_else(|_| "<unknown>".to_string());
+        let stack = match &spawner {
+            Some(spawner) => format!("{};{}", spawner, name),
+            None => name,
+        };
+        Self {
+            spawner,
+            stack: stack.into(),
+        }
+    }
+}
+
+impl Profiler {
+    /// Creates a profiler whose roots are the tasks already pending on `scheduler`.
+    pub fn new(scheduler: &Scheduler) -> Self {
+        let pending = scheduler
+            .stack()
+            .back_frames()
+            .unwrap_or_default()
+            .iter()
+            .map(|frame| Chain::new(None, frame))
+            .collect();
+        Self {
+            pending,
+            current: None,
+            in_step: false,
+            rescheduling: false,
+            samples: BTreeMap::new(),
+            step_start: None,
+        }
+    }
+
+    /// Returns the samples of every ancestry chain, keyed by the task types
+    /// joined with `;`.
+    pub fn samples(&self) -> &BTreeMap<String, Samples> {
+        &self.samples
+    }
+
+    /// Exports the samples in the folded-stack format, one chain per line.
+    pub fn to_folded(&self, weight: Weight) -> String {
+        let mut folded = String::new();
+        for (stack, samples) in &self.samples {
+            let count = match weight {
+                Weight::Steps => u128::from(samples.steps),
+                Weight::Nanos => samples.time.as_nanos(),
+            };
+            let _ = writeln!(folded, "{} {}", stack, count);
+        }
+        folded
+    }
+
+    /// Writes the samples in the folded-stack format to `path`.
+    pub fn save_folded(&self, path: impl AsRef<Path>, weight: Weight) -> Result<()> {
+        write_atomic(path.as_ref(), self.to_folded(weight).as_bytes())
+    }
+}
+
+impl Observer for Profiler {
+    fn on_event(&mut self, event: &Event<'_>, _stack: &SchedulerStack) -> Result<()> {
+        match *event {
+            Event::StepStarted => {
+                self.in_step = true;
+                self.step_start = Some(Instant::now());
+            }
+            Event::TaskPopped(_) => {
+                let chain = self.pending.pop();
+                if self.in_step {
+                    self.current = chain;
+                }
+            }
+            Event::TaskSelected(depth) => {
+                if let Some(index) = self.pending.len().checked_sub(depth + 1) {
+                    let chain = self.pending.remove(index);
+                    self.pending.push(chain);
+                }
+            }
+            Event::Rescheduled => self.rescheduling = true,
+            Event::TaskPushed(frame) => {
+                // Rescheduled executions are the same task as far as ancestry goes
+                let spawner = match self.current.as_ref().filter(|_| self.in_step) {
+                    Some(current) if self.rescheduling => current.spawner.clone(),
+                    Some(current) => Some(current.stack.clone()),
+                    None => None,
+                };
+                self.rescheduling = false;
+                self.pending.push(Chain::new(spawner, frame));
+            }
+            Event::StepFinished | Event::StepFailed => {
+                let elapsed = self.step_start.take().map(|start| start.elapsed());
+                if let Some(current) = self.current.take() {
+                    let samples = self.samples.entry(current.stack.to_string()).or_default();
+                    samples.steps += 1;
+                    samples.time += elapsed.unwrap_or_default();
+                }
+                self.in_step = false;
+                self.rescheduling = false;
+            }
+            Event::Cleared => self.pending.clear(),
+            Event::DataPopped(_) | Event::DataPushed(_) => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    /// Spawns a [`Leaf`] for every level below it and a `Nest` one level down.
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Nest {
+        levels: u32,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Nest {
+        fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            if self.levels == 0 {
+                return Ok(vec![]);
+            }
+            Ok(vec![
+                Box::new(Leaf),
+                Box::new(Nest {
+                    levels: self.levels - 1,
+                }),
+            ])
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Leaf;
+
+    #[typetag::serde]
+    impl SchedulerTask for Leaf {
+        fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            Ok(vec![])
+        }
+    }
+
+    fn profile() -> Profiler {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_task(Box::new(Nest { levels: 2 })).unwrap();
+        scheduler.push_task(Box::new(Leaf)).unwrap();
+        scheduler.add_observer(Profiler::new(&scheduler));
+        scheduler.execute_all().unwrap();
+        scheduler.remove_observer().unwrap()
+    }
+
+    #[test]
+    fn test_folded_steps() {
+        let profiler = profile();
+        assert!(profiler.pending.is_empty());
+        assert_eq!(
+            profiler.to_folded(Weight::Steps),
+            "Leaf 1\n\
+             Nest 1\n\
+             Nest;Leaf 1\n\
+             Nest;Nest 1\n\
+             Nest;Nest;Leaf 1\n\
+             Nest;Nest;Nest 1\n"
+        );
+    }
+
+    #[test]
+    fn test_folded_nanos() {
+        let profiler = profile();
+        let folded = profiler.to_folded(Weight::Nanos);
+
+        for (line, samples) in folded.lines().zip(profiler.samples().values()) {
+            let (_, nanos) = line.rsplit_once(' ').unwrap();
+            assert_eq!(nanos.parse::<u128>().unwrap(), samples.time.as_nanos());
+        }
+        assert_eq!(folded.lines().count(), 6);
+    }
+}
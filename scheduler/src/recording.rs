@@ -0,0 +1,363 @@
+use std::fmt;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::observer::{Event, Observer};
+use crate::selection::{Selection, raise};
+use crate::snapshot::write_atomic;
+use crate::{Error, Result, Scheduler, SchedulerStack};
+
+/// A single change to the stack, with the frame bytes involved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operation {
+    TaskPopped(Vec<u8>),
+    TaskPushed(Vec<u8>),
+    TaskSelected(usize),
+    DataPopped(Vec<u8>),
+    DataPushed(Vec<u8>),
+    Cleared,
+}
+
+impl Operation {
+    fn from_event(event: &Event<'_>) -> Option<Self> {
+        Some(match *event {
+            Event::TaskPopped(frame) => Operation::TaskPopped(frame.to_vec()),
+            Event::TaskPushed(frame) => Operation::TaskPushed(frame.to_vec()),
+            Event::TaskSelected(depth) => Operation::TaskSelected(depth),
+            Event::DataPopped(frame) => Operation::DataPopped(frame.to_vec()),
+            Event::DataPushed(frame) => Operation::DataPushed(frame.to_vec()),
+            Event::Cleared => Operation::Cleared,
+            Event::StepStarted | Event::Rescheduled | Event::StepFinished | Event::StepFailed => {
+                return None;
+            }
+        })
+    }
+
+    /// Applies the operation to a stack without executing anything.
+    fn apply(&self, stack: &mut SchedulerStack) -> Result<()> {
+        let (popped, expected) = match self {
+            Operation::TaskPushed(frame) => return Ok(stack.push_back(frame)?),
+            Operation::DataPushed(frame) => return Ok(stack.push_front(frame)?),
+            Operation::TaskSelected(depth) => return raise(stack, *depth, |_| {}),
+            Operation::Cleared => {
+                stack.clear();
+                return Ok(());
+            }
+            Operation::TaskPopped(frame) => (stack.pop_back()?, frame),
+            Operation::DataPopped(frame) => (stack.pop_front()?, frame),
+        };
+
+        if popped != *expected {
+            return Err(Error::InvalidData(
+                "popped frame doesn't match the recording".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Everything a single call to [`Scheduler::execute`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepRecord {
+    /// Operations in the order they happened
+    pub operations: Vec<Operation>,
+    /// Whether the step returned an error
+    pub failed: bool,
+}
+
+/// An entry of a [`Recording`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Entry {
+    /// A step executed by the scheduler.
+    Step(StepRecord),
+    /// A change made directly on the scheduler, outside of any step.
+    External(Operation),
+}
+
+/// Initial state of a scheduler plus everything that happened to it afterwards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    /// Snapshot of the scheduler when recording started
+    pub initial: Vec<u8>,
+    /// How the scheduler picked the next task to execute
+    #[serde(default)]
+    pub selection: Selection,
+    /// Steps and external changes in the order they happened
+    pub entries: Vec<Entry>,
+}
+
+impl Recording {
+    /// Returns the recorded steps, in execution order.
+    pub fn steps(&self) -> impl Iterator<Item = &StepRecord> {
+        self.entries.iter().filter_map(|entry| match entry {
+            Entry::Step(step) => Some(step),
+            Entry::External(_) => None,
+        })
+    }
+
+    /// Writes the recording to a file as CBOR.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut buffer = Vec::new();
+        ciborium::ser::into_writer(self, &mut buffer).map_err(Error::Serialization)?;
+
+        write_atomic(path.as_ref(), &buffer)
+    }
+
+    /// Reads a recording written by [`Recording::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let buffer = fs::read(path)?;
+
+        let mut cursor = Cursor::new(&buffer);
+        let result = ciborium::de::from_reader(&mut cursor).map_err(Error::Deserialization)?;
+
+        Ok(result)
+    }
+
+    /// Re-executes the recording and verifies every step against it.
+    ///
+    /// Steps are executed by the tasks registered in this binary starting from
+    /// the initial snapshot, and external changes are applied as recorded. The
+    /// replay stops at the first step whose operations differ from the
+    /// recording in any byte.
+    pub fn replay(&self) -> Result<ReplayReport> {
+        let mut scheduler = Scheduler::restore(&self.initial)?;
+        scheduler.set_selection(self.selection);
+        let mut step = 0;
+
+        for entry in &self.entries {
+            let expected = match entry {
+                Entry::External(operation) => {
+                    operation.apply(&mut scheduler.stack)?;
+                    continue;
+                }
+                Entry::Step(expected) => expected,
+            };
+
+            scheduler.add_observer(Recorder::default());
+            let failed = scheduler.execute().is_err();
+            let recorder = scheduler.remove_observer::<Recorder>().unwrap_or_default();
+            let mut actual = recorder
+                .into_recording()
+                .steps()
+                .next()
+                .cloned()
+                .unwrap_or_default();
+            actual.failed = failed;
+
+            if let Some(divergence) = Divergence::between(step, expected, &actual) {
+                return Ok(ReplayReport {
+                    steps: step,
+                    divergence: Some(divergence),
+                });
+            }
+            step += 1;
+        }
+
+        Ok(ReplayReport {
+            steps: step,
+            divergence: None,
+        })
+    }
+}
+
+/// Outcome of [`Recording::replay`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Number of steps that matched the recording
+    pub steps: usize,
+    /// The first step that didn't match, if any
+    pub divergence: Option<Divergence>,
+}
+
+/// The first difference between a recorded step and its replay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the divergent step
+    pub step: usize,
+    /// Index of the first differing operation within the step
+    pub operation: usize,
+    /// The recorded operation, or `None` if the replay did more
+    pub expected: Option<Operation>,
+    /// The replayed operation, or `None` if the replay did less
+    pub actual: Option<Operation>,
+    /// Whether the recorded and the replayed step failed
+    pub failed: (bool, bool),
+}
+
+impl Divergence {
+    fn between(step: usize, expected: &StepRecord, actual: &StepRecord) -> Option<Self> {
+        let length = expected.operations.len().max(actual.operations.len());
+        let operation = (0..length)
+            .find(|&i| expected.operations.get(i) != actual.operations.get(i))
+            .or((expected.failed != actual.failed).then_some(length))?;
+
+        Some(Self {
+            step,
+            operation,
+            expected: expected.operations.get(operation).cloned(),
+            actual: actual.operations.get(operation).cloned(),
+            failed: (expected.failed, actual.failed),
+        })
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "step {} diverged at operation {}: expected {:?}, got {:?}",
+            self.step, self.operation, self.expected, self.actual
+        )?;
+        if self.failed.0 != self.failed.1 {
+            write!(
+                f,
+                " (recorded step failed: {}, replayed step failed: {})",
+                self.failed.0, self.failed.1
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Observer that records everything a scheduler does.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    initial: Vec<u8>,
+    selection: Selection,
+    entries: Vec<Entry>,
+    /// The step in progress
+    current: Option<StepRecord>,
+}
+
+impl Recorder {
+    /// Starts recording from the current state of `scheduler`.
+    ///
+    /// The recorder must be attached with [`Scheduler::add_observer`].
+    pub fn new(scheduler: &Scheduler) -> Self {
+        Self {
+            initial: scheduler.snapshot(),
+            selection: scheduler.selection(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the recording of everything observed so far.
+    ///
+    /// A step in progress is not included.
+    pub fn recording(&self) -> Recording {
+        Recording {
+            initial: self.initial.clone(),
+            selection: self.selection,
+            entries: self.entries.clone(),
+        }
+    }
+
+    /// Consumes the recorder and returns its recording.
+    pub fn into_recording(self) -> Recording {
+        Recording {
+            initial: self.initial,
+            selection: self.selection,
+            entries: self.entries,
+        }
+    }
+}
+
+impl Observer for Recorder {
+    fn on_event(&mut self, event: &Event<'_>, _stack: &SchedulerStack) -> Result<()> {
+        match event {
+            Event::StepStarted => self.current = Some(StepRecord::default()),
+            Event::StepFinished | Event::StepFailed => {
+                if let Some(mut step) = self.current.take() {
+                    step.failed = *event == Event::StepFailed;
+                    self.entries.push(Entry::Step(step));
+                }
+            }
+            _ => {
+                let operation = Operation::from_event(event);
+                match (&mut self.current, operation) {
+                    (Some(step), Some(operation)) => step.operations.push(operation),
+                    (None, Some(operation)) => self.entries.push(Entry::External(operation)),
+                    (_, None) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SchedulerTask;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Double {}
+
+    #[typetag::serde]
+    impl SchedulerTask for Double {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            let value: u64 = scheduler.pop_data()?;
+            scheduler.push_data(&(value * 2))?;
+            Ok(vec![])
+        }
+    }
+
+    fn record() -> Recording {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_data(&3_u64).unwrap();
+
+        scheduler.add_observer(Recorder::new(&scheduler));
+        scheduler.push_task(Box::new(Double {})).unwrap();
+        scheduler.push_task(Box::new(Double {})).unwrap();
+        scheduler.execute_all().unwrap();
+        assert_eq!(scheduler.pop_data::<u64>().unwrap(), 12);
+
+        scheduler
+            .remove_observer::<Recorder>()
+            .unwrap()
+            .into_recording()
+    }
+
+    #[test]
+    fn test_replay_matches_recording() {
+        let recording = record();
+
+        assert_eq!(recording.steps().count(), 2);
+        assert_eq!(recording.entries.len(), 5);
+
+        let report = recording.replay().unwrap();
+        assert_eq!(report.steps, 2);
+        assert_eq!(report.divergence, None);
+    }
+
+    #[test]
+    fn test_replay_reports_first_divergence() {
+        let mut recording = record();
+
+        // Pretend the second step originally pushed a different value
+        let mut buffer = Vec::new();
+        ciborium::ser::into_writer(&13_u64, &mut buffer).unwrap();
+        let Entry::Step(step) = &mut recording.entries[3] else {
+            panic!("expected a step");
+        };
+        step.operations[2] = Operation::DataPushed(buffer);
+
+        let divergence = recording.replay().unwrap().divergence.unwrap();
+        assert_eq!(divergence.step, 1);
+        assert_eq!(divergence.operation, 2);
+        assert!(matches!(divergence.actual, Some(Operation::DataPushed(_))));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.rec");
+        let recording = record();
+
+        recording.save(&path).unwrap();
+        assert_eq!(Recording::load(&path).unwrap(), recording);
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::Cursor;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use ciborium::Value;
+
+use crate::{Error, Result, SchedulerStack, SchedulerTask, task_type};
+
+/// Schema version of task types without a registered version.
+pub const INITIAL_VERSION: u32 = 1;
+
+/// Upgrades a decoded task frame by one schema version.
+type Migration = Arc<dyn Fn(Value) -> Result<Value> + Send + Sync>;
+
+#[derive(Default)]
+struct TypeSchema {
+    version: u32,
+    /// Migrations keyed by the version they upgrade from
+    migrations: HashMap<u32, Migration>,
+}
+
+/// Schema versions and migrations of every task type, like the task types
+/// themselves registered once for the whole process.
+static REGISTRY: LazyLock<RwLock<HashMap<String, TypeSchema>>> = LazyLock::new(Default::default);
+
+/// Sets the current schema version of a task type.
+///
+/// Snapshots record the schema version of every task type they contain.
+/// When a snapshot holding an older version is restored, its task frames are
+/// upgraded with the migrations registered through [`register_migration`].
+/// Versions should be registered at startup, before any snapshot is taken or
+/// restored.
+pub fn set_schema_version(task_type: &str, version: u32) {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    registry.entry(task_type.to_string()).or_default().version = version;
+}
+
+/// Returns the current schema version of a task type.
+pub fn schema_version(task_type: &str) -> u32 {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry
+        .get(task_type)
+        .map_or(INITIAL_VERSION, |schema| schema.version)
+}
+
+/// Registers a migration upgrading frames of a task type from `from_version`
+/// to `from_version + 1`.
+///
+/// The migration receives the task frame decoded as a CBOR map, including its
+/// `type` entry. [`rename_field`] and [`insert_field`] cover the common cases.
+pub fn register_migration<F>(task_type: &str, from_version: u32, migration: F)
+where
+    F: Fn(Value) -> Result<Value> + Send + Sync + 'static,
+{
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    let schema = registry
+        .entry(task_type.to_string())
+        .or_insert_with(|| TypeSchema {
+            version: INITIAL_VERSION,
+            ..TypeSchema::default()
+        });
+    schema.migrations.insert(from_version, Arc::new(migration));
+}
+
+/// Renames a field of a decoded task frame.
+pub fn rename_field(mut value: Value, from: &str, to: &str) -> Result<Value> {
+    for (key, _) in fields(&mut value)? {
+        if key.as_text() == Some(from) {
+            *key = Value::Text(to.to_string());
+        }
+    }
+    Ok(value)
+}
+
+/// Adds a field to a decoded task frame, replacing any existing value.
+pub fn insert_field(mut value: Value, name: &str, field: Value) -> Result<Value> {
+    let fields = fields(&mut value)?;
+    fields.retain(|(key, _)| key.as_text() != Some(name));
+    fields.push((Value::Text(name.to_string()), field));
+    Ok(value)
+}
+
+fn fields(value: &mut Value) -> Result<&mut Vec<(Value, Value)>> {
+    match value {
+        Value::Map(fields) => Ok(fields),
+        _ => Err(Error::InvalidData("task frame is not a map".to_string())),
+    }
+}
+
+/// Returns the current schema version of every task type on `stack`.
+pub(crate) fn versions(stack: &SchedulerStack) -> Vec<(String, u32)> {
+    let task_types: BTreeSet<String> = stack
+        .back_frames()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|frame| task_type(frame).ok())
+        .collect();
+
+    task_types
+        .into_iter()
+        .map(|task_type| {
+            let version = schema_version(&task_type);
+            (task_type, version)
+        })
+        .collect()
+}
+
+/// Upgrades the task frames on `stack` from the recorded schema versions to
+/// the current ones, and checks that every task can be deserialized.
+///
+/// Task types missing from `recorded` are taken to be at [`INITIAL_VERSION`].
+/// Fails with [`Error::IncompatibleTasks`] listing every task type that is
+/// unknown or can't be migrated.
+pub(crate) fn upgrade(stack: &mut SchedulerStack, recorded: &[(String, u32)]) -> Result<()> {
+    let recorded: HashMap<&str, u32> = recorded
+        .iter()
+        .map(|(task_type, version)| (task_type.as_str(), *version))
+        .collect();
+
+    let mut frames = stack.back_frames()?;
+    let mut changed = false;
+    let mut incompatible = BTreeSet::new();
+
+    for frame in &mut frames {
+        let Ok(task_type) = task_type(frame) else {
+            incompatible.insert("<untyped task>".to_string());
+            continue;
+        };
+        let from = recorded
+            .get(task_type.as_str())
+            .copied()
+            .unwrap_or(INITIAL_VERSION);
+
+        match migrate(frame, &task_type, from) {
+            Ok(Some(migrated)) => {
+                *frame = migrated;
+                changed = true;
+            }
+            Ok(None) => {}
+            Err(reason) => {
+                incompatible.insert(format!("{} ({})", task_type, reason));
+                continue;
+            }
+        }
+
+        let mut cursor = Cursor::new(&*frame);
+        if ciborium::de::from_reader::<Box<dyn SchedulerTask>, _>(&mut cursor).is_err() {
+            incompatible.insert(format!("{} (unknown or invalid task)", task_type));
+        }
+    }
+
+    if !incompatible.is_empty() {
+        return Err(Error::IncompatibleTasks(incompatible.into_iter().collect()));
+    }
+
+    if changed {
+        while !stack.is_empty_back() {
+            stack.pop_back()?;
+        }
+        for frame in &frames {
+            stack.push_back(frame)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Migrates a task frame from version `from` to the current version of its
+/// type, returning `None` if it's already current.
+fn migrate(
+    frame: &[u8],
+    task_type: &str,
+    from: u32,
+) -> std::result::Result<Option<Vec<u8>>, String> {
+    let migrations: Vec<Migration> = {
+        let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+        let schema = registry.get(task_type);
+        let current = schema.map_or(INITIAL_VERSION, |schema| schema.version);
+
+        if from == current {
+            return Ok(None);
+        }
+        if from > current {
+            return Err(format!(
+                "version {} is newer than the supported version {}",
+                from, current
+            ));
+        }
+
+        (from..current)
+            .map(|version| {
+                schema
+                    .and_then(|schema| schema.migrations.get(&version))
+                    .cloned()
+                    .ok_or_else(|| format!("no migration from version {}", version))
+            })
+            .collect::<std::result::Result<_, _>>()?
+    };
+
+    let mut cursor = Cursor::new(frame);
+    let mut value: Value = ciborium::de::from_reader(&mut cursor).map_err(|e| e.to_string())?;
+    for migration in migrations {
+        value = migration(value).map_err(|e| e.to_string())?;
+    }
+
+    let mut migrated = Vec::new();
+    ciborium::ser::into_writer(&value, &mut migrated).map_err(|e| e.to_string())?;
+
+    Ok(Some(migrated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::Scheduler;
+    use crate::snapshot::encode_parts;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Renamed {
+        total: u32,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Renamed {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            scheduler.push_data(&self.total)?;
+            Ok(vec![])
+        }
+    }
+
+    /// Builds a snapshot holding a `Renamed` task in its version 1 layout.
+    fn old_snapshot(field: &str) -> Vec<u8> {
+        let value = Value::Map(vec![
+            (Value::Text("type".into()), Value::Text("Renamed".into())),
+            (Value::Text(field.into()), Value::Integer(7.into())),
+        ]);
+        let mut frame = Vec::new();
+        ciborium::ser::into_writer(&value, &mut frame).unwrap();
+
+        let mut stack = SchedulerStack::default();
+        stack.push_back(&frame).unwrap();
+        let schema = [("Renamed".to_string(), 1)];
+        encode_parts(
+            stack.front_index(),
+            stack.back_index(),
+            stack.as_bytes(),
+            &schema,
+        )
+    }
+
+    #[test]
+    fn test_migrates_old_frames() {
+        set_schema_version("Renamed", 2);
+        register_migration("Renamed", 1, |value| rename_field(value, "sum", "total"));
+
+        let mut scheduler = Scheduler::restore(&old_snapshot("sum")).unwrap();
+        scheduler.execute_all().unwrap();
+        assert_eq!(scheduler.pop_data::<u32>().unwrap(), 7);
+
+        // New snapshots record the current version
+        let snapshot = scheduler.snapshot();
+        assert!(Scheduler::restore(&snapshot).is_ok());
+    }
+
+    #[test]
+    fn test_lists_incompatible_tasks() {
+        let mut stack = SchedulerStack::default();
+        for value in ["Unknown", "Missing"] {
+            let value = Value::Map(vec![(
+                Value::Text("type".into()),
+                Value::Text(value.into()),
+            )]);
+            let mut frame = Vec::new();
+            ciborium::ser::into_writer(&value, &mut frame).unwrap();
+            stack.push_back(&frame).unwrap();
+        }
+        let schema = [("Missing".to_string(), 3)];
+        let snapshot = encode_parts(
+            stack.front_index(),
+            stack.back_index(),
+            stack.as_bytes(),
+            &schema,
+        );
+
+        let Err(Error::IncompatibleTasks(types)) = Scheduler::restore(&snapshot) else {
+            panic!("expected incompatible tasks");
+        };
+        assert_eq!(
+            types,
+            [
+                "Missing (version 3 is newer than the supported version 1)",
+                "Unknown (unknown or invalid task)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_field_helpers() {
+        let value = Value::Map(vec![(Value::Text("a".into()), Value::Bool(true))]);
+        let value = rename_field(value, "a", "b").unwrap();
+        let value = insert_field(value, "c", Value::Null).unwrap();
+
+        assert_eq!(
+            value,
+            Value::Map(vec![
+                (Value::Text("b".into()), Value::Bool(true)),
+                (Value::Text("c".into()), Value::Null),
+            ])
+        );
+        assert!(rename_field(Value::Null, "a", "b").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{DecodeLimits, Error, Result, Scheduler};
+
+/// Magic bytes at the start of every signed snapshot.
+const SIGNED_MAGIC: &[u8; 4] = b"SCHS";
+
+/// Magic bytes at the start of every encrypted snapshot.
+const ENCRYPTED_MAGIC: &[u8; 4] = b"SCHE";
+
+/// Current sealed snapshot format version.
+const VERSION: u8 = 1;
+
+/// Size of the magic bytes and the version.
+const HEADER_SIZE: usize = 5;
+
+/// Size of an HMAC-SHA256 tag.
+const TAG_SIZE: usize = 32;
+
+/// Size of a ChaCha20-Poly1305 nonce.
+const NONCE_SIZE: usize = 12;
+
+impl Scheduler {
+    /// Serializes the scheduler into a snapshot signed with HMAC-SHA256.
+    ///
+    /// The snapshot itself stays readable; the signature only lets
+    /// [`Scheduler::restore_signed`] detect modified or foreign snapshots.
+    pub fn snapshot_signed(&self, key: &[u8]) -> Vec<u8> {
+        let snapshot = self.snapshot();
+
+        let mut sealed = Vec::with_capacity(HEADER_SIZE + TAG_SIZE + snapshot.len());
+        sealed.extend_from_slice(SIGNED_MAGIC);
+        sealed.push(VERSION);
+        sealed.extend_from_slice(&sign(key, &sealed, &snapshot));
+        sealed.extend_from_slice(&snapshot);
+
+        sealed
+    }
+
+    /// Creates a scheduler from a snapshot produced by [`Scheduler::snapshot_signed`].
+    ///
+    /// Fails with [`Error::Authentication`] unless the snapshot was signed with
+    /// `key` and hasn't been modified since. The frames are checked against
+    /// `limits`, if given, once the signature is verified.
+    pub fn restore_signed(
+        sealed: &[u8],
+        key: &[u8],
+        limits: Option<&DecodeLimits>,
+    ) -> Result<Self> {
+        let body = check_header(sealed, SIGNED_MAGIC)?;
+        if body.len() < TAG_SIZE {
+            return Err(Error::Authentication);
+        }
+        let (tag, snapshot) = body.split_at(TAG_SIZE);
+
+        let mut mac = new_mac(key);
+        mac.update(&sealed[..HEADER_SIZE]);
+        mac.update(snapshot);
+        mac.verify_slice(tag).map_err(|_| Error::Authentication)?;
+
+        Self::restore_limited(snapshot, limits)
+    }
+
+    /// Serializes the scheduler into a snapshot encrypted with ChaCha20-Poly1305.
+    ///
+    /// Every snapshot uses a fresh random nonce. The encryption also
+    /// authenticates the snapshot, so [`Scheduler::restore_encrypted`] rejects
+    /// modified or foreign snapshots like [`Scheduler::restore_signed`] does.
+    pub fn snapshot_encrypted(&self, key: &[u8; 32]) -> Result<Vec<u8>> {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let mut sealed = Vec::new();
+        sealed.extend_from_slice(ENCRYPTED_MAGIC);
+        sealed.push(VERSION);
+
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &self.snapshot(),
+                    aad: &sealed,
+                },
+            )
+            .map_err(|_| Error::InvalidSnapshot("encryption failed".to_string()))?;
+
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+
+        Ok(sealed)
+    }
+
+    /// Creates a scheduler from a snapshot produced by [`Scheduler::snapshot_encrypted`].
+    ///
+    /// Fails with [`Error::Authentication`] unless the snapshot was encrypted
+    /// with `key` and hasn't been modified since. The frames are checked
+    /// against `limits`, if given, once the snapshot is decrypted.
+    pub fn restore_encrypted(
+        sealed: &[u8],
+        key: &[u8; 32],
+        limits: Option<&DecodeLimits>,
+    ) -> Result<Self> {
+        let body = check_header(sealed, ENCRYPTED_MAGIC)?;
+        if body.len() < NONCE_SIZE {
+            return Err(Error::Authentication);
+        }
+        let (nonce, ciphertext) = body.split_at(NONCE_SIZE);
+
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        let snapshot = cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &sealed[..HEADER_SIZE],
+                },
+            )
+            .map_err(|_| Error::Authentication)?;
+
+        Self::restore_limited(&snapshot, limits)
+    }
+}
+
+/// Checks the magic bytes and version of a sealed snapshot and returns the rest.
+fn check_header<'a>(sealed: &'a [u8], magic: &[u8; 4]) -> Result<&'a [u8]> {
+    if sealed.len() < HEADER_SIZE || &sealed[..4] != magic {
+        return Err(Error::InvalidSnapshot(
+            "missing sealed snapshot header".to_string(),
+        ));
+    }
+    if sealed[4] != VERSION {
+        return Err(Error::InvalidSnapshot(format!(
+            "unsupported sealed snapshot version {}",
+            sealed[4]
+        )));
+    }
+
+    Ok(&sealed[HEADER_SIZE..])
+}
+
+fn new_mac(key: &[u8]) -> Hmac<Sha256> {
+    // HMAC accepts keys of any length
+    <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap_or_else(|_| unreachable!())
+}
+
+fn sign(key: &[u8], header: &[u8], snapshot: &[u8]) -> [u8; TAG_SIZE] {
+    let mut mac = new_mac(key);
+    mac.update(header);
+    mac.update(snapshot);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler() -> Scheduler {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_data(&"secret").unwrap();
+        scheduler
+    }
+
+    #[test]
+    fn test_signed_roundtrip() {
+        let sealed = scheduler().snapshot_signed(b"key");
+
+        let mut restored = Scheduler::restore_signed(&sealed, b"key", None).unwrap();
+        assert_eq!(restored.pop_data::<String>().unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_sealed_restore_enforces_limits() {
+        let limits = DecodeLimits::new().max_string_len(4);
+        let key = [7; 32];
+
+        let signed = scheduler().snapshot_signed(b"key");
+        assert!(matches!(
+            Scheduler::restore_signed(&signed, b"key", Some(&limits)),
+            Err(Error::LimitExceeded(_))
+        ));
+
+        let encrypted = scheduler().snapshot_encrypted(&key).unwrap();
+        assert!(matches!(
+            Scheduler::restore_encrypted(&encrypted, &key, Some(&limits)),
+            Err(Error::LimitExceeded(_))
+        ));
+
+        let limits = DecodeLimits::new();
+        let restored = Scheduler::restore_signed(&signed, b"key", Some(&limits)).unwrap();
+        assert_eq!(restored.decode_limits(), Some(&limits));
+    }
+
+    #[test]
+    fn test_signed_rejects_tampering() {
+        let sealed = scheduler().snapshot_signed(b"key");
+
+        let mut tampered = sealed.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+
+        assert!(matches!(
+            Scheduler::restore_signed(&tampered, b"key", None),
+            Err(Error::Authentication)
+        ));
+        assert!(matches!(
+            Scheduler::restore_signed(&sealed, b"other key", None),
+            Err(Error::Authentication)
+        ));
+        assert!(matches!(
+            Scheduler::restore_signed(&sealed[..20], b"key", None),
+            Err(Error::Authentication)
+        ));
+    }
+
+    #[test]
+    fn test_encrypted_roundtrip() {
+        let key = [7; 32];
+        let sealed = scheduler().snapshot_encrypted(&key).unwrap();
+
+        // The plaintext must not leak into the encrypted snapshot
+        assert!(!sealed.windows(6).any(|window| window == b"secret"));
+        assert_ne!(sealed, scheduler().snapshot_encrypted(&key).unwrap());
+
+        let mut restored = Scheduler::restore_encrypted(&sealed, &key, None).unwrap();
+        assert_eq!(restored.pop_data::<String>().unwrap(), "secret");
+
+        let mut tampered = sealed.clone();
+        tampered[HEADER_SIZE + NONCE_SIZE] ^= 1;
+        assert!(matches!(
+            Scheduler::restore_encrypted(&tampered, &key, None),
+            Err(Error::Authentication)
+        ));
+        assert!(matches!(
+            Scheduler::restore_encrypted(&sealed, &[8; 32], None),
+            Err(Error::Authentication)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_wrong_format() {
+        let key = [7; 32];
+        let signed = scheduler().snapshot_signed(&key);
+
+        assert!(matches!(
+            Scheduler::restore_encrypted(&signed, &key, None),
+            Err(Error::InvalidSnapshot(_))
+        ));
+        assert!(matches!(
+            Scheduler::restore_signed(&scheduler().snapshot(), &key, None),
+            Err(Error::InvalidSnapshot(_))
+        ));
+    }
+}
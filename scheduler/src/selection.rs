@@ -0,0 +1,427 @@
+use std::cmp::Reverse;
+use std::io::Cursor;
+
+use serde::{Deserialize, Serialize};
+
+use crate::observer::Event;
+use crate::{DecodeLimits, Result, Scheduler, SchedulerStack, SchedulerTask};
+
+/// How a [`Scheduler`] picks the pending task that executes next.
+///
+/// Pending tasks normally run in stack order, which phased tasks like `Mul`
+/// rely on: a task finds the data of the tasks spawned before it on top of the
+/// data stack. The other modes only ever move a task ahead of the top one if it
+/// is [`SchedulerTask::independent`], i.e. it takes no data from the tasks it
+/// overtakes and leaves none behind, so results never change.
+///
+/// Selection is opt-in per task type: a type takes part by overriding
+/// [`SchedulerTask::priority`] or [`SchedulerTask::deadline`] and
+/// [`SchedulerTask::independent`]. Tasks that don't, like the arithmetic tasks
+/// of the `tasks` crate, whose results the next task expects in place, run in
+/// stack order in every mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Selection {
+    /// The most recently pushed task runs next.
+    #[default]
+    Lifo,
+    /// The runnable task with the highest priority runs next.
+    Priority,
+    /// The runnable task with the earliest deadline runs next (EDF).
+    ///
+    /// Tasks without a deadline run after all tasks that have one; ties are
+    /// broken by priority.
+    EarliestDeadline,
+}
+
+impl Scheduler {
+    /// Sets how the next task to execute is picked among the pending tasks.
+    ///
+    /// Every mode other than [`Selection::Lifo`] compares the priority,
+    /// deadline and independence of the pending tasks at the start of each
+    /// step. They are read when a task is pushed, or from its frame the first
+    /// time it's compared, and kept until it's popped.
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = selection;
+    }
+
+    /// Returns how the next task to execute is picked.
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+
+    /// Moves the task that should execute next to the top of the task stack.
+    ///
+    /// The top task is always runnable; a task further down only if it is
+    /// independent. Ties go to the task closest to the top, so tasks of equal
+    /// rank keep their stack order.
+    pub(crate) fn select(&mut self) -> Result<()> {
+        if self.selection == Selection::Lifo {
+            return Ok(());
+        }
+        self.candidates.load(&self.stack, self.limits.as_ref())?;
+
+        let mut best: Option<(usize, Rank)> = None;
+        for (depth, slot) in self.candidates.slots.iter().rev().enumerate() {
+            // Frames that can't be loaded are left for the step that pops them to report
+            let Slot::Read(candidate) = slot else {
+                continue;
+            };
+            if depth > 0 && !candidate.independent {
+                continue;
+            }
+
+            let rank = Rank::new(self.selection, candidate);
+            if best.as_ref().is_none_or(|(_, best)| rank < *best) {
+                best = Some((depth, rank));
+            }
+        }
+
+        match best {
+            Some((depth, _)) if depth > 0 => {
+                raise(&mut self.stack, depth, |stack| {
+                    // File-backed storage shadows the popped frames before they are rewritten
+                    let (front_index, back_index) = (stack.front_index(), stack.back_index());
+                    stack.storage_mut().on_event(
+                        &Event::TaskSelected(depth),
+                        front_index,
+                        back_index,
+                    );
+                })?;
+                self.ancestry.selected(depth);
+                self.candidates.selected(depth);
+
+                self.notify(Event::TaskSelected(depth))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What selection compares of a pending task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Candidate {
+    priority: u32,
+    deadline: Option<u64>,
+    independent: bool,
+}
+
+impl Candidate {
+    pub(crate) fn of(task: &dyn SchedulerTask) -> Self {
+        Self {
+            priority: task.priority(),
+            deadline: task.deadline(),
+            independent: task.independent(),
+        }
+    }
+}
+
+/// A pending task as far as selection knows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    /// Pushed as a frame; read from it when it's first compared.
+    Unread,
+    /// The frame can't be loaded.
+    Unloadable,
+    Read(Candidate),
+}
+
+/// A slot for every pending task, next task last, so each task is
+/// deserialized at most once for selection.
+#[derive(Debug, Default)]
+pub(crate) struct Candidates {
+    slots: Vec<Slot>,
+    /// Whether there is a slot for every task frame on the stack. Tasks that
+    /// were already on the stack, e.g. after a restore, have none until the
+    /// first selection.
+    synced: bool,
+}
+
+impl Candidates {
+    pub(crate) fn pushed(&mut self) {
+        self.slots.push(Slot::Unread);
+    }
+
+    /// Records the candidate of the task pushed last.
+    pub(crate) fn read(&mut self, candidate: Candidate) {
+        if let Some(slot) = self.slots.last_mut() {
+            *slot = Slot::Read(candidate);
+        }
+    }
+
+    pub(crate) fn popped(&mut self) {
+        self.slots.pop();
+    }
+
+    /// Moves the slot of the pending task `depth` tasks below the top to the top.
+    pub(crate) fn selected(&mut self, depth: usize) {
+        if let Some(index) = self.slots.len().checked_sub(depth + 1) {
+            let slot = self.slots.remove(index);
+            self.slots.push(slot);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+        self.synced = true;
+    }
+
+    /// Reads the frames of the tasks that have no candidate yet.
+    fn load(&mut self, stack: &SchedulerStack, limits: Option<&DecodeLimits>) -> Result<()> {
+        if self.synced && !self.slots.contains(&Slot::Unread) {
+            return Ok(());
+        }
+
+        let frames = stack.back_frames()?;
+        if !self.synced {
+            self.slots = vec![Slot::Unread; frames.len()];
+            self.synced = true;
+        }
+        for (slot, frame) in self.slots.iter_mut().zip(&frames) {
+            if *slot == Slot::Unread {
+                *slot = match load(frame, limits) {
+                    Some(task) => Slot::Read(Candidate::of(task.as_ref())),
+                    None => Slot::Unloadable,
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load(frame: &[u8], limits: Option<&DecodeLimits>) -> Option<Box<dyn SchedulerTask>> {
+    if let Some(limits) = limits {
+        limits.check_task(frame).ok()?;
+    }
+    ciborium::de::from_reader(Cursor::new(frame)).ok()
+}
+
+/// Sort key of a candidate task; lower ranks run first.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    Priority(Reverse<u32>),
+    Deadline(bool, Option<u64>, Reverse<u32>),
+}
+
+impl Rank {
+    fn new(selection: Selection, candidate: &Candidate) -> Self {
+        match selection {
+            Selection::Lifo | Selection::Priority => Rank::Priority(Reverse(candidate.priority)),
+            Selection::EarliestDeadline => Rank::Deadline(
+                candidate.deadline.is_none(),
+                candidate.deadline,
+                Reverse(candidate.priority),
+            ),
+        }
+    }
+}
+
+/// Moves the task frame `depth` frames below the top of the task stack to the top.
+///
+/// `popped` is called once that frame and the ones above it are popped, before
+/// they are pushed back in their new order.
+pub(crate) fn raise(
+    stack: &mut SchedulerStack,
+    depth: usize,
+    popped: impl FnOnce(&mut SchedulerStack),
+) -> Result<()> {
+    let mut frames = Vec::with_capacity(depth + 1);
+    for _ in 0..=depth {
+        frames.push(stack.pop_back()?);
+    }
+    popped(stack);
+
+    let selected = frames.pop();
+    for frame in frames.iter().rev().chain(&selected) {
+        stack.push_back(frame)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Observer;
+    use crate::recording::Recorder;
+
+    /// Pushes nothing; only its place in the execution order matters.
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Ping {
+        priority: u32,
+        deadline: Option<u64>,
+        independent: bool,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Ping {
+        fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            Ok(vec![])
+        }
+
+        fn priority(&self) -> u32 {
+            self.priority
+        }
+
+        fn deadline(&self) -> Option<u64> {
+            self.deadline
+        }
+
+        fn independent(&self) -> bool {
+            self.independent
+        }
+    }
+
+    /// Collects the frame of every executed task.
+    #[derive(Debug, Default)]
+    struct Executed {
+        frames: Vec<Vec<u8>>,
+        in_step: bool,
+    }
+
+    impl Observer for Executed {
+        fn on_event(&mut self, event: &Event<'_>, _stack: &SchedulerStack) -> Result<()> {
+            match *event {
+                Event::StepStarted => self.in_step = true,
+                Event::TaskPopped(frame) if self.in_step => {
+                    self.frames.push(frame.to_vec());
+                    self.in_step = false;
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+
+    fn ping(priority: u32, deadline: Option<u64>, independent: bool) -> Box<dyn SchedulerTask> {
+        Box::new(Ping {
+            priority,
+            deadline,
+            independent,
+        })
+    }
+
+    /// Runs the tasks, pushed in order, and returns the priorities in execution order.
+    fn run(selection: Selection, tasks: Vec<Box<dyn SchedulerTask>>) -> Vec<u32> {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_selection(selection);
+        for task in tasks {
+            scheduler.push_task(task).unwrap();
+        }
+        scheduler.add_observer(Executed::default());
+        scheduler.execute_all().unwrap();
+
+        let executed = scheduler.remove_observer::<Executed>().unwrap();
+        executed
+            .frames
+            .iter()
+            .map(|frame| {
+                let task: Box<dyn SchedulerTask> =
+                    ciborium::de::from_reader(Cursor::new(frame)).unwrap();
+                task.priority()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lifo_by_default() {
+        let tasks = vec![
+            ping(3, None, true),
+            ping(1, None, true),
+            ping(2, None, true),
+        ];
+        assert_eq!(run(Selection::Lifo, tasks), [2, 1, 3]);
+    }
+
+    #[test]
+    fn test_priority_only_raises_independent_tasks() {
+        let tasks = vec![
+            ping(9, None, false),
+            ping(5, None, true),
+            ping(1, None, false),
+            ping(7, None, true),
+            ping(0, None, false),
+        ];
+
+        // The dependent task with priority 9 waits until it reaches the top
+        assert_eq!(run(Selection::Priority, tasks), [7, 5, 0, 1, 9]);
+    }
+
+    #[test]
+    fn test_earliest_deadline() {
+        let tasks = vec![
+            ping(1, None, true),
+            ping(2, Some(20), true),
+            ping(3, Some(10), true),
+            ping(4, Some(20), true),
+            ping(5, None, false),
+        ];
+        assert_eq!(run(Selection::EarliestDeadline, tasks), [3, 4, 2, 5, 1]);
+    }
+
+    #[test]
+    fn test_candidates_follow_the_stack() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_selection(Selection::Priority);
+        scheduler.push_task(ping(1, None, true)).unwrap();
+        scheduler.push_task(ping(2, None, true)).unwrap();
+
+        // Tasks pushed as frames are read when they're first compared
+        let mut frame = Vec::new();
+        ciborium::ser::into_writer(&ping(3, None, true), &mut frame).unwrap();
+        scheduler.push_task_frame(&frame).unwrap();
+        assert_eq!(scheduler.candidates.slots[2], Slot::Unread);
+
+        scheduler.select().unwrap();
+        let priorities: Vec<_> = scheduler
+            .candidates
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                Slot::Read(candidate) => candidate.priority,
+                _ => panic!("unread slot {:?}", slot),
+            })
+            .collect();
+        assert_eq!(priorities, [1, 2, 3]);
+
+        // A restored scheduler reads the tasks it didn't push on its first selection
+        let mut restored = Scheduler::restore(&scheduler.snapshot()).unwrap();
+        restored.set_selection(Selection::Priority);
+        restored.push_task(ping(5, None, true)).unwrap();
+        restored.execute_all().unwrap();
+        assert!(restored.candidates.slots.is_empty());
+    }
+
+    #[test]
+    fn test_raise() {
+        let mut stack = SchedulerStack::new();
+        for frame in [[1_u8], [2], [3], [4]] {
+            stack.push_back(&frame).unwrap();
+        }
+
+        let mut popped = None;
+        raise(&mut stack, 2, |stack| popped = Some(stack.back_index())).unwrap();
+
+        assert_eq!(popped, Some(crate::SCHEDULER_CAPACITY - 3));
+        assert_eq!(stack.back_frames().unwrap(), [[1], [3], [4], [2]]);
+    }
+
+    #[test]
+    fn test_replay_selects_like_the_recording() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_selection(Selection::Priority);
+        scheduler.add_observer(Recorder::new(&scheduler));
+        for task in [ping(2, None, true), ping(1, None, true)] {
+            scheduler.push_task(task).unwrap();
+        }
+        scheduler.execute_all().unwrap();
+
+        let recording = scheduler
+            .remove_observer::<Recorder>()
+            .unwrap()
+            .into_recording();
+        assert_eq!(recording.selection, Selection::Priority);
+
+        let report = recording.replay().unwrap();
+        assert_eq!((report.steps, report.divergence), (2, None));
+    }
+}
@@ -0,0 +1,287 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use crate::{Error, Result, Scheduler, SchedulerStack, schema};
+
+/// Magic bytes at the start of every snapshot.
+const MAGIC: &[u8; 4] = b"SCHD";
+
+/// Current snapshot format version.
+///
+/// Version 2 added the schema versions of the task types on the stack.
+const VERSION: u8 = 2;
+
+/// Size of the fixed snapshot header preceding the schema section.
+const HEADER_SIZE: usize = 18;
+
+/// Length of the frame length headers used by [`SchedulerStack`].
+const LENGTH_SIZE: u8 = 2;
+
+impl Scheduler {
+    /// Serializes the scheduler's stack into a snapshot.
+    ///
+    /// The snapshot holds the stack indices and the schema versions of the
+    /// task types on the stack, followed by the whole stack buffer, so
+    /// restoring it reproduces the exact pending tasks and data. Observers are
+    /// not part of the snapshot.
+    pub fn snapshot(&self) -> Vec<u8> {
+        encode(&self.stack)
+    }
+
+    /// Creates a scheduler from a snapshot produced by [`Scheduler::snapshot`].
+    ///
+    /// Task frames written with an older schema version of their type are
+    /// upgraded with the registered migrations. Fails with
+    /// [`Error::IncompatibleTasks`] if any task can't be loaded by this binary.
+    pub fn restore(snapshot: &[u8]) -> Result<Self> {
+        let (mut stack, recorded) = read_snapshot(snapshot)?;
+        schema::upgrade(&mut stack, &recorded)?;
+
+        Ok(Self {
+            stack,
+            ..Self::default()
+        })
+    }
+
+    /// Writes a snapshot to `path`.
+    ///
+    /// The snapshot is written to a temporary file, synced and then renamed
+    /// over `path`, so a crash never leaves a partially written snapshot.
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), &self.snapshot())
+    }
+
+    /// Creates a scheduler from a snapshot file written by [`Scheduler::save_snapshot`].
+    pub fn load_snapshot(path: impl AsRef<Path>) -> Result<Self> {
+        Self::restore(&fs::read(path)?)
+    }
+}
+
+/// Reads the stack and the recorded schema versions out of a snapshot.
+///
+/// Unlike [`Scheduler::restore`] this neither migrates nor deserializes any
+/// task, so it also works for snapshots holding task types that are unknown
+/// to this binary or that are corrupt.
+pub fn read_snapshot(snapshot: &[u8]) -> Result<(SchedulerStack, Vec<(String, u32)>)> {
+    let parts = decode(snapshot)?;
+    let stack = SchedulerStack::from_parts(parts.front_index, parts.back_index, parts.buffer)?;
+
+    Ok((stack, parts.schema))
+}
+
+/// Serializes a stack into the snapshot format.
+pub(crate) fn encode(stack: &SchedulerStack) -> Vec<u8> {
+    encode_parts(
+        stack.front_index(),
+        stack.back_index(),
+        stack.as_bytes(),
+        &schema::versions(stack),
+    )
+}
+
+/// Serializes stack indices, task type schema versions and a stack buffer
+/// into the snapshot format.
+pub(crate) fn encode_parts(
+    front_index: usize,
+    back_index: usize,
+    buffer: &[u8],
+    schema: &[(String, u32)],
+) -> Vec<u8> {
+    let mut snapshot = Vec::with_capacity(HEADER_SIZE + buffer.len());
+
+    snapshot.extend_from_slice(MAGIC);
+    snapshot.push(VERSION);
+    snapshot.push(LENGTH_SIZE);
+    snapshot.extend_from_slice(&encode_index(buffer.len()));
+    snapshot.extend_from_slice(&encode_index(front_index));
+    snapshot.extend_from_slice(&encode_index(back_index));
+
+    snapshot.extend_from_slice(&encode_index(schema.len()));
+    for (task_type, version) in schema {
+        snapshot.extend_from_slice(&encode_index(task_type.len()));
+        snapshot.extend_from_slice(task_type.as_bytes());
+        snapshot.extend_from_slice(&version.to_le_bytes());
+    }
+
+    snapshot.extend_from_slice(buffer);
+
+    snapshot
+}
+
+/// The parts of a decoded snapshot.
+pub(crate) struct SnapshotParts<'a> {
+    pub front_index: usize,
+    pub back_index: usize,
+    pub buffer: &'a [u8],
+    /// Schema version of every task type on the stack
+    pub schema: Vec<(String, u32)>,
+}
+
+/// Splits a snapshot into its parts.
+///
+/// Snapshots of version 1 have no schema section; their task types are all
+/// at the initial schema version.
+pub(crate) fn decode(snapshot: &[u8]) -> Result<SnapshotParts<'_>> {
+    if snapshot.len() < HEADER_SIZE || &snapshot[..4] != MAGIC {
+        return Err(Error::InvalidSnapshot(
+            "missing snapshot header".to_string(),
+        ));
+    }
+    if snapshot[4] != VERSION && snapshot[4] != 1 {
+        return Err(Error::InvalidSnapshot(format!(
+            "unsupported version {}",
+            snapshot[4]
+        )));
+    }
+    if snapshot[5] != LENGTH_SIZE {
+        return Err(Error::InvalidSnapshot(format!(
+            "unsupported length size {}",
+            snapshot[5]
+        )));
+    }
+
+    let capacity = decode_index(&snapshot[6..10]);
+    let front_index = decode_index(&snapshot[10..14]);
+    let back_index = decode_index(&snapshot[14..18]);
+    let mut rest = &snapshot[HEADER_SIZE..];
+
+    let mut schema = Vec::new();
+    if snapshot[4] >= 2 {
+        let truncated = || Error::InvalidSnapshot("truncated schema section".to_string());
+        let mut take = |length: usize| {
+            let taken = rest.get(..length).ok_or_else(truncated)?;
+            rest = &rest[length..];
+            Ok::<_, Error>(taken)
+        };
+
+        for _ in 0..decode_index(take(4)?) {
+            let length = decode_index(take(4)?);
+            let task_type = String::from_utf8(take(length)?.to_vec())
+                .map_err(|_| Error::InvalidSnapshot("task type is not UTF-8".to_string()))?;
+            schema.push((task_type, decode_index(take(4)?) as u32));
+        }
+    }
+
+    if rest.len() != capacity {
+        return Err(Error::InvalidSnapshot(format!(
+            "expected {} buffer bytes, found {}",
+            capacity,
+            rest.len()
+        )));
+    }
+
+    Ok(SnapshotParts {
+        front_index,
+        back_index,
+        buffer: rest,
+        schema,
+    })
+}
+
+/// Atomically replaces the contents of `path` with `bytes`.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    let mut file = File::create(&temporary)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&temporary, path)?;
+
+    // Persist the rename itself
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        File::open(parent)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+fn encode_index(index: usize) -> [u8; 4] {
+    u32::try_from(index).unwrap_or(u32::MAX).to_le_bytes()
+}
+
+fn decode_index(bytes: &[u8]) -> usize {
+    let mut index = [0; 4];
+    index.copy_from_slice(bytes);
+    u32::from_le_bytes(index) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_data(&42_u64).unwrap();
+        scheduler.push_data(&"text").unwrap();
+
+        let snapshot = scheduler.snapshot();
+        let mut restored = Scheduler::restore(&snapshot).unwrap();
+
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.pop_data::<String>().unwrap(), "text");
+        assert_eq!(restored.pop_data::<u64>().unwrap(), 42);
+        assert!(restored.is_empty());
+    }
+
+    #[test]
+    fn test_read_snapshot_keeps_unknown_tasks() {
+        let value = ciborium::Value::Map(vec![(
+            ciborium::Value::Text("type".into()),
+            ciborium::Value::Text("Unknown".into()),
+        )]);
+        let mut frame = Vec::new();
+        ciborium::ser::into_writer(&value, &mut frame).unwrap();
+
+        let mut stack = SchedulerStack::default();
+        stack.push_back(&frame).unwrap();
+        let snapshot = encode(&stack);
+
+        assert!(Scheduler::restore(&snapshot).is_err());
+        let (read, schema) = read_snapshot(&snapshot).unwrap();
+        assert_eq!(read.back_frames().unwrap(), [frame]);
+        assert_eq!(schema, [("Unknown".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_restore_version_1() {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_data(&7_u8).unwrap();
+
+        // Version 1 snapshots have no schema section
+        let mut snapshot = scheduler.snapshot();
+        snapshot[4] = 1;
+        snapshot.drain(HEADER_SIZE..HEADER_SIZE + 4);
+
+        let mut restored = Scheduler::restore(&snapshot).unwrap();
+        assert_eq!(restored.pop_data::<u8>().unwrap(), 7);
+    }
+
+    #[test]
+    fn test_restore_rejects_invalid_snapshots() {
+        let snapshot = Scheduler::new().snapshot();
+
+        assert!(matches!(
+            Scheduler::restore(&snapshot[..10]),
+            Err(Error::InvalidSnapshot(_))
+        ));
+        assert!(matches!(
+            Scheduler::restore(&snapshot[..snapshot.len() - 1]),
+            Err(Error::InvalidSnapshot(_))
+        ));
+
+        let mut corrupted = snapshot.clone();
+        corrupted[10..14].copy_from_slice(&encode_index(70_000));
+        assert!(matches!(
+            Scheduler::restore(&corrupted),
+            Err(Error::StackCapacity(_))
+        ));
+    }
+}
@@ -0,0 +1,424 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::inspect::describe_frame;
+use crate::observer::{Event, Observer};
+use crate::snapshot::write_atomic;
+use crate::{Result, Scheduler, SchedulerStack};
+
+/// How a task got onto the task stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnKind {
+    /// Pushed directly on the scheduler rather than by an executing task.
+    Root,
+    /// Returned by the executing task.
+    Spawned,
+    /// The executing task itself, pushed back because of
+    /// [`SchedulerTask::push_self`](crate::SchedulerTask::push_self).
+    Rescheduled,
+}
+
+/// A task pushed onto the task stack while the [`SpawnRecorder`] was attached.
+///
+/// A task that reschedules itself gets a new node for every execution, whose
+/// parent is its previous execution.
+#[derive(Debug, Clone)]
+pub struct SpawnNode {
+    /// The task frame as pushed.
+    pub frame: Vec<u8>,
+    pub kind: SpawnKind,
+    /// The task that pushed this one.
+    pub parent: Option<usize>,
+    /// The tasks this one pushed, in push order.
+    pub children: Vec<usize>,
+    pub executed: bool,
+    pub failed: bool,
+}
+
+/// A data frame pushed by one task and popped by another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataFlow {
+    /// Node of the task that pushed the data.
+    pub from: usize,
+    /// Node of the task that popped the data.
+    pub to: usize,
+    /// The data frame.
+    pub frame: Vec<u8>,
+}
+
+/// Observer recording which task spawned which and the data passed between them.
+///
+/// Nodes are numbered in push order and stay around for the whole recording,
+/// so the complete spawn tree can be exported with [`SpawnRecorder::to_dot`].
+/// The recorder also knows the spawn ancestry of every pending and executing
+/// task at any time.
+#[derive(Debug, Default)]
+pub struct SpawnRecorder {
+    nodes: Vec<SpawnNode>,
+    /// Node of every frame on the task stack, next task last.
+    pending: Vec<usize>,
+    /// Node that pushed every frame on the data stack, top last.
+    producers: Vec<Option<usize>>,
+    data_flows: Vec<DataFlow>,
+    /// Node of the task executing in the current step.
+    current: Option<usize>,
+    in_step: bool,
+    rescheduling: bool,
+    skip_data_flows: bool,
+}
+
+impl SpawnRecorder {
+    /// Creates a recorder whose roots are the tasks already pending on `scheduler`.
+    pub fn new(scheduler: &Scheduler) -> Self {
+        let mut recorder = Self::default();
+        for frame in scheduler.stack().back_frames().unwrap_or_default() {
+            recorder.push(&frame, SpawnKind::Root, None);
+        }
+        recorder.producers = vec![None; scheduler.stack().front_frames().unwrap_or_default().len()];
+        recorder
+    }
+
+    /// Sets whether the data passed between tasks is recorded; it is by default.
+    ///
+    /// Every data flow keeps a copy of its frame, so observers that only need
+    /// the spawn tree turn it off.
+    pub fn record_data_flows(mut self, record: bool) -> Self {
+        self.skip_data_flows = !record;
+        self
+    }
+
+    /// Returns every recorded node, indexed by node number.
+    pub fn nodes(&self) -> &[SpawnNode] {
+        &self.nodes
+    }
+
+    /// Returns the nodes of the pending tasks, next task last.
+    pub fn pending(&self) -> &[usize] {
+        &self.pending
+    }
+
+    /// Returns the node of the task executing right now, if any.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Returns the data passed between tasks, in pop order.
+    pub fn data_flows(&self) -> &[DataFlow] {
+        &self.data_flows
+    }
+
+    /// Returns the task that spawned `node`, skipping earlier executions of
+    /// rescheduled tasks.
+    pub fn spawner(&self, node: usize) -> Option<usize> {
+        let mut node = node;
+        while self.nodes[node].kind == SpawnKind::Rescheduled {
+            node = self.nodes[node].parent?;
+        }
+        self.nodes[node].parent
+    }
+
+    /// Returns the chain of tasks that spawned `node`, root first and ending with `node`.
+    pub fn ancestry(&self, node: usize) -> Vec<usize> {
+        let mut ancestry = vec![node];
+        while let Some(spawner) = self.spawner(ancestry[ancestry.len() - 1]) {
+            ancestry.push(spawner);
+        }
+        ancestry.reverse();
+        ancestry
+    }
+
+    /// Exports the spawn tree in Graphviz DOT format.
+    ///
+    /// Nodes are labelled with the task type and fields; tasks that never ran
+    /// are dashed and failed tasks red. Solid edges lead to spawned tasks,
+    /// dashed edges to rescheduled executions, and dotted blue edges show the
+    /// data values passed from the task that pushed them to the task that
+    /// popped them.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph spawn {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (id, node) in self.nodes.iter().enumerate() {
+            let style = if node.failed {
+                ", color=red"
+            } else if !node.executed {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                dot,
+                "    n{} [label=\"{}\"{}];",
+                id,
+                escape(&describe_frame(&node.frame)),
+                style
+            );
+        }
+
+        for (id, node) in self.nodes.iter().enumerate() {
+            if let Some(parent) = node.parent {
+                let style = match node.kind {
+                    SpawnKind::Rescheduled => " [style=dashed, label=\"push_self\"]",
+                    _ => "",
+                };
+                let _ = writeln!(dot, "    n{} -> n{}{};", parent, id, style);
+            }
+        }
+
+        for flow in &self.data_flows {
+            let _ = writeln!(
+                dot,
+                "    n{} -> n{} [style=dotted, color=blue, constraint=false, label=\"{}\"];",
+                flow.from,
+                flow.to,
+                escape(&describe_frame(&flow.frame))
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Writes the spawn tree in Graphviz DOT format to `path`.
+    pub fn save_dot(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), self.to_dot().as_bytes())
+    }
+
+    fn push(&mut self, frame: &[u8], kind: SpawnKind, parent: Option<usize>) {
+        let id = self.nodes.len();
+        self.nodes.push(SpawnNode {
+            frame: frame.to_vec(),
+            kind,
+            parent,
+            children: Vec::new(),
+            executed: false,
+            failed: false,
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(id);
+        }
+        self.pending.push(id);
+    }
+}
+
+impl Observer for SpawnRecorder {
+    fn on_event(&mut self, event: &Event<'_>, _stack: &SchedulerStack) -> Result<()> {
+        match *event {
+            Event::StepStarted => self.in_step = true,
+            Event::TaskPopped(_) => {
+                let node = self.pending.pop();
+                if self.in_step {
+                    if let Some(node) = node {
+                        self.nodes[node].executed = true;
+                    }
+                    self.current = node;
+                }
+            }
+            Event::TaskSelected(depth) => {
+                if let Some(index) = self.pending.len().checked_sub(depth + 1) {
+                    let node = self.pending.remove(index);
+                    self.pending.push(node);
+                }
+            }
+            Event::Rescheduled => self.rescheduling = true,
+            Event::TaskPushed(frame) => match self.current.filter(|_| self.in_step) {
+                Some(current) if self.rescheduling => {
+                    self.rescheduling = false;
+                    self.push(frame, SpawnKind::Rescheduled, Some(current));
+                }
+                Some(current) => self.push(frame, SpawnKind::Spawned, Some(current)),
+                None => self.push(frame, SpawnKind::Root, None),
+            },
+            Event::DataPushed(_) | Event::DataPopped(_) if self.skip_data_flows => {}
+            Event::DataPushed(_) => self.producers.push(self.current.filter(|_| self.in_step)),
+            Event::DataPopped(frame) => {
+                let producer = self.producers.pop().flatten();
+                if let (Some(from), Some(to)) = (producer, self.current.filter(|_| self.in_step)) {
+                    self.data_flows.push(DataFlow {
+                        from,
+                        to,
+                        frame: frame.to_vec(),
+                    });
+                }
+            }
+            Event::StepFinished | Event::StepFailed => {
+                if let (Some(current), true) = (self.current, *event == Event::StepFailed) {
+                    self.nodes[current].failed = true;
+                }
+                self.in_step = false;
+                self.rescheduling = false;
+                self.current = None;
+            }
+            Event::Cleared => {
+                self.pending.clear();
+                self.producers.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escapes a label for a double-quoted DOT string.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    /// Pushes its counter, rescheduling itself until it reaches zero, then
+    /// spawns a [`Total`].
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Repeat {
+        left: u32,
+        #[serde(skip)]
+        again: bool,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Repeat {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            scheduler.push_data(&self.left)?;
+            self.again = self.left > 0;
+            if !self.again {
+                return Ok(vec![Box::new(Total { count: 3 })]);
+            }
+            self.left -= 1;
+            Ok(vec![])
+        }
+
+        fn push_self(&mut self) -> bool {
+            self.again
+        }
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Total {
+        count: u32,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Total {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            let mut total = 0;
+            for _ in 0..self.count {
+                total += scheduler.pop_data::<u32>()?;
+            }
+            scheduler.push_data(&total)?;
+            Ok(vec![])
+        }
+    }
+
+    fn record() -> SpawnRecorder {
+        record_with(true)
+    }
+
+    fn record_with(data_flows: bool) -> SpawnRecorder {
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Repeat {
+                left: 2,
+                again: false,
+            }))
+            .unwrap();
+        scheduler.add_observer(SpawnRecorder::new(&scheduler).record_data_flows(data_flows));
+        scheduler.execute_all().unwrap();
+        scheduler.remove_observer().unwrap()
+    }
+
+    #[test]
+    fn test_records_spawns_and_reschedules() {
+        let recorder = record();
+
+        let kinds: Vec<_> = recorder
+            .nodes()
+            .iter()
+            .map(|node| (node.kind, node.parent, describe_frame(&node.frame)))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (SpawnKind::Root, None, "Repeat {left: 2}".to_string()),
+                (
+                    SpawnKind::Rescheduled,
+                    Some(0),
+                    "Repeat {left: 1}".to_string()
+                ),
+                (
+                    SpawnKind::Rescheduled,
+                    Some(1),
+                    "Repeat {left: 0}".to_string()
+                ),
+                (SpawnKind::Spawned, Some(2), "Total {count: 3}".to_string()),
+            ]
+        );
+        assert!(recorder.nodes().iter().all(|node| node.executed));
+        assert!(recorder.pending().is_empty());
+
+        // Rescheduled executions are the same task as far as ancestry goes
+        assert_eq!(recorder.spawner(3), Some(2));
+        assert_eq!(recorder.spawner(2), None);
+        assert_eq!(recorder.ancestry(3), [2, 3]);
+
+        let flows: Vec<_> = recorder
+            .data_flows()
+            .iter()
+            .map(|flow| (flow.from, flow.to, describe_frame(&flow.frame)))
+            .collect();
+        assert_eq!(
+            flows,
+            [
+                (2, 3, "0".to_string()),
+                (1, 3, "1".to_string()),
+                (0, 3, "2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skip_data_flows() {
+        let recorder = record_with(false);
+
+        assert_eq!(recorder.nodes().len(), 4);
+        assert_eq!(recorder.ancestry(3), [2, 3]);
+        assert!(recorder.data_flows().is_empty());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = record().to_dot();
+
+        assert!(dot.starts_with("digraph spawn {\n"));
+        assert!(dot.contains("    n0 [label=\"Repeat {left: 2}\"];\n"));
+        assert!(dot.contains("    n0 -> n1 [style=dashed, label=\"push_self\"];\n"));
+        assert!(dot.contains("    n2 -> n3;\n"));
+        assert!(
+            dot.contains(
+                "    n1 -> n3 [style=dotted, color=blue, constraint=false, label=\"1\"];\n"
+            )
+        );
+        assert_eq!(escape("\"a\\b\""), "\\\"a\\\\b\\\"");
+    }
+
+    #[test]
+    fn test_failed_and_pending_tasks() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add_observer(SpawnRecorder::default());
+        scheduler.push_task(Box::new(Total { count: 1 })).unwrap();
+        scheduler.push_task(Box::new(Total { count: 1 })).unwrap();
+        assert!(scheduler.execute().is_err());
+
+        let recorder = scheduler.observer::<SpawnRecorder>().unwrap();
+        assert!(recorder.nodes()[1].failed);
+        assert_eq!(recorder.pending(), [0]);
+        assert!(
+            recorder
+                .to_dot()
+                .contains("n0 [label=\"Total {count: 1}\", style=dashed]")
+        );
+    }
+}
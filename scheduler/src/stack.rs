@@ -1,7 +1,12 @@
+use std::fmt;
 use std::num::TryFromIntError;
+use std::ops::Range;
 
 use thiserror::Error;
 
+/// Largest frame [`BidirectionalStack::push_back`] accepts, in bytes.
+pub const MAX_BACK_FRAME_SIZE: usize = u8::MAX as usize;
+
 #[derive(Error, Debug)]
 pub enum StackError {
     #[error("Not enough space in BidirectionalStack")]
@@ -15,20 +20,169 @@ pub enum StackError {
 
     #[error("Conversion")]
     Conversion(#[from] TryFromIntError),
+
+    #[error("Invalid stack layout - indices or buffer size don't match the capacity")]
+    InvalidLayout,
+}
+
+/// A corrupt spot found by [`BidirectionalStack::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corruption {
+    /// Offset in the buffer of the corrupt length header or frame.
+    pub offset: usize,
+    /// What is wrong at that offset.
+    pub problem: String,
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.problem)
+    }
+}
+
+/// Outcome of validating a [`BidirectionalStack`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Number of frames found at the front.
+    pub front_frames: usize,
+    /// Number of frames found at the back.
+    pub back_frames: usize,
+    /// The first corrupt spot, if any.
+    pub corruption: Option<Corruption>,
+}
+
+impl ValidationReport {
+    /// Returns true if no corruption was found.
+    pub fn is_valid(&self) -> bool {
+        self.corruption.is_none()
+    }
+}
+
+/// Backing memory of a [`BidirectionalStack`].
+///
+/// The stack reports every change of its indices, so storages that persist
+/// their contents can keep the indices alongside the buffer.
+pub trait Storage: AsRef<[u8]> + AsMut<[u8]> {
+    /// Called after every change of the stack indices.
+    fn store_indices(&mut self, _front_index: usize, _back_index: usize) {}
 }
 
+/// Heap-allocated in-memory storage of a fixed size.
+#[derive(Clone, Debug)]
+pub struct ArrayStorage<const CAPACITY: usize>(Box<[u8; CAPACITY]>);
+
+impl<const CAPACITY: usize> Default for ArrayStorage<CAPACITY> {
+    fn default() -> Self {
+        // Allocate directly on the heap instead of building the array on the stack
+        let buffer: Box<[u8]> = vec![0; CAPACITY].into_boxed_slice();
+        Self(buffer.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+}
+
+impl<const CAPACITY: usize> AsRef<[u8]> for ArrayStorage<CAPACITY> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl<const CAPACITY: usize> AsMut<[u8]> for ArrayStorage<CAPACITY> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut()
+    }
+}
+
+impl<const CAPACITY: usize> Storage for ArrayStorage<CAPACITY> {}
+
 #[derive(Clone, Debug)]
-pub struct BidirectionalStack<const CAPACITY: usize, const LENGTH_SIZE: usize> {
+pub struct BidirectionalStack<
+    const CAPACITY: usize,
+    const LENGTH_SIZE: usize,
+    S = ArrayStorage<CAPACITY>,
+> {
     front_index: usize,
     back_index: usize,
-    buffer: [u8; CAPACITY],
+    buffer: S,
 }
 
-impl<const CAPACITY: usize, const LENGTH_SIZE: usize> BidirectionalStack<CAPACITY, LENGTH_SIZE> {
+impl<const CAPACITY: usize, const LENGTH_SIZE: usize, S: Storage + Default>
+    BidirectionalStack<CAPACITY, LENGTH_SIZE, S>
+{
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Rebuilds a stack from its indices and raw buffer contents.
+    pub fn from_parts(
+        front_index: usize,
+        back_index: usize,
+        buffer: &[u8],
+    ) -> Result<Self, StackError> {
+        let mut storage = S::default();
+        if buffer.len() != CAPACITY || storage.as_ref().len() != CAPACITY {
+            return Err(StackError::InvalidLayout);
+        }
+        storage.as_mut().copy_from_slice(buffer);
+
+        Self::with_storage(storage, front_index, back_index)
+    }
+}
+
+impl<const CAPACITY: usize, const LENGTH_SIZE: usize, S: Storage>
+    BidirectionalStack<CAPACITY, LENGTH_SIZE, S>
+{
+    /// Creates a stack over existing storage holding frames up to the given indices.
+    pub fn with_storage(
+        storage: S,
+        front_index: usize,
+        back_index: usize,
+    ) -> Result<Self, StackError> {
+        if front_index > back_index || back_index > storage.as_ref().len() {
+            return Err(StackError::InvalidLayout);
+        }
+
+        let mut stack = Self {
+            front_index,
+            back_index,
+            buffer: storage,
+        };
+        stack.store_indices();
+
+        Ok(stack)
+    }
+
+    pub fn front_index(&self) -> usize {
+        self.front_index
+    }
+
+    pub fn back_index(&self) -> usize {
+        self.back_index
+    }
+
+    /// Returns the whole underlying buffer, including free space.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+
+    /// Returns the size of the buffer shared by both ends.
+    ///
+    /// This is `CAPACITY` for default storage, but storage passed to
+    /// [`BidirectionalStack::with_storage`] may be of any size.
+    pub fn capacity(&self) -> usize {
+        self.buffer.as_ref().len()
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.buffer
+    }
+
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.buffer
+    }
+
+    fn store_indices(&mut self) {
+        self.buffer.store_indices(self.front_index, self.back_index);
+    }
+
     pub fn available_capacity(&self) -> usize {
         if self.back_index >= self.front_index {
             self.back_index - self.front_index
@@ -49,16 +203,19 @@ impl<const CAPACITY: usize, const LENGTH_SIZE: usize> BidirectionalStack<CAPACIT
             return Err(StackError::InsufficientCapacity);
         }
 
+        let buffer = self.buffer.as_mut();
+
         for byte in data {
-            self.buffer[self.front_index] = *byte;
+            buffer[self.front_index] = *byte;
             self.front_index = self.front_index.saturating_add(1);
         }
 
         for i in 0..LENGTH_SIZE {
-            self.buffer[self.front_index] = ((data_length >> (i * 8)) & 0xFF).try_into()?;
+            buffer[self.front_index] = ((data_length >> (i * 8)) & 0xFF).try_into()?;
             self.front_index = self.front_index.saturating_add(1);
         }
 
+        self.store_indices();
         Ok(())
     }
 
@@ -67,27 +224,30 @@ impl<const CAPACITY: usize, const LENGTH_SIZE: usize> BidirectionalStack<CAPACIT
             return Err(StackError::Underflow);
         }
 
+        let buffer = self.buffer.as_ref();
+
         let mut data_length = 0_usize;
         for _ in 0..LENGTH_SIZE {
             self.front_index = self.front_index.saturating_sub(1);
-            let x: usize = self.buffer[self.front_index].into();
+            let x: usize = buffer[self.front_index].into();
             data_length = (data_length << 8) | x;
         }
 
         let mut result = Vec::with_capacity(data_length);
         for _ in 0..data_length {
             self.front_index = self.front_index.saturating_sub(1);
-            result.push(self.buffer[self.front_index]);
+            result.push(buffer[self.front_index]);
         }
         result.reverse();
 
+        self.store_indices();
         Ok(result)
     }
 
     pub fn push_back(&mut self, data: &[u8]) -> Result<(), StackError> {
         let data_length = data.len();
 
-        if data_length > u8::MAX.into() {
+        if data_length > MAX_BACK_FRAME_SIZE {
             return Err(StackError::DataTooLarge);
         }
 
@@ -95,16 +255,19 @@ impl<const CAPACITY: usize, const LENGTH_SIZE: usize> BidirectionalStack<CAPACIT
             return Err(StackError::InsufficientCapacity);
         }
 
+        let buffer = self.buffer.as_mut();
+
         for byte in data {
             self.back_index = self.back_index.saturating_sub(1);
-            self.buffer[self.back_index] = *byte;
+            buffer[self.back_index] = *byte;
         }
 
         for i in 0..LENGTH_SIZE {
             self.back_index = self.back_index.saturating_sub(1);
-            self.buffer[self.back_index] = ((data_length >> (i * 8)) & 0xFF).try_into()?;
+            buffer[self.back_index] = ((data_length >> (i * 8)) & 0xFF).try_into()?;
         }
 
+        self.store_indices();
         Ok(())
     }
 
@@ -113,29 +276,226 @@ impl<const CAPACITY: usize, const LENGTH_SIZE: usize> BidirectionalStack<CAPACIT
             return Err(StackError::Underflow);
         }
 
+        let buffer = self.buffer.as_ref();
+
         let mut data_length = 0_usize;
         for _ in 0..LENGTH_SIZE {
-            let x: usize = self.buffer[self.back_index].into();
+            let x: usize = buffer[self.back_index].into();
             data_length = (data_length << 8) | x;
             self.back_index = self.back_index.saturating_add(1);
         }
 
         let mut result = Vec::with_capacity(data_length);
         for _ in 0..data_length {
-            result.push(self.buffer[self.back_index]);
+            result.push(buffer[self.back_index]);
             self.back_index = self.back_index.saturating_add(1);
         }
         result.reverse();
 
+        self.store_indices();
         Ok(result)
     }
 
+    /// Returns a copy of the top frame at the back without popping it.
+    pub fn peek_back(&self) -> Result<Vec<u8>, StackError> {
+        if self.is_empty_back() {
+            return Err(StackError::Underflow);
+        }
+
+        let buffer = self.buffer.as_ref();
+        let mut index = self.back_index;
+
+        let mut data_length = 0_usize;
+        for _ in 0..LENGTH_SIZE {
+            let x: usize = buffer[index].into();
+            data_length = (data_length << 8) | x;
+            index = index.saturating_add(1);
+        }
+
+        let end = index.saturating_add(data_length).min(buffer.len());
+        let mut result = buffer[index..end].to_vec();
+        result.reverse();
+        Ok(result)
+    }
+
+    /// Returns copies of the frames at the front, bottom of the stack first.
+    ///
+    /// Fails with [`StackError::InvalidLayout`] if a length header points
+    /// outside the used part of the buffer.
+    pub fn front_frames(&self) -> Result<Vec<Vec<u8>>, StackError> {
+        let buffer = self.buffer.as_ref();
+        let spans = self.front_spans().map_err(|_| StackError::InvalidLayout)?;
+
+        Ok(spans
+            .into_iter()
+            .map(|span| buffer[span].to_vec())
+            .collect())
+    }
+
+    /// Returns copies of the frames at the back, bottom of the stack first.
+    ///
+    /// The last frame is the one [`BidirectionalStack::pop_back`] returns next.
+    /// Fails with [`StackError::InvalidLayout`] if a length header points
+    /// outside the used part of the buffer.
+    pub fn back_frames(&self) -> Result<Vec<Vec<u8>>, StackError> {
+        let buffer = self.buffer.as_ref();
+        let spans = self.back_spans().map_err(|_| StackError::InvalidLayout)?;
+
+        Ok(spans
+            .into_iter()
+            .map(|span| {
+                let mut frame = buffer[span].to_vec();
+                frame.reverse();
+                frame
+            })
+            .collect())
+    }
+
+    /// Checks that the indices and every length header stay within the used
+    /// parts of the buffer.
+    pub fn validate(&self) -> ValidationReport {
+        self.validate_with(|_| None, |_| None)
+    }
+
+    /// Checks the layout like [`BidirectionalStack::validate`], then passes
+    /// every front and back frame to `check_front` and `check_back`, which
+    /// return the problem with a frame, if any.
+    ///
+    /// Back frames are passed in the order they were pushed in, not reversed
+    /// as stored. Frames are checked in buffer order, so the report points at
+    /// the corrupt frame with the lowest offset.
+    pub fn validate_with(
+        &self,
+        mut check_front: impl FnMut(&[u8]) -> Option<String>,
+        mut check_back: impl FnMut(&[u8]) -> Option<String>,
+    ) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let spans = self
+            .front_spans()
+            .and_then(|front| Ok((front, self.back_spans()?)));
+        let (front, back) = match spans {
+            Ok(spans) => spans,
+            Err(corruption) => {
+                report.corruption = Some(corruption);
+                return report;
+            }
+        };
+        report.front_frames = front.len();
+        report.back_frames = back.len();
+
+        let buffer = self.buffer.as_ref();
+        let front = front
+            .into_iter()
+            .map(|span| (span.start, check_front(&buffer[span])));
+        let back = back.into_iter().rev().map(|span| {
+            let mut frame = buffer[span.clone()].to_vec();
+            frame.reverse();
+            (span.start, check_back(&frame))
+        });
+        report.corruption = front.chain(back).find_map(|(offset, problem)| {
+            Some(Corruption {
+                offset,
+                problem: problem?,
+            })
+        });
+        report
+    }
+
+    /// Returns the buffer range of every front frame, bottom of the stack first.
+    fn front_spans(&self) -> Result<Vec<Range<usize>>, Corruption> {
+        self.check_indices()?;
+        let buffer = self.buffer.as_ref();
+        let mut spans = Vec::new();
+        let mut index = self.front_index;
+
+        while index > 0 {
+            let header = index.checked_sub(LENGTH_SIZE).ok_or(Corruption {
+                offset: 0,
+                problem: format!("truncated length header below index {}", index),
+            })?;
+            let data_length = buffer[header..index]
+                .iter()
+                .rev()
+                .fold(0_usize, |length, byte| (length << 8) | usize::from(*byte));
+            let start = header.checked_sub(data_length).ok_or(Corruption {
+                offset: header,
+                problem: format!(
+                    "length header of {} bytes points before the start of the buffer",
+                    data_length
+                ),
+            })?;
+
+            spans.push(start..header);
+            index = start;
+        }
+        spans.reverse();
+
+        Ok(spans)
+    }
+
+    /// Returns the buffer range of every back frame, bottom of the stack first.
+    fn back_spans(&self) -> Result<Vec<Range<usize>>, Corruption> {
+        self.check_indices()?;
+        let buffer = self.buffer.as_ref();
+        let mut spans = Vec::new();
+        let capacity = self.capacity();
+        let mut index = self.back_index;
+
+        while index < capacity {
+            let start = index
+                .checked_add(LENGTH_SIZE)
+                .filter(|start| *start <= capacity)
+                .ok_or(Corruption {
+                    offset: index,
+                    problem: "truncated length header at the end of the buffer".to_string(),
+                })?;
+            let data_length = buffer[index..start]
+                .iter()
+                .fold(0_usize, |length, byte| (length << 8) | usize::from(*byte));
+            let end = start
+                .checked_add(data_length)
+                .filter(|end| *end <= capacity)
+                .ok_or(Corruption {
+                    offset: index,
+                    problem: format!(
+                        "length header of {} bytes points past the end of the buffer",
+                        data_length
+                    ),
+                })?;
+
+            spans.push(start..end);
+            index = end;
+        }
+        spans.reverse();
+
+        Ok(spans)
+    }
+
+    fn check_indices(&self) -> Result<(), Corruption> {
+        let size = self.capacity();
+        let problem = if self.back_index > size {
+            format!("back index {} is past the capacity", self.back_index)
+        } else if self.front_index > self.back_index {
+            format!(
+                "front index {} is past back index {}",
+                self.front_index, self.back_index
+            )
+        } else {
+            return Ok(());
+        };
+
+        Err(Corruption {
+            offset: self.front_index.min(size),
+            problem,
+        })
+    }
+
     pub fn is_empty_front(&self) -> bool {
         self.front_index == 0
     }
 
     pub fn is_empty_back(&self) -> bool {
-        self.back_index == CAPACITY
+        self.back_index == self.capacity()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -144,16 +504,17 @@ impl<const CAPACITY: usize, const LENGTH_SIZE: usize> BidirectionalStack<CAPACIT
 
     pub fn clear(&mut self) {
         self.front_index = 0;
-        self.back_index = CAPACITY;
+        self.back_index = self.capacity();
+        self.store_indices();
     }
 }
 
-impl<const CAPACITY: usize, const LENGTH_SIZE: usize> Default
-    for BidirectionalStack<CAPACITY, LENGTH_SIZE>
+impl<const CAPACITY: usize, const LENGTH_SIZE: usize, S: Storage + Default> Default
+    for BidirectionalStack<CAPACITY, LENGTH_SIZE, S>
 {
     fn default() -> Self {
         BidirectionalStack {
-            buffer: [0; CAPACITY],
+            buffer: S::default(),
             front_index: 0,
             back_index: CAPACITY,
         }
@@ -185,10 +546,12 @@ mod tests {
 
         stack.push_back(&[1, 2, 3]).unwrap();
         assert!(!stack.is_empty_back());
+        assert_eq!(stack.peek_back().unwrap(), vec![1, 2, 3]);
 
         let data = stack.pop_back().unwrap();
         assert_eq!(data, vec![1, 2, 3]);
         assert!(stack.is_empty_back());
+        assert!(stack.peek_back().is_err());
     }
 
     #[test]
@@ -216,6 +579,87 @@ mod tests {
         assert_eq!(back_data, vec![3, 4]);
     }
 
+    #[test]
+    fn test_from_parts() {
+        let mut stack = BidirectionalStack::<10, 1>::new();
+        stack.push_front(&[1, 2]).unwrap();
+        stack.push_back(&[3]).unwrap();
+
+        let mut copy = BidirectionalStack::<10, 1>::from_parts(
+            stack.front_index(),
+            stack.back_index(),
+            stack.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(copy.pop_front().unwrap(), vec![1, 2]);
+        assert_eq!(copy.pop_back().unwrap(), vec![3]);
+
+        assert!(BidirectionalStack::<10, 1>::from_parts(5, 4, &[0; 10]).is_err());
+        assert!(BidirectionalStack::<10, 1>::from_parts(0, 11, &[0; 10]).is_err());
+        assert!(BidirectionalStack::<10, 1>::from_parts(0, 10, &[0; 9]).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut stack = BidirectionalStack::<32, 2>::new();
+        stack.push_front(&[1, 2, 3]).unwrap();
+        stack.push_front(&[]).unwrap();
+        stack.push_back(&[4, 5]).unwrap();
+        stack.push_back(&[6]).unwrap();
+
+        assert_eq!(stack.front_frames().unwrap(), vec![vec![1, 2, 3], vec![]]);
+        assert_eq!(stack.back_frames().unwrap(), vec![vec![4, 5], vec![6]]);
+        assert_eq!(stack.pop_back().unwrap(), vec![6]);
+
+        let corrupt = BidirectionalStack::<8, 2>::from_parts(2, 8, &[9, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(corrupt.unwrap().front_frames().is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut stack = BidirectionalStack::<32, 2>::new();
+        stack.push_front(&[1, 2, 3]).unwrap();
+        stack.push_front(&[4]).unwrap();
+        stack.push_back(&[5, 6]).unwrap();
+        stack.push_back(&[7]).unwrap();
+
+        let report = stack.validate();
+        assert!(report.is_valid());
+        assert_eq!((report.front_frames, report.back_frames), (2, 2));
+
+        // Back frames are checked from the top of the task stack down, each
+        // in the byte order it was pushed in
+        let mut checked = Vec::new();
+        let report = stack.validate_with(
+            |_| None,
+            |frame| {
+                checked.push(frame.to_vec());
+                (frame == [5, 6]).then(|| "bad back".to_string())
+            },
+        );
+        assert_eq!(checked, [vec![7], vec![5, 6]]);
+        assert_eq!(report.corruption.unwrap().offset, 30);
+
+        // Checking stops at the frame with the lowest offset
+        let report = stack.validate_with(
+            |frame| (frame == [4]).then(|| "bad front".to_string()),
+            |_| panic!("back frames come after front frames"),
+        );
+        let corruption = report.corruption.unwrap();
+        assert_eq!(
+            (corruption.offset, corruption.problem.as_str()),
+            (5, "bad front")
+        );
+
+        let corrupt = BidirectionalStack::<8, 2>::from_parts(2, 6, &[0, 0, 0, 0, 0, 0, 9, 0]);
+        let corruption = corrupt.unwrap().validate().corruption.unwrap();
+        assert_eq!(corruption.offset, 6);
+        assert_eq!(
+            corruption.to_string(),
+            "offset 6: length header of 2304 bytes points past the end of the buffer"
+        );
+    }
+
     #[test]
     fn test_clear() {
         let mut stack = BidirectionalStack::<10, 1>::new();
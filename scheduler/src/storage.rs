@@ -0,0 +1,432 @@
+use std::fs::OpenOptions;
+use std::path::Path;
+
+use memmap2::MmapMut;
+
+use crate::observer::Event;
+use crate::stack::{ArrayStorage, Storage};
+use crate::{DecodeLimits, Error, Result, Scheduler, SchedulerStack};
+
+/// Capacity of the stack backing a [`Scheduler`].
+pub const SCHEDULER_CAPACITY: usize = 65536;
+
+/// Magic bytes at the start of every stack file.
+const MAGIC: &[u8; 4] = b"SCHM";
+
+/// Current stack file format version.
+const VERSION: u8 = 1;
+
+/// Length of the frame length headers used by [`SchedulerStack`].
+const LENGTH_SIZE: u8 = 2;
+
+/// Size of the stack file header preceding the buffer.
+const HEADER_SIZE: usize = 72;
+
+// Header field offsets
+const CAPACITY_OFFSET: usize = 8;
+const FRONT_OFFSET: usize = 16;
+const BACK_OFFSET: usize = 24;
+const IN_STEP_OFFSET: usize = 32;
+const COMMITTED_FRONT_OFFSET: usize = 40;
+const COMMITTED_BACK_OFFSET: usize = 48;
+const LOW_FRONT_OFFSET: usize = 56;
+const HIGH_BACK_OFFSET: usize = 64;
+
+/// Storage of a [`Scheduler`]'s stack.
+#[derive(Debug)]
+pub enum SchedulerStorage {
+    /// Heap memory, lost when the scheduler is dropped.
+    Memory(ArrayStorage<SCHEDULER_CAPACITY>),
+    /// A memory-mapped file that outlives the process.
+    Mapped(MappedStorage),
+}
+
+impl Default for SchedulerStorage {
+    fn default() -> Self {
+        Self::Memory(ArrayStorage::default())
+    }
+}
+
+impl AsRef<[u8]> for SchedulerStorage {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Self::Memory(storage) => storage.as_ref(),
+            Self::Mapped(storage) => storage.as_ref(),
+        }
+    }
+}
+
+impl AsMut<[u8]> for SchedulerStorage {
+    fn as_mut(&mut self) -> &mut [u8] {
+        match self {
+            Self::Memory(storage) => storage.as_mut(),
+            Self::Mapped(storage) => storage.as_mut(),
+        }
+    }
+}
+
+impl Storage for SchedulerStorage {
+    fn store_indices(&mut self, front_index: usize, back_index: usize) {
+        if let Self::Mapped(storage) = self {
+            storage.store_indices(front_index, back_index);
+        }
+    }
+}
+
+impl SchedulerStorage {
+    /// Lets the storage track step boundaries; called before observers see the event.
+    pub(crate) fn on_event(&mut self, event: &Event<'_>, front_index: usize, back_index: usize) {
+        if let Self::Mapped(storage) = self {
+            storage.on_event(event, front_index, back_index);
+        }
+    }
+}
+
+/// Stack storage in a memory-mapped file.
+///
+/// The file starts with a header holding the stack indices, followed by the
+/// stack buffer and a shadow buffer of the same size. Indices are written to
+/// the header after every push and pop, so the file always describes the
+/// current stack.
+///
+/// To keep steps atomic, the header also records the indices at the start of
+/// the current step. Whenever the step pops below that point, the affected
+/// bytes are copied to the shadow buffer before they can be overwritten. When
+/// a file is opened with a step still in progress, the shadowed bytes and the
+/// recorded indices are restored, so the interrupted step runs again.
+///
+/// Changes survive the process crashing; call [`MappedStorage::flush`] to
+/// also make them survive a power loss.
+#[derive(Debug)]
+pub struct MappedStorage {
+    map: MmapMut,
+    capacity: usize,
+}
+
+impl MappedStorage {
+    /// Opens the stack file at `path`, creating an empty stack if it doesn't exist.
+    ///
+    /// Returns the storage together with the stored front and back indices.
+    pub(crate) fn open(path: impl AsRef<Path>, capacity: usize) -> Result<(Self, usize, usize)> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let size = (HEADER_SIZE + 2 * capacity) as u64;
+        let created = file.metadata()?.len() == 0;
+        if created {
+            file.set_len(size)?;
+        } else if file.metadata()?.len() != size {
+            return Err(Error::StackFile(format!(
+                "stack file has {} bytes, expected {}",
+                file.metadata()?.len(),
+                size
+            )));
+        }
+
+        // SAFETY: the file is opened read-write by this process, and the map
+        // is the only way the storage accesses it
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        let mut storage = Self { map, capacity };
+
+        if created {
+            storage.map[..MAGIC.len()].copy_from_slice(MAGIC);
+            storage.map[4] = VERSION;
+            storage.map[5] = LENGTH_SIZE;
+            storage.write(CAPACITY_OFFSET, capacity);
+            storage.store_indices(0, capacity);
+        } else {
+            storage.check_header()?;
+            storage.roll_back_step()?;
+        }
+
+        let (front_index, back_index) = (storage.read(FRONT_OFFSET), storage.read(BACK_OFFSET));
+        Ok((storage, front_index, back_index))
+    }
+
+    /// Flushes all changes to the file on disk.
+    pub fn flush(&self) -> Result<()> {
+        Ok(self.map.flush()?)
+    }
+
+    fn check_header(&self) -> Result<()> {
+        if &self.map[..MAGIC.len()] != MAGIC {
+            return Err(Error::StackFile("missing stack file header".to_string()));
+        }
+        if self.map[4] != VERSION || self.map[5] != LENGTH_SIZE {
+            return Err(Error::StackFile(format!(
+                "unsupported stack file version {}",
+                self.map[4]
+            )));
+        }
+        if self.read(CAPACITY_OFFSET) != self.capacity {
+            return Err(Error::StackFile(format!(
+                "stack file capacity is {}, expected {}",
+                self.read(CAPACITY_OFFSET),
+                self.capacity
+            )));
+        }
+        Ok(())
+    }
+
+    /// Restores the state from the start of an interrupted step.
+    fn roll_back_step(&mut self) -> Result<()> {
+        if self.map[IN_STEP_OFFSET] == 0 {
+            return Ok(());
+        }
+
+        let committed_front = self.read(COMMITTED_FRONT_OFFSET);
+        let committed_back = self.read(COMMITTED_BACK_OFFSET);
+        let low_front = self.read(LOW_FRONT_OFFSET);
+        let high_back = self.read(HIGH_BACK_OFFSET);
+
+        if !(low_front <= committed_front
+            && committed_front <= committed_back
+            && committed_back <= high_back
+            && high_back <= self.capacity)
+        {
+            return Err(Error::StackFile(
+                "stack file has an inconsistent step record".to_string(),
+            ));
+        }
+
+        self.restore_shadow(low_front, committed_front);
+        self.restore_shadow(committed_back, high_back);
+        self.store_indices(committed_front, committed_back);
+        self.map[IN_STEP_OFFSET] = 0;
+
+        Ok(())
+    }
+
+    fn on_event(&mut self, event: &Event<'_>, front_index: usize, back_index: usize) {
+        match event {
+            Event::StepStarted => {
+                self.write(COMMITTED_FRONT_OFFSET, front_index);
+                self.write(COMMITTED_BACK_OFFSET, back_index);
+                self.write(LOW_FRONT_OFFSET, front_index);
+                self.write(HIGH_BACK_OFFSET, back_index);
+                self.map[IN_STEP_OFFSET] = 1;
+            }
+            Event::StepFinished | Event::StepFailed => self.map[IN_STEP_OFFSET] = 0,
+            _ if self.map[IN_STEP_OFFSET] != 0 => {
+                // Shadow committed bytes that the rest of the step may overwrite
+                let low_front = self.read(LOW_FRONT_OFFSET);
+                if front_index < low_front {
+                    self.save_shadow(front_index, low_front);
+                    self.write(LOW_FRONT_OFFSET, front_index);
+                }
+
+                let high_back = self.read(HIGH_BACK_OFFSET);
+                if back_index > high_back {
+                    self.save_shadow(high_back, back_index);
+                    self.write(HIGH_BACK_OFFSET, back_index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn save_shadow(&mut self, start: usize, end: usize) {
+        let buffer = HEADER_SIZE + start..HEADER_SIZE + end;
+        self.map
+            .copy_within(buffer, HEADER_SIZE + self.capacity + start);
+    }
+
+    fn restore_shadow(&mut self, start: usize, end: usize) {
+        let shadow = HEADER_SIZE + self.capacity + start..HEADER_SIZE + self.capacity + end;
+        self.map.copy_within(shadow, HEADER_SIZE + start);
+    }
+
+    fn read(&self, offset: usize) -> usize {
+        let mut value = [0; 8];
+        value.copy_from_slice(&self.map[offset..offset + 8]);
+        u64::from_le_bytes(value) as usize
+    }
+
+    fn write(&mut self, offset: usize, value: usize) {
+        self.map[offset..offset + 8].copy_from_slice(&(value as u64).to_le_bytes());
+    }
+}
+
+impl AsRef<[u8]> for MappedStorage {
+    fn as_ref(&self) -> &[u8] {
+        &self.map[HEADER_SIZE..HEADER_SIZE + self.capacity]
+    }
+}
+
+impl AsMut<[u8]> for MappedStorage {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.map[HEADER_SIZE..HEADER_SIZE + self.capacity]
+    }
+}
+
+impl Storage for MappedStorage {
+    fn store_indices(&mut self, front_index: usize, back_index: usize) {
+        self.write(FRONT_OFFSET, front_index);
+        self.write(BACK_OFFSET, back_index);
+    }
+}
+
+impl Scheduler {
+    /// Opens a scheduler whose stack lives in the memory-mapped file at `path`.
+    ///
+    /// A missing file is created with an empty stack of [`SCHEDULER_CAPACITY`]
+    /// bytes. An existing file is reopened with the tasks and data it holds, so
+    /// execution continues where it left off; a step interrupted by a crash is
+    /// rolled back and runs again. The frames already in the file are checked
+    /// against `limits`, if given, and the limits stay enforced from then on.
+    pub fn open_mapped(path: impl AsRef<Path>, limits: Option<&DecodeLimits>) -> Result<Self> {
+        Self::open_mapped_with_capacity(path, SCHEDULER_CAPACITY, limits)
+    }
+
+    /// Opens a scheduler whose stack of `capacity` bytes lives in the
+    /// memory-mapped file at `path`, like [`Scheduler::open_mapped`].
+    ///
+    /// The file takes twice the capacity on disk, for the stack and its shadow
+    /// buffer, and only the pages in use are kept in memory. An existing file
+    /// must have been created with the same capacity. Snapshots of stacks
+    /// larger than [`SCHEDULER_CAPACITY`] can't be restored into memory.
+    pub fn open_mapped_with_capacity(
+        path: impl AsRef<Path>,
+        capacity: usize,
+        limits: Option<&DecodeLimits>,
+    ) -> Result<Self> {
+        let (storage, front_index, back_index) = MappedStorage::open(path, capacity)?;
+
+        let stack = SchedulerStack::with_storage(
+            SchedulerStorage::Mapped(storage),
+            front_index,
+            back_index,
+        )?;
+
+        let mut scheduler = Self {
+            stack,
+            ..Self::default()
+        };
+        scheduler.enforce_limits(limits)?;
+        Ok(scheduler)
+    }
+
+    /// Flushes a file-backed stack to disk; does nothing for in-memory stacks.
+    pub fn flush(&self) -> Result<()> {
+        match self.stack.storage() {
+            SchedulerStorage::Memory(_) => Ok(()),
+            SchedulerStorage::Mapped(storage) => storage.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Increment {}
+
+    #[typetag::serde]
+    impl SchedulerTask for Increment {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            let value: u64 = scheduler.pop_data()?;
+            scheduler.push_data(&(value + 1))?;
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn test_reopen_continues_execution() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stack.bin");
+
+        {
+            let mut scheduler = Scheduler::open_mapped(&path, None).unwrap();
+            scheduler.push_data(&40_u64).unwrap();
+            scheduler.push_task(Box::new(Increment {})).unwrap();
+            scheduler.push_task(Box::new(Increment {})).unwrap();
+            scheduler.execute().unwrap();
+            scheduler.flush().unwrap();
+        }
+
+        let mut scheduler = Scheduler::open_mapped(&path, None).unwrap();
+        scheduler.execute_all().unwrap();
+        assert_eq!(scheduler.pop_data::<u64>().unwrap(), 42);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_reopen_enforces_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stack.bin");
+
+        {
+            let mut scheduler = Scheduler::open_mapped(&path, None).unwrap();
+            scheduler.push_task(Box::new(Increment {})).unwrap();
+            scheduler.flush().unwrap();
+        }
+
+        let limits = DecodeLimits::new().allow_task("Fork");
+        assert!(matches!(
+            Scheduler::open_mapped(&path, Some(&limits)),
+            Err(Error::TaskNotAllowed(name)) if name == "Increment"
+        ));
+
+        let limits = DecodeLimits::new().allow_task("Increment");
+        let scheduler = Scheduler::open_mapped(&path, Some(&limits)).unwrap();
+        assert_eq!(scheduler.decode_limits(), Some(&limits));
+    }
+
+    #[test]
+    fn test_capacity_beyond_memory_stack() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stack.bin");
+        let capacity = 4 * SCHEDULER_CAPACITY;
+
+        {
+            let mut scheduler =
+                Scheduler::open_mapped_with_capacity(&path, capacity, None).unwrap();
+            for value in 0..40_000_u64 {
+                scheduler.push_data(&value).unwrap();
+            }
+            assert!(scheduler.stack().front_index() > SCHEDULER_CAPACITY);
+        }
+
+        assert!(matches!(
+            Scheduler::open_mapped(&path, None),
+            Err(Error::StackFile(message)) if message.contains("bytes, expected")
+        ));
+
+        let mut scheduler = Scheduler::open_mapped_with_capacity(&path, capacity, None).unwrap();
+        assert_eq!(scheduler.stack().capacity(), capacity);
+        assert_eq!(scheduler.pop_data::<u64>().unwrap(), 39_999);
+        assert!(scheduler.validate().is_valid());
+    }
+
+    #[test]
+    fn test_interrupted_step_is_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stack.bin");
+
+        {
+            let mut scheduler = Scheduler::open_mapped(&path, None).unwrap();
+            scheduler.push_data(&1_u64).unwrap();
+            scheduler.push_task(Box::new(Increment {})).unwrap();
+
+            // Simulate a crash after the step popped its task and operand and
+            // overwrote the operand with a larger value
+            scheduler.notify(Event::StepStarted).unwrap();
+            scheduler.pop_task().unwrap();
+            scheduler.pop_data::<u64>().unwrap();
+            scheduler.push_data(&u64::MAX).unwrap();
+        }
+
+        let mut scheduler = Scheduler::open_mapped(&path, None).unwrap();
+        scheduler.execute_all().unwrap();
+        assert_eq!(scheduler.pop_data::<u64>().unwrap(), 2);
+        assert!(scheduler.is_empty_data());
+    }
+}
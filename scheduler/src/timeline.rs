@@ -0,0 +1,316 @@
+use std::fmt::Write;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use ciborium::Value;
+
+use crate::inspect::{describe_value, json_string};
+use crate::observer::{Event, Observer};
+use crate::snapshot::write_atomic;
+use crate::spawn::SpawnRecorder;
+use crate::{Result, Scheduler, SchedulerStack};
+
+/// A task execution on the timeline.
+///
+/// A span starts when its task starts executing and ends once every task it
+/// spawned, directly or indirectly, has finished, so spans nest by spawn
+/// ancestry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    /// Node of the task in the [`SpawnRecorder`].
+    pub node: usize,
+    /// Spawn ancestry depth; root tasks are at depth 0.
+    pub depth: usize,
+    /// Task type name.
+    pub name: String,
+    /// Start time, relative to when the [`Timeline`] was created.
+    pub start: Duration,
+    /// Time until the task and everything it spawned finished.
+    pub duration: Duration,
+    /// Time spent in the task's own step.
+    pub execution: Duration,
+    pub failed: bool,
+}
+
+/// Observer timing every task execution, exportable as a Chrome Trace Event
+/// file for Perfetto or `chrome://tracing`.
+///
+/// Spawn relationships are tracked by an embedded [`SpawnRecorder`], which
+/// doesn't record data flows.
+#[derive(Debug)]
+pub struct Timeline {
+    spawns: SpawnRecorder,
+    origin: Instant,
+    /// Start of the step in progress.
+    step_start: Option<Duration>,
+    /// Start and end of the step that executed every node, if it ran.
+    steps: Vec<Option<(Duration, Duration)>>,
+}
+
+impl Timeline {
+    /// Creates a timeline whose roots are the tasks already pending on `scheduler`.
+    pub fn new(scheduler: &Scheduler) -> Self {
+        Self {
+            spawns: SpawnRecorder::new(scheduler).record_data_flows(false),
+            origin: Instant::now(),
+            step_start: None,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Returns the spawn relationships recorded so far.
+    pub fn spawns(&self) -> &SpawnRecorder {
+        &self.spawns
+    }
+
+    /// Returns a span for every executed task, in execution order.
+    pub fn spans(&self) -> Vec<Span> {
+        let nodes = self.spawns.nodes();
+
+        // Spawners always have lower node numbers than the tasks they spawn
+        let mut ends: Vec<Option<Duration>> =
+            self.steps.iter().map(|step| step.map(|s| s.1)).collect();
+        ends.resize(nodes.len(), None);
+        for node in (0..ends.len()).rev() {
+            if let (Some(end), Some(spawner)) = (ends[node], self.spawns.spawner(node)) {
+                ends[spawner] = ends[spawner].max(Some(end));
+            }
+        }
+
+        let mut spans: Vec<Span> = self
+            .steps
+            .iter()
+            .enumerate()
+            .filter_map(|(node, step)| {
+                let (start, end) = (*step)?;
+                Some(Span {
+                    node,
+                    depth: self.spawns.ancestry(node).len() - 1,
+                    name: task_name(&nodes[node].frame),
+                    start,
+                    duration: ends[node].unwrap_or(end) - start,
+                    execution: end - start,
+                    failed: nodes[node].failed,
+                })
+            })
+            .collect();
+        spans.sort_by_key(|span| span.start);
+        spans
+    }
+
+    /// Exports the timeline in the Chrome Trace Event JSON format.
+    ///
+    /// Every span becomes a complete event named after its task type, with
+    /// the task's fields and its own execution time as arguments.
+    pub fn to_chrome_trace(&self) -> String {
+        let mut json = String::from("{\"traceEvents\":[\n");
+        json.push_str(
+            "{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":1,\"tid\":1,\"args\":{\"name\":\"scheduler\"}}",
+        );
+
+        for span in self.spans() {
+            let _ = write!(
+                json,
+                ",\n{{\"name\":{},\"cat\":\"task\",\"ph\":\"X\",\"pid\":1,\"tid\":1,\"ts\":{},\"dur\":{},\"args\":{{",
+                json_string(&span.name),
+                micros(span.start),
+                micros(span.duration)
+            );
+
+            let frame = &self.spawns.nodes()[span.node].frame;
+            if let Ok(Value::Map(fields)) = ciborium::de::from_reader(Cursor::new(frame)) {
+                for (key, value) in &fields {
+                    let key = match key {
+                        Value::Text(key) if key == "type" => continue,
+                        Value::Text(key) => key.clone(),
+                        key => describe_value(key),
+                    };
+                    let _ = write!(json, "{}:", json_string(&key));
+                    write_json(&mut json, value);
+                    json.push(',');
+                }
+            }
+            let _ = write!(json, "\"execute_us\":{}", micros(span.execution));
+            if span.failed {
+                json.push_str(",\"failed\":true");
+            }
+            json.push_str("}}");
+        }
+
+        json.push_str("\n],\"displayTimeUnit\":\"ns\"}\n");
+        json
+    }
+
+    /// Writes the timeline in the Chrome Trace Event JSON format to `path`.
+    pub fn save_chrome_trace(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), self.to_chrome_trace().as_bytes())
+    }
+}
+
+impl Observer for Timeline {
+    fn on_event(&mut self, event: &Event<'_>, stack: &SchedulerStack) -> Result<()> {
+        match event {
+            Event::StepStarted => self.step_start = Some(self.origin.elapsed()),
+            Event::StepFinished | Event::StepFailed => {
+                if let (Some(start), Some(node)) = (self.step_start.take(), self.spawns.current()) {
+                    if self.steps.len() <= node {
+                        self.steps.resize(node + 1, None);
+                    }
+                    self.steps[node] = Some((start, self.origin.elapsed()));
+                }
+            }
+            _ => {}
+        }
+        self.spawns.on_event(event, stack)
+    }
+}
+
+/// Returns the task type stored in a task frame.
+fn task_name(frame: &[u8]) -> String {
+    crate::task_type(frame).unwrap_or_else(|_| "<unknown>".to_string())
+}
+
+/// Formats a duration in microseconds with nanosecond precision, exactly, so
+/// nested spans never poke out of their parents through rounding.
+fn micros(duration: Duration) -> String {
+    let nanos = duration.as_nanos();
+    format!("{}.{:03}", nanos / 1000, nanos % 1000)
+}
+
+/// Writes a CBOR value as JSON; byte strings become hex strings and tags are dropped.
+fn write_json(out: &mut String, value: &Value) {
+    match value {
+        Value::Integer(integer) => {
+            let _ = write!(out, "{}", i128::from(*integer));
+        }
+        Value::Float(float) if float.is_finite() => {
+            let _ = write!(out, "{}", float);
+        }
+        Value::Text(text) => out.push_str(&json_string(text)),
+        Value::Bool(value) => {
+            let _ = write!(out, "{}", value);
+        }
+        Value::Tag(_, value) => write_json(out, value),
+        Value::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(out, value);
+            }
+            out.push(']');
+        }
+        Value::Map(entries) => {
+            out.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                let key = match key {
+                    Value::Text(key) => key.clone(),
+                    key => describe_value(key),
+                };
+                let _ = write!(out, "{}:", json_string(&key));
+                write_json(out, value);
+            }
+            out.push('}');
+        }
+        Value::Bytes(_) => out.push_str(&json_string(&describe_value(value))),
+        _ => out.push_str("null"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    /// Spawns `width` copies of itself with one level less until `levels` reaches zero.
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Branch {
+        levels: u32,
+        width: u32,
+        label: String,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Branch {
+        fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            if self.levels == 0 {
+                return Ok(vec![]);
+            }
+            Ok((0..self.width)
+                .map(|_| {
+                    Box::new(Branch {
+                        levels: self.levels - 1,
+                        width: self.width,
+                        label: self.label.clone(),
+                    }) as Box<dyn SchedulerTask>
+                })
+                .collect())
+        }
+    }
+
+    fn timeline(label: &str) -> Timeline {
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .push_task(Box::new(Branch {
+                levels: 2,
+                width: 2,
+                label: label.to_string(),
+            }))
+            .unwrap();
+        scheduler.add_observer(Timeline::new(&scheduler));
+        scheduler.execute_all().unwrap();
+        scheduler.remove_observer().unwrap()
+    }
+
+    #[test]
+    fn test_spans_nest_by_ancestry() {
+        let spans = timeline("tree").spans();
+
+        let depths: Vec<_> = spans.iter().map(|span| span.depth).collect();
+        assert_eq!(depths, [0, 1, 2, 2, 1, 2, 2]);
+
+        for (i, span) in spans.iter().enumerate() {
+            assert!(span.execution <= span.duration);
+            // Every deeper span that follows lies within this one until the next sibling
+            for inner in spans[i + 1..].iter().take_while(|s| s.depth > span.depth) {
+                assert!(inner.start >= span.start);
+                assert!(inner.start + inner.duration <= span.start + span.duration);
+            }
+        }
+        assert_eq!(spans[0].name, "Branch");
+    }
+
+    #[test]
+    fn test_chrome_trace_json() {
+        let trace = timeline("say \"hi\"\n").to_chrome_trace();
+
+        assert!(trace.starts_with("{\"traceEvents\":["));
+        assert!(trace.trim_end().ends_with("\"displayTimeUnit\":\"ns\"}"));
+        assert_eq!(trace.matches("\"ph\":\"X\"").count(), 7);
+        assert!(trace.contains("\"name\":\"Branch\""));
+        assert!(trace.contains("\"levels\":2,\"width\":2,\"label\":\"say \\\"hi\\\"\\n\""));
+        assert!(trace.contains("\"execute_us\":"));
+    }
+
+    #[test]
+    fn test_json_values() {
+        let mut json = String::new();
+        write_json(
+            &mut json,
+            &Value::Array(vec![
+                Value::Integer(u64::MAX.into()),
+                Value::Float(f64::NAN),
+                Value::Bytes(vec![0xab]),
+                Value::Null,
+            ]),
+        );
+        assert_eq!(json, "[18446744073709551615,null,\"h'ab'\",null]");
+    }
+}
@@ -0,0 +1,610 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::inspect::hex;
+use crate::observer::{Event, Observer};
+use crate::recording::{Operation, Recorder};
+use crate::snapshot::write_atomic;
+use crate::{Error, Result, Scheduler, SchedulerStack, task_type};
+
+/// Magic bytes at the start of every binary trace.
+const MAGIC: &[u8; 4] = b"SCHT";
+
+/// Current binary trace format version.
+const VERSION: u8 = 1;
+
+/// Length of the frame length headers used by [`SchedulerStack`].
+const LENGTH_SIZE: i64 = 2;
+
+/// A list of serialized frames.
+type Frames = Vec<Vec<u8>>;
+
+/// One executed step of a [`Trace`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceRow {
+    /// Index of the step, starting at 0
+    pub step: u64,
+    /// Index of the task's type name in [`Trace::task_types`]
+    pub task_type: u32,
+    /// The executed task, serialized
+    pub task: Vec<u8>,
+    /// Data frames popped by the task, in order
+    pub data_popped: Vec<Vec<u8>>,
+    /// Data frames pushed by the task, in order
+    pub data_pushed: Vec<Vec<u8>>,
+    /// Task frames pushed after the task executed, in order
+    pub tasks_pushed: Vec<Vec<u8>>,
+    /// Front and back stack indices before the step
+    pub before: (u32, u32),
+    /// Front and back stack indices after the step
+    pub after: (u32, u32),
+    /// Whether the step returned an error
+    pub failed: bool,
+}
+
+/// Execution trace with one row per executed step.
+///
+/// Traces are written with a [`Tracer`] and can be exported in a columnar
+/// binary format or as CSV. [`Trace::check`] re-executes every row against
+/// the tasks registered in this binary.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    /// Names of the task types referenced by the rows
+    pub task_types: Vec<String>,
+    /// One row per step, in execution order
+    pub rows: Vec<TraceRow>,
+}
+
+impl Trace {
+    /// Returns the type name of the task executed in `row`.
+    pub fn task_type_name(&self, row: &TraceRow) -> Option<&str> {
+        self.task_types
+            .get(row.task_type as usize)
+            .map(String::as_str)
+    }
+
+    /// Encodes the trace in the columnar binary format.
+    ///
+    /// After the header and the task type names, every field is stored as its
+    /// own column: fixed-size columns hold one little-endian value per row,
+    /// byte columns hold all lengths followed by all bytes, and frame list
+    /// columns additionally start with the number of frames per row.
+    pub fn encode(&self) -> Vec<u8> {
+        let rows = &self.rows;
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        put_u32(&mut bytes, self.task_types.len());
+        for name in &self.task_types {
+            put_bytes(&mut bytes, name.as_bytes());
+        }
+        bytes.extend_from_slice(&(rows.len() as u64).to_le_bytes());
+
+        for row in rows {
+            bytes.extend_from_slice(&row.step.to_le_bytes());
+        }
+        for row in rows {
+            bytes.extend_from_slice(&row.task_type.to_le_bytes());
+        }
+        for column in [
+            |row: &TraceRow| row.before.0,
+            |row: &TraceRow| row.before.1,
+            |row: &TraceRow| row.after.0,
+            |row: &TraceRow| row.after.1,
+        ] {
+            for row in rows {
+                bytes.extend_from_slice(&column(row).to_le_bytes());
+            }
+        }
+        bytes.extend(rows.iter().map(|row| u8::from(row.failed)));
+
+        put_frames(&mut bytes, rows.iter().map(|row| row.task.as_slice()));
+        let lists: [fn(&TraceRow) -> &Frames; 3] = [
+            |row| &row.data_popped,
+            |row| &row.data_pushed,
+            |row| &row.tasks_pushed,
+        ];
+        for column in lists {
+            for row in rows {
+                put_u32(&mut bytes, column(row).len());
+            }
+            put_frames(&mut bytes, rows.iter().flat_map(column).map(Vec::as_slice));
+        }
+
+        bytes
+    }
+
+    /// Decodes a trace produced by [`Trace::encode`].
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(4)? != MAGIC {
+            return Err(Error::InvalidTrace("missing trace header".to_string()));
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(Error::InvalidTrace(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+
+        let mut task_types = Vec::new();
+        for _ in 0..reader.u32()? {
+            let name = String::from_utf8(reader.take_bytes()?.to_vec())
+                .map_err(|_| Error::InvalidTrace("task type is not UTF-8".to_string()))?;
+            task_types.push(name);
+        }
+
+        let count = usize::try_from(reader.u64()?)
+            .map_err(|_| Error::InvalidTrace("too many rows".to_string()))?;
+        // Every row takes at least 37 bytes, reject counts the input can't hold
+        if count > reader.bytes.len() / 37 {
+            return Err(Error::InvalidTrace("truncated trace".to_string()));
+        }
+        let mut rows = vec![TraceRow::default(); count];
+
+        for row in &mut rows {
+            row.step = reader.u64()?;
+        }
+        for row in &mut rows {
+            row.task_type = reader.u32()?;
+        }
+        let indices: [fn(&mut TraceRow) -> &mut u32; 4] = [
+            |row| &mut row.before.0,
+            |row| &mut row.before.1,
+            |row| &mut row.after.0,
+            |row| &mut row.after.1,
+        ];
+        for column in indices {
+            for row in &mut rows {
+                *column(row) = reader.u32()?;
+            }
+        }
+        for (row, failed) in rows.iter_mut().zip(reader.take(count)?) {
+            row.failed = *failed != 0;
+        }
+
+        let tasks = reader.frames(count)?;
+        for (row, task) in rows.iter_mut().zip(tasks) {
+            row.task = task;
+        }
+        let lists: [fn(&mut TraceRow) -> &mut Frames; 3] = [
+            |row| &mut row.data_popped,
+            |row| &mut row.data_pushed,
+            |row| &mut row.tasks_pushed,
+        ];
+        for column in lists {
+            let mut counts = Vec::with_capacity(count);
+            for _ in 0..count {
+                counts.push(reader.u32()? as usize);
+            }
+            let total = counts
+                .iter()
+                .try_fold(0_usize, |total, count| total.checked_add(*count))
+                .ok_or_else(|| Error::InvalidTrace("too many frames".to_string()))?;
+
+            let mut frames = reader.frames(total)?.into_iter();
+            for (row, count) in rows.iter_mut().zip(counts) {
+                column(row).extend(frames.by_ref().take(count));
+            }
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(Error::InvalidTrace(
+                "unexpected bytes after the trace".to_string(),
+            ));
+        }
+
+        Ok(Self { task_types, rows })
+    }
+
+    /// Formats the trace as CSV with a header line.
+    ///
+    /// Frames are written as lowercase hex, and the frames of list columns are
+    /// separated by `;`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "step,task_type,task,data_popped,data_pushed,tasks_pushed,\
+             front_before,back_before,front_after,back_after,failed\n",
+        );
+
+        for row in &self.rows {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                row.step,
+                self.task_type_name(row).unwrap_or_default(),
+                hex(&row.task),
+                hex_list(&row.data_popped),
+                hex_list(&row.data_pushed),
+                hex_list(&row.tasks_pushed),
+                row.before.0,
+                row.before.1,
+                row.after.0,
+                row.after.1,
+                row.failed,
+            );
+        }
+
+        csv
+    }
+
+    /// Writes the trace to a file in the binary format.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), &self.encode())
+    }
+
+    /// Writes the trace to a file as CSV.
+    pub fn save_csv(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), self.to_csv().as_bytes())
+    }
+
+    /// Reads a trace written by [`Trace::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    /// Re-validates every transition of the trace.
+    ///
+    /// Each row's task is executed on a fresh scheduler holding only the data
+    /// frames the row says it popped, and must pop, push and fail exactly as
+    /// recorded. The stack indices must follow from the recorded frames and
+    /// continue from one row to the next. Returns the first violation found.
+    pub fn check(&self) -> Result<()> {
+        let mut previous: Option<&TraceRow> = None;
+
+        for (index, row) in self.rows.iter().enumerate() {
+            let violation =
+                |reason: String| Error::InvalidTrace(format!("step {}: {}", row.step, reason));
+
+            if row.step != index as u64 {
+                return Err(violation(format!("expected step index {}", index)));
+            }
+            if let Some(previous) = previous.filter(|previous| previous.after != row.before) {
+                return Err(violation(format!(
+                    "starts at indices {:?}, previous step ended at {:?}",
+                    row.before, previous.after
+                )));
+            }
+            if self.task_type_name(row) != task_type(&row.task).ok().as_deref() {
+                return Err(violation("task doesn't match its type".to_string()));
+            }
+            if expected_after(row) != Some(row.after) {
+                return Err(violation(format!(
+                    "indices {:?} don't follow from the recorded frames",
+                    row.after
+                )));
+            }
+
+            let actual = execute_row(row)?;
+            if actual.task != row.task
+                || actual.data_popped != row.data_popped
+                || actual.data_pushed != row.data_pushed
+                || actual.tasks_pushed != row.tasks_pushed
+                || actual.failed != row.failed
+            {
+                return Err(violation(
+                    "re-execution doesn't match the recorded transition".to_string(),
+                ));
+            }
+
+            previous = Some(row);
+        }
+
+        Ok(())
+    }
+}
+
+/// Observer that builds a [`Trace`] of every executed step.
+#[derive(Debug, Default)]
+pub struct Tracer {
+    trace: Trace,
+    /// Index of every task type name in the trace
+    type_ids: HashMap<String, u32>,
+    /// The step in progress
+    current: Option<TraceRow>,
+}
+
+impl Tracer {
+    /// Creates an empty tracer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the trace of the steps completed so far.
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Consumes the tracer and returns its trace.
+    pub fn into_trace(self) -> Trace {
+        self.trace
+    }
+
+    fn type_id(&mut self, name: String) -> Result<u32> {
+        if let Some(&id) = self.type_ids.get(&name) {
+            return Ok(id);
+        }
+
+        let id = narrow(self.trace.task_types.len(), "task type index")?;
+        self.trace.task_types.push(name.clone());
+        self.type_ids.insert(name, id);
+        Ok(id)
+    }
+}
+
+impl Observer for Tracer {
+    fn on_event(&mut self, event: &Event<'_>, stack: &SchedulerStack) -> Result<()> {
+        let indices = (
+            narrow(stack.front_index(), "front index")?,
+            narrow(stack.back_index(), "back index")?,
+        );
+
+        if *event == Event::StepStarted {
+            self.current = Some(TraceRow {
+                step: self.trace.rows.len() as u64,
+                before: indices,
+                ..TraceRow::default()
+            });
+            return Ok(());
+        }
+        if self.current.is_none() {
+            return Ok(());
+        }
+
+        // The first task popped in a step is the one being executed
+        if let Event::TaskPopped(frame) = *event {
+            let task_type = self.type_id(task_type(frame)?)?;
+            if let Some(row) = self.current.as_mut().filter(|row| row.task.is_empty()) {
+                row.task = frame.to_vec();
+                row.task_type = task_type;
+            }
+            return Ok(());
+        }
+
+        let Some(row) = &mut self.current else {
+            return Ok(());
+        };
+        match *event {
+            Event::DataPopped(frame) => row.data_popped.push(frame.to_vec()),
+            Event::DataPushed(frame) => row.data_pushed.push(frame.to_vec()),
+            Event::TaskPushed(frame) => row.tasks_pushed.push(frame.to_vec()),
+            Event::StepFinished | Event::StepFailed => {
+                row.after = indices;
+                row.failed = *event == Event::StepFailed;
+                self.trace.rows.extend(self.current.take());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Executes the task of `row` on a scheduler holding only its popped data.
+fn execute_row(row: &TraceRow) -> Result<TraceRow> {
+    let mut scheduler = Scheduler::new();
+    for frame in row.data_popped.iter().rev() {
+        scheduler.push_data_frame(frame)?;
+    }
+    scheduler.push_task_frame(&row.task)?;
+
+    scheduler.add_observer(Recorder::default());
+    let failed = scheduler.execute().is_err();
+    let recording = scheduler
+        .remove_observer::<Recorder>()
+        .unwrap_or_default()
+        .into_recording();
+
+    let mut actual = TraceRow {
+        failed,
+        ..TraceRow::default()
+    };
+    for operation in recording.steps().flat_map(|step| &step.operations) {
+        match operation {
+            Operation::TaskPopped(frame) => actual.task = frame.clone(),
+            Operation::DataPopped(frame) => actual.data_popped.push(frame.clone()),
+            Operation::DataPushed(frame) => actual.data_pushed.push(frame.clone()),
+            Operation::TaskPushed(frame) => actual.tasks_pushed.push(frame.clone()),
+            Operation::TaskSelected(_) | Operation::Cleared => {}
+        }
+    }
+
+    Ok(actual)
+}
+
+/// Computes the stack indices after `row` from its recorded frames.
+fn expected_after(row: &TraceRow) -> Option<(u32, u32)> {
+    let size = |frames: &[Vec<u8>]| {
+        frames
+            .iter()
+            .map(|frame| frame.len() as i64 + LENGTH_SIZE)
+            .sum::<i64>()
+    };
+
+    let front = i64::from(row.before.0) - size(&row.data_popped) + size(&row.data_pushed);
+    let back =
+        i64::from(row.before.1) + row.task.len() as i64 + LENGTH_SIZE - size(&row.tasks_pushed);
+
+    Some((u32::try_from(front).ok()?, u32::try_from(back).ok()?))
+}
+
+/// Converts `value` to the `u32` stored in a [`TraceRow`], failing instead of
+/// wrapping if it doesn't fit.
+fn narrow(value: usize, what: &str) -> Result<u32> {
+    u32::try_from(value)
+        .map_err(|_| Error::InvalidTrace(format!("{} {} doesn't fit in a trace row", what, value)))
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&u32::try_from(value).unwrap_or(u32::MAX).to_le_bytes());
+}
+
+fn put_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    put_u32(bytes, value.len());
+    bytes.extend_from_slice(value);
+}
+
+/// Writes a byte column: all lengths followed by all bytes.
+fn put_frames<'a>(bytes: &mut Vec<u8>, frames: impl Iterator<Item = &'a [u8]> + Clone) {
+    for frame in frames.clone() {
+        put_u32(bytes, frame.len());
+    }
+    for frame in frames {
+        bytes.extend_from_slice(frame);
+    }
+}
+
+fn hex_list(frames: &[Vec<u8>]) -> String {
+    frames
+        .iter()
+        .map(|frame| hex(frame))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Reads the binary trace format front to back.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        if length > self.bytes.len() {
+            return Err(Error::InvalidTrace("truncated trace".to_string()));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut value = [0; 4];
+        value.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(value))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    fn take_bytes(&mut self) -> Result<&'a [u8]> {
+        let length = self.u32()? as usize;
+        self.take(length)
+    }
+
+    /// Reads a byte column of `count` frames.
+    fn frames(&mut self, count: usize) -> Result<Vec<Vec<u8>>> {
+        if count > self.bytes.len() / 4 {
+            return Err(Error::InvalidTrace("truncated trace".to_string()));
+        }
+
+        let mut lengths = Vec::with_capacity(count);
+        for _ in 0..count {
+            lengths.push(self.u32()? as usize);
+        }
+
+        lengths
+            .into_iter()
+            .map(|length| Ok(self.take(length)?.to_vec()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::SchedulerTask;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Sum {
+        operands: u8,
+    }
+
+    #[typetag::serde]
+    impl SchedulerTask for Sum {
+        fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            let mut sum = 0_u32;
+            for _ in 0..self.operands {
+                sum += scheduler.pop_data::<u32>()?;
+            }
+            scheduler.push_data(&sum)?;
+            Ok(vec![])
+        }
+    }
+
+    fn trace() -> Trace {
+        let mut scheduler = Scheduler::new();
+        for value in [1_u32, 2, 3] {
+            scheduler.push_data(&value).unwrap();
+        }
+        scheduler.push_task(Box::new(Sum { operands: 2 })).unwrap();
+        scheduler.push_task(Box::new(Sum { operands: 2 })).unwrap();
+
+        scheduler.add_observer(Tracer::new());
+        scheduler.execute_all().unwrap();
+        assert_eq!(scheduler.pop_data::<u32>().unwrap(), 6);
+
+        scheduler.remove_observer::<Tracer>().unwrap().into_trace()
+    }
+
+    #[test]
+    fn test_trace_rows() {
+        let trace = trace();
+
+        assert_eq!(trace.task_types, ["Sum"]);
+        assert_eq!(trace.rows.len(), 2);
+        assert_eq!(trace.rows[0].data_popped.len(), 2);
+        assert_eq!(trace.rows[0].data_pushed.len(), 1);
+        assert_eq!(trace.rows[0].after, trace.rows[1].before);
+        assert!(trace.check().is_ok());
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let trace = trace();
+        let bytes = trace.encode();
+
+        assert_eq!(Trace::decode(&bytes).unwrap(), trace);
+        assert!(Trace::decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = trace().to_csv();
+        let lines: Vec<_> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("step,task_type,task,"));
+        assert!(lines[1].starts_with("0,Sum,"));
+    }
+
+    #[test]
+    fn test_check_rejects_forged_output() {
+        let mut trace = trace();
+
+        let mut forged = Vec::new();
+        ciborium::ser::into_writer(&7_u32, &mut forged).unwrap();
+        trace.rows[1].data_pushed[0] = forged;
+
+        let error = trace.check().unwrap_err().to_string();
+        assert!(error.contains("step 1"), "{}", error);
+    }
+
+    #[test]
+    fn test_narrow_rejects_large_indices() {
+        assert_eq!(narrow(7, "front index").unwrap(), 7);
+        assert!(matches!(
+            narrow(u32::MAX as usize + 1, "front index"),
+            Err(Error::InvalidTrace(_))
+        ));
+    }
+}
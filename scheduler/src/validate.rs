@@ -0,0 +1,173 @@
+use std::io::Cursor;
+
+use ciborium::Value;
+
+use crate::stack::{MAX_BACK_FRAME_SIZE, ValidationReport};
+use crate::{Scheduler, SchedulerTask, task_type};
+
+impl Scheduler {
+    /// Checks the integrity of the whole stack.
+    ///
+    /// Besides the layout checks of
+    /// [`BidirectionalStack::validate`](crate::stack::BidirectionalStack::validate),
+    /// every data frame must hold exactly one CBOR value and every task frame
+    /// a task registered in this binary that fits the
+    /// [`MAX_BACK_FRAME_SIZE`] bytes of a task frame. With
+    /// [`Scheduler::set_decode_limits`], every frame must also stay within the
+    /// limits. Useful after restoring a scheduler from disk, before executing
+    /// anything.
+    pub fn validate(&self) -> ValidationReport {
+        let limits = self.decode_limits();
+        let check_data = |frame: &[u8]| {
+            let problem = limits.and_then(|limits| limits.check_data(frame).err());
+            problem
+                .map(|e| e.to_string())
+                .or_else(|| check_data_frame(frame))
+        };
+        let check_task = |frame: &[u8]| {
+            if frame.len() > MAX_BACK_FRAME_SIZE {
+                return Some(format!(
+                    "task frame of {} bytes exceeds {} bytes",
+                    frame.len(),
+                    MAX_BACK_FRAME_SIZE
+                ));
+            }
+            let problem = limits.and_then(|limits| limits.check_task(frame).err());
+            problem
+                .map(|e| e.to_string())
+                .or_else(|| check_task_frame(frame))
+        };
+
+        self.stack().validate_with(check_data, check_task)
+    }
+}
+
+/// Checks that a data frame holds exactly one CBOR value, returning the problem if not.
+pub fn check_data_frame(frame: &[u8]) -> Option<String> {
+    let mut cursor = Cursor::new(frame);
+    if let Err(e) = ciborium::de::from_reader::<Value, _>(&mut cursor) {
+        return Some(format!("invalid CBOR: {}", e));
+    }
+    if cursor.position() as usize != frame.len() {
+        return Some("unexpected bytes after the value".to_string());
+    }
+    None
+}
+
+/// Checks that a task frame holds a task registered in this binary, returning
+/// the problem if not.
+pub fn check_task_frame(frame: &[u8]) -> Option<String> {
+    if let Some(problem) = check_data_frame(frame) {
+        return Some(problem);
+    }
+    if let Err(e) = task_type(frame) {
+        return Some(e.to_string());
+    }
+
+    let mut cursor = Cursor::new(frame);
+    ciborium::de::from_reader::<Box<dyn SchedulerTask>, _>(&mut cursor)
+        .err()
+        .map(|e| format!("can't be loaded: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Result, SchedulerStack};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Noop {}
+
+    #[typetag::serde]
+    impl SchedulerTask for Noop {
+        fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+            Ok(vec![])
+        }
+    }
+
+    fn scheduler() -> Scheduler {
+        let mut scheduler = Scheduler::new();
+        scheduler.push_data(&"text").unwrap();
+        scheduler.push_data(&[1_u8, 2]).unwrap();
+        scheduler.push_task(Box::new(Noop {})).unwrap();
+        scheduler
+    }
+
+    #[test]
+    fn test_valid_scheduler() {
+        let report = scheduler().validate();
+        assert!(report.is_valid());
+        assert_eq!((report.front_frames, report.back_frames), (2, 1));
+    }
+
+    #[test]
+    fn test_reports_first_corrupt_frame() {
+        let mut bytes = scheduler().snapshot();
+
+        // The second data frame `[1, 2]` starts at offset 7 of the stack
+        let stack_start = bytes.len() - crate::SCHEDULER_CAPACITY;
+        bytes[stack_start + 7] = 0x83;
+        let restored = Scheduler::restore(&bytes).unwrap();
+
+        let corruption = restored.validate().corruption.unwrap();
+        assert_eq!(corruption.offset, 7);
+        assert!(corruption.problem.starts_with("invalid CBOR"));
+    }
+
+    #[test]
+    fn test_checks_decode_limits() {
+        let mut scheduler = scheduler();
+        assert!(scheduler.validate().is_valid());
+
+        scheduler.set_decode_limits(crate::DecodeLimits::new().max_string_len(3));
+        let corruption = scheduler.validate().corruption.unwrap();
+        assert_eq!(corruption.offset, 0);
+        assert!(corruption.problem.contains("string longer than 3 bytes"));
+
+        scheduler.set_decode_limits(crate::DecodeLimits::new().allow_task("Fork"));
+        let corruption = scheduler.validate().corruption.unwrap();
+        assert!(corruption.problem.contains("Noop"));
+    }
+
+    #[test]
+    fn test_checks_task_frame_size() {
+        let fields = vec![
+            (Value::from("type"), Value::from("Noop")),
+            (Value::from("padding"), Value::Bytes(vec![0; 300])),
+        ];
+        let mut frame = Vec::new();
+        ciborium::ser::into_writer(&Value::Map(fields), &mut frame).unwrap();
+
+        // Lay the oversized frame out like push_back would, bypassing its cap
+        let capacity = crate::SCHEDULER_CAPACITY;
+        let back_index = capacity - frame.len() - 2;
+        let mut buffer = vec![0; capacity];
+        buffer[back_index..back_index + 2].copy_from_slice(&(frame.len() as u16).to_be_bytes());
+        buffer[back_index + 2..].copy_from_slice(&frame);
+        buffer[back_index + 2..].reverse();
+
+        let scheduler = Scheduler {
+            stack: SchedulerStack::from_parts(0, back_index, &buffer).unwrap(),
+            ..Scheduler::default()
+        };
+        let corruption = scheduler.validate().corruption.unwrap();
+        assert_eq!(corruption.offset, back_index + 2);
+        assert!(corruption.problem.contains("exceeds 255 bytes"));
+    }
+
+    #[test]
+    fn test_frame_checks() {
+        assert_eq!(check_data_frame(&[0x01]), None);
+        assert_eq!(
+            check_data_frame(&[0x01, 0x02]).unwrap(),
+            "unexpected bytes after the value"
+        );
+
+        let mut frame = Vec::new();
+        ciborium::ser::into_writer(&Value::Map(vec![]), &mut frame).unwrap();
+        assert!(check_data_frame(&frame).is_none());
+        assert!(check_task_frame(&frame).is_some());
+    }
+}
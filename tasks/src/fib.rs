@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use scheduler::{Result, Scheduler, SchedulerTask};
+use scheduler::{Fork, Result, Scheduler, SchedulerTask};
 
 use crate::add::Add;
 
@@ -47,6 +47,44 @@ impl SchedulerTask for Fib {
     }
 }
 
+/// A task that calculates the nth Fibonacci number in parallel
+///
+/// Above the cutoff, F(n-1) and F(n-2) are computed as independent branches of
+/// a [`Fork`] and combined once both are done. At or below the cutoff the
+/// computation falls back to the sequential [`Fib`] task, so small subproblems
+/// don't pay the cost of spawning child schedulers.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ParFib {
+    /// The index of the Fibonacci number to calculate
+    pub n: u128,
+    /// Largest index computed sequentially
+    pub cutoff: u128,
+}
+
+impl ParFib {
+    /// Creates a new parallel Fibonacci task with the given index and cutoff.
+    pub fn new(n: u128, cutoff: u128) -> Self {
+        Self { n, cutoff }
+    }
+}
+
+#[typetag::serde]
+impl SchedulerTask for ParFib {
+    fn execute(&mut self, _scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+        if self.n < 2 || self.n <= self.cutoff {
+            return Ok(vec![Box::new(Fib::new(self.n))]);
+        }
+
+        Ok(vec![
+            Box::new(Fork::new(vec![
+                Box::new(ParFib::new(self.n - 1, self.cutoff)),
+                Box::new(ParFib::new(self.n - 2, self.cutoff)),
+            ])),
+            Box::new(FibCombiner::new()),
+        ])
+    }
+}
+
 /// A helper task that combines the results of two Fibonacci subtasks
 ///
 /// This task takes the two most recent Fibonacci results from the data stack
@@ -73,6 +111,7 @@ impl SchedulerTask for FibCombiner {
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)] // test_fib_sequence builds its expectations with vec!
 mod tests {
     use super::*;
     use scheduler::Scheduler;
@@ -122,4 +161,21 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_par_fib_matches_fib() {
+        for n in 0..12 {
+            let mut scheduler = Scheduler::default();
+            scheduler.push_task(Box::new(Fib::new(n))).unwrap();
+            scheduler.execute_all().unwrap();
+            let expected: u128 = scheduler.pop_data().unwrap();
+
+            let mut scheduler = Scheduler::default();
+            scheduler.push_task(Box::new(ParFib::new(n, 3))).unwrap();
+            scheduler.execute_all().unwrap();
+            let output: u128 = scheduler.pop_data().unwrap();
+
+            assert_eq!(output, expected, "ParFib({}) should match Fib({})", n, n);
+        }
+    }
 }
@@ -0,0 +1,83 @@
+use scheduler::{Error, Scheduler};
+use tasks::exp::Exp;
+use tasks::fib::Fib;
+
+#[test]
+fn test_fib_combiner_backtrace() {
+    let mut scheduler = Scheduler::default();
+    scheduler.set_backtraces(true);
+    scheduler.push_task(Box::new(Fib::new(3))).unwrap();
+
+    // Run Fib(3), Fib(2), Fib(1) and Fib(0), then take away their results
+    for _ in 0..4 {
+        scheduler.execute().unwrap();
+    }
+    scheduler.pop_data::<u128>().unwrap();
+    scheduler.pop_data::<u128>().unwrap();
+
+    let error = scheduler.execute().unwrap_err();
+    let Error::TaskFailed { error, backtrace } = &error else {
+        panic!("expected a task backtrace, got {:?}", error);
+    };
+    assert!(error.to_string().contains("Stack underflow"));
+    assert_eq!(
+        backtrace.tasks,
+        ["FibCombiner {}", "Fib {n: 2}", "Fib {n: 3}"]
+    );
+    assert_eq!(backtrace.pending, 2);
+    assert_eq!(backtrace.next, ["Fib {n: 1}", "FibCombiner {}"]);
+}
+
+#[test]
+fn test_rescheduled_task_backtrace() {
+    let mut scheduler = Scheduler::default();
+    scheduler.set_backtraces(true);
+    scheduler.push_task(Box::new(Exp::new(2, 3))).unwrap();
+
+    // Run until ExpInternal is about to execute for the second time
+    let mut runs = 0;
+    loop {
+        let next = scheduler.stack().back_frames().unwrap().pop().unwrap();
+        if scheduler::task_type(&next).unwrap() == "ExpInternal" {
+            runs += 1;
+            if runs == 2 {
+                break;
+            }
+        }
+        scheduler.execute().unwrap();
+    }
+    while !scheduler.is_empty_data() {
+        scheduler.pop_data::<u128>().unwrap();
+    }
+
+    // Earlier executions of the rescheduled task are left out
+    let error = scheduler.execute().unwrap_err();
+    let Error::TaskFailed { backtrace, .. } = &error else {
+        panic!("expected a task backtrace, got {:?}", error);
+    };
+    assert_eq!(backtrace.tasks.len(), 2);
+    assert!(backtrace.tasks[0].starts_with("ExpInternal"));
+    assert_eq!(backtrace.tasks[1], "Exp {x: 2, y: 3}");
+    assert!(
+        error
+            .to_string()
+            .contains("task backtrace (failed task first):")
+    );
+    assert!(matches!(error.without_backtrace(), Error::Execution(_)));
+}
+
+#[test]
+fn test_backtraces_are_opt_in() {
+    let mut scheduler = Scheduler::default();
+    assert!(!scheduler.backtraces());
+
+    // Run Fib(2), Fib(1) and Fib(0), then take away one of their results
+    scheduler.push_task(Box::new(Fib::new(2))).unwrap();
+    for _ in 0..3 {
+        scheduler.execute().unwrap();
+    }
+    scheduler.pop_data::<u128>().unwrap();
+
+    // Without backtraces the task's error is returned as is
+    assert!(matches!(scheduler.execute(), Err(Error::Execution(_))));
+}
@@ -0,0 +1,30 @@
+use scheduler::{CheckpointPolicy, Checkpointer, Scheduler};
+use tasks::fib::Fib;
+
+#[test]
+fn test_checkpoint_resume_latest() {
+    let dir = tempfile::tempdir().unwrap();
+
+    {
+        let mut scheduler = Scheduler::default();
+        scheduler.push_task(Box::new(Fib::new(12))).unwrap();
+
+        let policy = CheckpointPolicy::new().every_steps(50).keep(2);
+        scheduler.add_observer(Checkpointer::new(dir.path(), policy).unwrap());
+
+        // Interrupt the computation part way through
+        for _ in 0..230 {
+            scheduler.execute().unwrap();
+        }
+
+        let checkpointer = scheduler.observer::<Checkpointer>().unwrap();
+        assert_eq!(checkpointer.checkpoints().unwrap().len(), 2);
+    }
+
+    let mut scheduler = Scheduler::resume_latest(dir.path(), None).unwrap();
+    scheduler.execute_all().unwrap();
+
+    let output: u128 = scheduler.pop_data().unwrap();
+    assert_eq!(output, 144);
+    assert!(scheduler.is_empty_data());
+}
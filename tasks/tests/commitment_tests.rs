@@ -0,0 +1,24 @@
+use scheduler::{HashChain, Scheduler};
+use tasks::fib::Fib;
+
+fn fib_chain(n: u128) -> HashChain {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(n))).unwrap();
+    scheduler.add_observer(HashChain::new(&scheduler));
+    scheduler.execute_all().unwrap();
+
+    scheduler.remove_observer::<HashChain>().unwrap()
+}
+
+#[test]
+fn test_fib_commitment_is_reproducible() {
+    let chain = fib_chain(8);
+    let again = fib_chain(8);
+
+    assert_eq!(chain.digests(), again.digests());
+    assert_eq!(
+        chain.commitment(),
+        chain.digests().last().unwrap().as_slice()
+    );
+    assert_ne!(chain.commitment(), fib_chain(9).commitment());
+}
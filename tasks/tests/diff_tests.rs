@@ -0,0 +1,49 @@
+use scheduler::{FrameDiff, Scheduler, SnapshotDiff};
+use tasks::fib::Fib;
+
+#[test]
+fn test_incremental_fib_snapshots() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(15))).unwrap();
+
+    let base = scheduler.snapshot();
+    let mut previous = base.clone();
+    let mut diffs = Vec::new();
+    for _ in 0..10 {
+        for _ in 0..25 {
+            scheduler.execute().unwrap();
+        }
+        let snapshot = scheduler.snapshot();
+        let diff = SnapshotDiff::between(&previous, &snapshot).unwrap();
+        assert!(diff.encode().len() < snapshot.len() / 10);
+
+        diffs.push(diff.encode());
+        previous = snapshot;
+    }
+
+    // Rebuild the latest state from the base and the chain of diffs
+    let mut state = base;
+    for diff in &diffs {
+        state = SnapshotDiff::decode(diff).unwrap().apply(&state).unwrap();
+    }
+    assert_eq!(state, scheduler.snapshot());
+
+    let mut restored = Scheduler::restore(&state).unwrap();
+    restored.execute_all().unwrap();
+    let output: u128 = restored.pop_data().unwrap();
+    assert_eq!(output, 610);
+}
+
+#[test]
+fn test_fib_frame_diff() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(5))).unwrap();
+    let base = scheduler.snapshot();
+
+    scheduler.execute().unwrap();
+    let diff = FrameDiff::between(&base, &scheduler.snapshot()).unwrap();
+
+    assert_eq!(diff.tasks.unchanged, 0);
+    assert_eq!(diff.tasks.removed.len(), 1);
+    assert!(diff.to_string().contains("- Fib {"), "{}", diff);
+}
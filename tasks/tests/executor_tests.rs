@@ -0,0 +1,37 @@
+use scheduler::{Executor, SchedulerTask};
+use tasks::fib::Fib;
+use tasks::mul::Mul;
+
+#[test]
+fn test_executor_fib_batch() {
+    let jobs: Vec<Box<dyn SchedulerTask>> = (0..200)
+        .map(|n| Box::new(Fib::new(n % 15)) as Box<dyn SchedulerTask>)
+        .collect();
+
+    let results = Executor::new(4).run(jobs);
+
+    let expected = [0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144, 233, 377];
+    for (i, result) in results.into_iter().enumerate() {
+        let mut output = result.unwrap();
+        assert_eq!(output.len(), 1, "Job {} should leave one value", i);
+        let value: u128 = output.pop().unwrap();
+        assert_eq!(value, expected[i % 15], "Job {} returned wrong value", i);
+    }
+}
+
+#[test]
+fn test_executor_mixed_jobs_are_isolated() {
+    let jobs: Vec<Box<dyn SchedulerTask>> = vec![
+        Box::new(Mul::new(6, 7)),
+        Box::new(Fib::new(10)),
+        Box::new(Mul::new(3, 0)),
+    ];
+
+    let results = Executor::new(2).run(jobs);
+    let values: Vec<u128> = results
+        .into_iter()
+        .map(|result| result.unwrap().pop().unwrap())
+        .collect();
+
+    assert_eq!(values, vec![42, 55, 0]);
+}
@@ -1,5 +1,5 @@
 use scheduler::Scheduler;
-use tasks::fib::Fib;
+use tasks::fib::{Fib, ParFib};
 
 #[test]
 fn test_fib_base_cases() {
@@ -109,6 +109,20 @@ fn test_fib_multiple_calculations() {
     assert_eq!(output3, 55); // Fib(10) = 55
 }
 
+#[test]
+fn test_par_fib_medium_values() {
+    let test_cases = [(10, 55), (15, 610), (20, 6765)];
+
+    for (n, expected) in test_cases {
+        let mut scheduler = Scheduler::default();
+        scheduler.push_task(Box::new(ParFib::new(n, 10))).unwrap();
+        scheduler.execute_all().unwrap();
+        let output: u128 = scheduler.pop_data().unwrap();
+        assert_eq!(output, expected, "ParFib({}) should be {}", n, expected);
+        assert!(scheduler.is_empty_data());
+    }
+}
+
 #[test]
 #[should_panic(expected = "StackCapacity(Underflow)")]
 fn test_fib_empty_stack_error() {
@@ -7,9 +7,28 @@ use tasks::mul::Mul;
 
 // Include the module tests
 mod add_tests;
+mod backtrace_tests;
+mod checkpoint_tests;
+mod commitment_tests;
+mod diff_tests;
+mod executor_tests;
 mod exp_tests;
 mod fib_tests;
+mod jobs_tests;
+mod journal_tests;
+mod limits_tests;
+mod metrics_tests;
 mod mul_tests;
+mod profile_tests;
+mod replay_tests;
+mod schema_tests;
+mod sealed_tests;
+mod selection_tests;
+mod spawn_tests;
+mod storage_tests;
+mod timeline_tests;
+mod trace_tests;
+mod validate_tests;
 
 #[test]
 fn test_task_composition() {
@@ -0,0 +1,39 @@
+use scheduler::{JobManager, JobStatus, Policy};
+use tasks::exp::Exp;
+use tasks::fib::Fib;
+use tasks::mul::Mul;
+
+#[test]
+fn test_jobs_have_isolated_stacks() {
+    let mut manager = JobManager::new();
+
+    let fib = manager.submit(Box::new(Fib::new(10))).unwrap();
+    let mul = manager.submit(Box::new(Mul::new(6, 7))).unwrap();
+    let exp = manager.submit(Box::new(Exp::new(2, 5))).unwrap();
+
+    manager.run_all();
+
+    assert_eq!(manager.status(fib).unwrap(), JobStatus::Completed);
+    assert_eq!(manager.result::<u128>(fib).unwrap(), 55);
+    assert_eq!(manager.result::<u128>(mul).unwrap(), 42);
+    assert_eq!(manager.result::<u128>(exp).unwrap(), 32);
+    assert!(manager.result::<u128>(fib).is_err());
+}
+
+#[test]
+fn test_jobs_weighted_policy() {
+    let mut manager = JobManager::with_policy(Policy::Weighted);
+
+    let heavy = manager.submit_weighted(Box::new(Fib::new(12)), 8).unwrap();
+    let light = manager.submit_weighted(Box::new(Fib::new(12)), 1).unwrap();
+
+    // The heavier job gets more steps per turn, so it finishes first
+    while manager.status(heavy).unwrap() == JobStatus::Running {
+        manager.step();
+    }
+    assert_eq!(manager.status(light).unwrap(), JobStatus::Running);
+
+    manager.run_all();
+    assert_eq!(manager.result::<u128>(heavy).unwrap(), 144);
+    assert_eq!(manager.result::<u128>(light).unwrap(), 144);
+}
@@ -0,0 +1,55 @@
+use scheduler::{FsyncPolicy, Journal, Scheduler};
+use tasks::exp::Exp;
+use tasks::fib::Fib;
+
+#[test]
+fn test_journal_recovers_after_crash() {
+    let dir = tempfile::tempdir().unwrap();
+
+    {
+        let mut scheduler = Scheduler::default();
+        scheduler.push_task(Box::new(Fib::new(10))).unwrap();
+
+        let journal = Journal::create(dir.path(), FsyncPolicy::EveryN(16), &scheduler).unwrap();
+        scheduler.add_observer(journal);
+
+        for _ in 0..100 {
+            scheduler.execute().unwrap();
+        }
+        // The scheduler is dropped here, as if the process died
+    }
+
+    let mut scheduler = Journal::recover(dir.path(), None).unwrap();
+    scheduler.execute_all().unwrap();
+
+    let output: u128 = scheduler.pop_data().unwrap();
+    assert_eq!(output, 55);
+    assert!(scheduler.is_empty_data());
+}
+
+#[test]
+fn test_journal_recovers_after_checkpoints() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Exp::new(3, 4))).unwrap();
+
+    let journal = Journal::create(dir.path(), FsyncPolicy::Never, &scheduler).unwrap();
+    scheduler.add_observer(journal);
+
+    let mut steps = 0;
+    while steps < 60 && !scheduler.is_empty() {
+        scheduler.execute().unwrap();
+        steps += 1;
+        if steps % 25 == 0 {
+            Journal::checkpoint(&mut scheduler).unwrap();
+        }
+    }
+
+    let mut recovered = Journal::recover(dir.path(), None).unwrap();
+    assert_eq!(recovered.snapshot(), scheduler.snapshot());
+
+    recovered.execute_all().unwrap();
+    let output: u128 = recovered.pop_data().unwrap();
+    assert_eq!(output, 81);
+}
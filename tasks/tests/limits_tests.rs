@@ -0,0 +1,48 @@
+use scheduler::{DecodeLimits, Error, Scheduler};
+use tasks::add::Add;
+use tasks::fib::Fib;
+
+#[test]
+fn test_allowlisted_fib_snapshot_resumes() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(10))).unwrap();
+    for _ in 0..20 {
+        scheduler.execute().unwrap();
+    }
+    let snapshot = scheduler.snapshot();
+
+    let limits = DecodeLimits::new()
+        .allow_task("Fib")
+        .allow_task("FibCombiner")
+        .allow_task("Add");
+    let mut resumed = Scheduler::restore_with_limits(&snapshot, limits).unwrap();
+    resumed.execute_all().unwrap();
+    let output: u128 = resumed.pop_data().unwrap();
+    assert_eq!(output, 55);
+}
+
+#[test]
+fn test_unexpected_task_type_is_rejected() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Add::new(1, 2))).unwrap();
+    let snapshot = scheduler.snapshot();
+
+    let limits = DecodeLimits::new().allow_task("Fib");
+    assert!(matches!(
+        Scheduler::restore_with_limits(&snapshot, limits),
+        Err(Error::TaskNotAllowed(name)) if name == "Add"
+    ));
+}
+
+#[test]
+fn test_oversized_data_is_rejected() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_data(&vec![0_u8; 100]).unwrap();
+    let snapshot = scheduler.snapshot();
+
+    let limits = DecodeLimits::new().max_collection_len(64);
+    assert!(matches!(
+        Scheduler::restore_with_limits(&snapshot, limits),
+        Err(Error::LimitExceeded(_))
+    ));
+}
@@ -0,0 +1,43 @@
+use scheduler::{Metrics, Scheduler};
+use tasks::fib::Fib;
+use tasks::mul::Mul;
+
+#[test]
+fn test_fib_metrics() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(6))).unwrap();
+    scheduler.add_observer(Metrics::new(&scheduler));
+    scheduler.execute_all().unwrap();
+
+    let metrics = scheduler.observer::<Metrics>().unwrap();
+    let fib = metrics.task("Fib").unwrap();
+    assert_eq!(fib.executions, 25);
+    // Every Fib(n) with n > 1 spawns two Fibs and a combiner
+    assert_eq!(fib.spawned, 36);
+
+    let combiner = metrics.task("FibCombiner").unwrap();
+    assert_eq!(combiner.executions, 12);
+    assert_eq!(combiner.data_pops, 24);
+    assert_eq!(metrics.task("Add").unwrap().data_pushes, 12);
+    assert!(metrics.front_high_water() > 0);
+    assert!(metrics.peak_usage() >= metrics.front_high_water());
+}
+
+#[test]
+fn test_mul_prometheus_export() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("metrics.prom");
+
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Mul::new(3, 4))).unwrap();
+    scheduler.add_observer(Metrics::new(&scheduler));
+    scheduler.execute_all().unwrap();
+
+    let metrics = scheduler.observer::<Metrics>().unwrap();
+    assert_eq!(metrics.task("MulInternal").unwrap().reschedules, 3);
+    metrics.save_prometheus(&path).unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.contains("scheduler_task_reschedules_total{task_type=\"MulInternal\"} 3\n"));
+    assert!(text.contains("# TYPE scheduler_stack_peak_usage_bytes gauge\n"));
+}
@@ -0,0 +1,38 @@
+use scheduler::{Profiler, Scheduler, Weight};
+use tasks::exp::Exp;
+use tasks::fib::Fib;
+
+#[test]
+fn test_fib_folded_stacks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("fib.folded");
+
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(6))).unwrap();
+    scheduler.add_observer(Profiler::new(&scheduler));
+    scheduler.execute_all().unwrap();
+
+    let profiler = scheduler.observer::<Profiler>().unwrap();
+    profiler.save_folded(&path, Weight::Steps).unwrap();
+    let folded = std::fs::read_to_string(&path).unwrap();
+
+    // Fib(6) recurses down to Fib(0) along its Fib(n-1) branches
+    assert!(folded.contains("\nFib;Fib;Fib;Fib;Fib;Fib 2\n"));
+    assert!(folded.contains("\nFib;FibCombiner;Add 1\n"));
+    let steps: u64 = folded
+        .lines()
+        .map(|line| line.rsplit_once(' ').unwrap().1.parse::<u64>().unwrap())
+        .sum();
+    assert_eq!(steps, 49);
+}
+
+#[test]
+fn test_exp_reschedules_share_a_stack() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Exp::new(2, 3))).unwrap();
+    scheduler.add_observer(Profiler::new(&scheduler));
+    scheduler.execute_all().unwrap();
+
+    let samples = scheduler.observer::<Profiler>().unwrap().samples();
+    assert_eq!(samples["Exp;ExpInternal"].steps, 3);
+}
@@ -0,0 +1,45 @@
+use scheduler::{Recorder, Recording, Scheduler};
+use tasks::exp::Exp;
+use tasks::fib::Fib;
+
+#[test]
+fn test_record_and_replay_fib() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("fib.rec");
+
+    let mut scheduler = Scheduler::default();
+    scheduler.add_observer(Recorder::new(&scheduler));
+    scheduler.push_task(Box::new(Fib::new(10))).unwrap();
+    scheduler.execute_all().unwrap();
+
+    let output: u128 = scheduler.pop_data().unwrap();
+    assert_eq!(output, 55);
+
+    let recorder = scheduler.remove_observer::<Recorder>().unwrap();
+    recorder.into_recording().save(&path).unwrap();
+
+    let recording = Recording::load(&path).unwrap();
+    let report = recording.replay().unwrap();
+    assert_eq!(report.steps, recording.steps().count());
+    assert_eq!(report.divergence, None);
+}
+
+#[test]
+fn test_replay_detects_tampered_task() {
+    let mut scheduler = Scheduler::default();
+    scheduler.add_observer(Recorder::new(&scheduler));
+    scheduler.push_task(Box::new(Exp::new(2, 5))).unwrap();
+    scheduler.execute_all().unwrap();
+
+    let mut recording = scheduler.observer::<Recorder>().unwrap().recording();
+
+    // Start the replay from a different root task
+    let mut initial = Scheduler::default();
+    initial.push_task(Box::new(Exp::new(3, 5))).unwrap();
+    recording.initial = initial.snapshot();
+    recording.entries.remove(0);
+
+    let divergence = recording.replay().unwrap().divergence.unwrap();
+    assert_eq!(divergence.step, 0);
+    assert_eq!(divergence.operation, 0);
+}
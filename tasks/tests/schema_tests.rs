@@ -0,0 +1,90 @@
+use scheduler::schema::{insert_field, register_migration, rename_field, set_schema_version};
+use scheduler::{Error, Result, Scheduler, SchedulerTask};
+use serde::{Deserialize, Serialize};
+
+/// Version 1 of `Countdown`, with its counter named `count`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "Countdown")]
+struct CountdownV1 {
+    count: u32,
+}
+
+/// Version 3 of `Countdown`: `count` became `remaining` and `step` was added.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Countdown {
+    remaining: u32,
+    step: u32,
+}
+
+#[typetag::serde]
+impl SchedulerTask for Countdown {
+    fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+        scheduler.push_data(&self.remaining)?;
+        self.remaining = self.remaining.saturating_sub(self.step);
+        Ok(vec![])
+    }
+
+    fn push_self(&mut self) -> bool {
+        self.remaining > 0
+    }
+}
+
+fn register() {
+    set_schema_version("Countdown", 3);
+    register_migration("Countdown", 1, |value| {
+        rename_field(value, "count", "remaining")
+    });
+    register_migration("Countdown", 2, |value| {
+        insert_field(value, "step", ciborium::Value::Integer(2.into()))
+    });
+}
+
+/// A snapshot written before the schema section existed, holding one task.
+fn version_1_snapshot(task: &impl Serialize) -> Vec<u8> {
+    let mut frame = Vec::new();
+    ciborium::ser::into_writer(task, &mut frame).unwrap();
+
+    let capacity = scheduler::SCHEDULER_CAPACITY;
+    let mut buffer = vec![0; capacity];
+    let back = capacity - frame.len() - 2;
+    buffer[back] = 0;
+    buffer[back + 1] = frame.len() as u8;
+    for (i, byte) in frame.iter().rev().enumerate() {
+        buffer[back + 2 + i] = *byte;
+    }
+
+    let mut snapshot = b"SCHD\x01\x02".to_vec();
+    for index in [capacity, 0, back] {
+        snapshot.extend_from_slice(&(index as u32).to_le_bytes());
+    }
+    snapshot.extend_from_slice(&buffer);
+    snapshot
+}
+
+#[test]
+fn test_old_snapshot_is_migrated() {
+    register();
+
+    let snapshot = version_1_snapshot(&CountdownV1 { count: 5 });
+    let mut scheduler = Scheduler::restore(&snapshot).unwrap();
+    scheduler.execute_all().unwrap();
+
+    let values: Vec<u32> = (0..3).map(|_| scheduler.pop_data().unwrap()).collect();
+    assert_eq!(values, [1, 3, 5]);
+}
+
+#[test]
+fn test_unknown_task_type_is_listed() {
+    #[derive(Serialize)]
+    #[serde(tag = "type")]
+    struct Removed {}
+
+    let snapshot = version_1_snapshot(&Removed {});
+
+    match Scheduler::restore(&snapshot) {
+        Err(Error::IncompatibleTasks(types)) => {
+            assert_eq!(types, ["Removed (unknown or invalid task)"]);
+        }
+        other => panic!("expected incompatible tasks, got {:?}", other.map(|_| ())),
+    }
+}
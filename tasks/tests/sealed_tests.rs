@@ -0,0 +1,38 @@
+use scheduler::{Error, Scheduler};
+use tasks::fib::Fib;
+
+#[test]
+fn test_encrypted_fib_snapshot_resumes() {
+    let key = [42; 32];
+
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(10))).unwrap();
+    for _ in 0..40 {
+        scheduler.execute().unwrap();
+    }
+    let sealed = scheduler.snapshot_encrypted(&key).unwrap();
+
+    let mut resumed = Scheduler::restore_encrypted(&sealed, &key, None).unwrap();
+    resumed.execute_all().unwrap();
+    let output: u128 = resumed.pop_data().unwrap();
+    assert_eq!(output, 55);
+}
+
+#[test]
+fn test_injected_task_is_rejected() {
+    let key = b"shared secret";
+
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(3))).unwrap();
+    let sealed = scheduler.snapshot_signed(key);
+
+    // Swap the argument of the pending task for a much larger one
+    let mut tampered = sealed.clone();
+    let position = tampered.iter().rposition(|byte| *byte == 3).unwrap();
+    tampered[position] = 0x17;
+
+    assert!(matches!(
+        Scheduler::restore_signed(&tampered, key, None),
+        Err(Error::Authentication)
+    ));
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+
+use scheduler::recording::Operation;
+use scheduler::{Error, Recorder, Result, Scheduler, SchedulerTask, Selection};
+use tasks::exp::Exp;
+use tasks::mul::Mul;
+
+/// Pushes its id for an [`Acknowledge`] it spawns, leaving the data stack as it found it.
+#[derive(Debug, Serialize, Deserialize)]
+struct Alert {
+    id: u128,
+    priority: u32,
+    deadline: Option<u64>,
+}
+
+#[typetag::serde]
+impl SchedulerTask for Alert {
+    fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+        scheduler.push_data(&self.id)?;
+        Ok(vec![Box::new(Acknowledge { id: self.id })])
+    }
+
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        self.deadline
+    }
+
+    fn independent(&self) -> bool {
+        true
+    }
+}
+
+/// Pops the id pushed by its [`Alert`].
+#[derive(Debug, Serialize, Deserialize)]
+struct Acknowledge {
+    id: u128,
+}
+
+#[typetag::serde]
+impl SchedulerTask for Acknowledge {
+    fn execute(&mut self, scheduler: &mut Scheduler) -> Result<Vec<Box<dyn SchedulerTask>>> {
+        let id: u128 = scheduler.pop_data()?;
+        if id != self.id {
+            return Err(Error::Task(format!("expected {}, got {}", self.id, id)));
+        }
+        Ok(vec![])
+    }
+}
+
+/// Interleaves alerts with `Mul(7, 6)` and `Exp(3, 4)` and returns both results
+/// and the number of steps that ran a task out of stack order.
+fn run(selection: Selection) -> (u128, u128, usize) {
+    let mut scheduler = Scheduler::new();
+    scheduler.set_selection(selection);
+    scheduler.add_observer(Recorder::new(&scheduler));
+
+    scheduler.push_task(Box::new(Mul::new(7, 6))).unwrap();
+    for id in 0..4 {
+        scheduler
+            .push_task(Box::new(Alert {
+                id,
+                priority: id as u32 * 3,
+                deadline: Some(40 - id as u64 * 10),
+            }))
+            .unwrap();
+    }
+    scheduler.push_task(Box::new(Exp::new(3, 4))).unwrap();
+    scheduler.execute_all().unwrap();
+
+    let mul: u128 = scheduler.pop_data().unwrap();
+    let exp: u128 = scheduler.pop_data().unwrap();
+    assert!(scheduler.is_empty_data());
+
+    let recording = scheduler
+        .remove_observer::<Recorder>()
+        .unwrap()
+        .into_recording();
+    let selected = recording
+        .steps()
+        .filter(|step| {
+            step.operations
+                .iter()
+                .any(|operation| matches!(operation, Operation::TaskSelected(_)))
+        })
+        .count();
+
+    (mul, exp, selected)
+}
+
+#[test]
+fn test_selection_keeps_results() {
+    assert_eq!(run(Selection::Lifo), (42, 81, 0));
+
+    for selection in [Selection::Priority, Selection::EarliestDeadline] {
+        let (mul, exp, selected) = run(selection);
+        assert_eq!((mul, exp), (42, 81));
+        assert!(
+            selected > 0,
+            "{:?} never ran a task out of order",
+            selection
+        );
+    }
+}
+
+#[test]
+fn test_dependent_tasks_keep_stack_order() {
+    // Mul and Exp spawn tasks that consume their siblings' data, so without
+    // independent tasks every mode executes them exactly like Lifo
+    for selection in [Selection::Priority, Selection::EarliestDeadline] {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_selection(selection);
+        scheduler.push_task(Box::new(Mul::new(5, 11))).unwrap();
+        scheduler.push_task(Box::new(Exp::new(2, 5))).unwrap();
+        scheduler.add_observer(Recorder::new(&scheduler));
+        scheduler.execute_all().unwrap();
+
+        assert_eq!(scheduler.pop_data::<u128>().unwrap(), 55);
+        assert_eq!(scheduler.pop_data::<u128>().unwrap(), 32);
+
+        let recording = scheduler.remove_observer::<Recorder>().unwrap();
+        assert!(
+            recording
+                .into_recording()
+                .steps()
+                .flat_map(|step| &step.operations)
+                .all(|operation| !matches!(operation, Operation::TaskSelected(_)))
+        );
+    }
+}
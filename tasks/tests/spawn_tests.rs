@@ -0,0 +1,62 @@
+use scheduler::{Scheduler, SpawnKind, SpawnRecorder};
+use tasks::exp::Exp;
+use tasks::fib::Fib;
+
+#[test]
+fn test_fib_spawn_tree() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(6))).unwrap();
+    scheduler.add_observer(SpawnRecorder::new(&scheduler));
+    scheduler.execute_all().unwrap();
+
+    let output: u128 = scheduler.pop_data().unwrap();
+    assert_eq!(output, 8);
+
+    let recorder = scheduler.remove_observer::<SpawnRecorder>().unwrap();
+    let nodes = recorder.nodes();
+    assert!(nodes.iter().all(|node| node.executed && !node.failed));
+    assert!(recorder.pending().is_empty());
+
+    // Fib(6) spawns Fib(5), Fib(4) and a combiner, each combiner an Add
+    assert_eq!(nodes[0].kind, SpawnKind::Root);
+    assert_eq!(nodes[0].children, [1, 2, 3]);
+    let fibs = nodes
+        .iter()
+        .filter(|node| scheduler::task_type(&node.frame).unwrap() == "Fib")
+        .count();
+    assert_eq!(fibs, 25);
+
+    let dot = recorder.to_dot();
+    assert!(dot.starts_with("digraph spawn {"));
+    assert!(dot.contains("n0 [label=\"Fib {n: 6}\"];"));
+    assert!(dot.contains("n0 -> n1;"));
+    // The top-level Add consumes Fib(5) and Fib(4)
+    assert!(dot.contains("label=\"5\""));
+    assert!(dot.contains("label=\"3\""));
+}
+
+#[test]
+fn test_exp_reschedules_itself() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("exp.dot");
+
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Exp::new(2, 3))).unwrap();
+    scheduler.add_observer(SpawnRecorder::new(&scheduler));
+    scheduler.execute_all().unwrap();
+
+    let recorder = scheduler.observer::<SpawnRecorder>().unwrap();
+    let rescheduled: Vec<_> = (0..recorder.nodes().len())
+        .filter(|id| recorder.nodes()[*id].kind == SpawnKind::Rescheduled)
+        .collect();
+    assert!(!rescheduled.is_empty());
+
+    // Every execution of a rescheduled task traces back to the root Exp
+    for id in rescheduled {
+        assert_eq!(recorder.ancestry(id)[0], 0);
+    }
+
+    recorder.save_dot(&path).unwrap();
+    let dot = std::fs::read_to_string(&path).unwrap();
+    assert!(dot.contains("[style=dashed, label=\"push_self\"]"));
+}
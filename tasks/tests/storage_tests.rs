@@ -0,0 +1,29 @@
+use scheduler::Scheduler;
+use tasks::fib::Fib;
+
+#[test]
+fn test_mapped_scheduler_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("fib.stack");
+
+    {
+        let mut scheduler = Scheduler::open_mapped(&path, None).unwrap();
+        scheduler.push_task(Box::new(Fib::new(10))).unwrap();
+        for _ in 0..150 {
+            scheduler.execute().unwrap();
+        }
+    }
+
+    let mut scheduler = Scheduler::open_mapped(&path, None).unwrap();
+    scheduler.execute_all().unwrap();
+
+    let output: u128 = scheduler.pop_data().unwrap();
+    assert_eq!(output, 55);
+    assert!(scheduler.is_empty_data());
+
+    // The emptied stack is persisted as well
+    drop(scheduler);
+    let scheduler = Scheduler::open_mapped(&path, None).unwrap();
+    assert!(scheduler.is_empty());
+    assert!(scheduler.is_empty_data());
+}
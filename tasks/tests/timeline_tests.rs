@@ -0,0 +1,70 @@
+use scheduler::{Scheduler, Timeline};
+use tasks::exp::Exp;
+use tasks::fib::Fib;
+
+#[test]
+fn test_fib_chrome_trace() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("fib.json");
+
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(6))).unwrap();
+    scheduler.add_observer(Timeline::new(&scheduler));
+    scheduler.execute_all().unwrap();
+
+    let timeline = scheduler.observer::<Timeline>().unwrap();
+    timeline.save_chrome_trace(&path).unwrap();
+
+    let trace: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let spans: Vec<_> = trace["traceEvents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|event| event["ph"] == "X")
+        .collect();
+    assert_eq!(spans.len(), timeline.spawns().nodes().len());
+
+    assert_eq!(spans[0]["name"], "Fib");
+    assert_eq!(spans[0]["args"]["n"], 6);
+
+    // The root span covers the whole run, in the exported file too
+    let (root_start, root_end) = interval(spans[0]);
+    assert!(spans.iter().all(|span| {
+        let (start, end) = interval(span);
+        root_start <= start && end <= root_end
+    }));
+
+    let spans = timeline.spans();
+    let end = spans[0].start + spans[0].duration;
+    assert!(spans.iter().all(|span| span.start + span.duration <= end));
+}
+
+/// Returns the start and end of an exported span in whole nanoseconds.
+fn interval(span: &serde_json::Value) -> (u64, u64) {
+    // Microseconds are exported with three decimals, so rounding recovers the nanoseconds
+    let nanos = |micros: &serde_json::Value| (micros.as_f64().unwrap() * 1000.0).round() as u64;
+    let start = nanos(&span["ts"]);
+    (start, start + nanos(&span["dur"]))
+}
+
+#[test]
+fn test_rescheduled_executions_are_siblings() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Exp::new(2, 3))).unwrap();
+    scheduler.add_observer(Timeline::new(&scheduler));
+    scheduler.execute_all().unwrap();
+
+    let spans = scheduler.observer::<Timeline>().unwrap().spans();
+    let internal: Vec<_> = spans
+        .iter()
+        .filter(|span| span.name == "ExpInternal")
+        .collect();
+    assert_eq!(internal.len(), 3);
+
+    // Every execution is spawned by the root Exp, one after the other
+    assert!(internal.iter().all(|span| span.depth == 1));
+    for pair in internal.windows(2) {
+        assert!(pair[0].start + pair[0].duration <= pair[1].start);
+    }
+}
@@ -0,0 +1,42 @@
+use scheduler::{Scheduler, Trace, Tracer};
+use tasks::exp::Exp;
+
+#[test]
+fn test_exp_trace_checks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("exp.trace");
+
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Exp::new(3, 4))).unwrap();
+    scheduler.add_observer(Tracer::new());
+    scheduler.execute_all().unwrap();
+
+    let output: u128 = scheduler.pop_data().unwrap();
+    assert_eq!(output, 81);
+
+    let trace = scheduler.remove_observer::<Tracer>().unwrap().into_trace();
+    trace.save(&path).unwrap();
+    trace.save_csv(dir.path().join("exp.csv")).unwrap();
+
+    let loaded = Trace::load(&path).unwrap();
+    assert_eq!(loaded, trace);
+    assert!(loaded.task_types.iter().any(|name| name == "Exp"));
+    loaded.check().unwrap();
+}
+
+#[test]
+fn test_check_rejects_skipped_step() {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Exp::new(2, 3))).unwrap();
+    scheduler.add_observer(Tracer::new());
+    scheduler.execute_all().unwrap();
+
+    let mut trace = scheduler.observer::<Tracer>().unwrap().trace().clone();
+    assert!(trace.rows.len() > 2);
+
+    trace.rows.remove(1);
+    for (step, row) in trace.rows.iter_mut().enumerate() {
+        row.step = step as u64;
+    }
+    assert!(trace.check().is_err());
+}
@@ -0,0 +1,64 @@
+use scheduler::stack::BidirectionalStack;
+use scheduler::{SCHEDULER_CAPACITY, Scheduler};
+use tasks::fib::Fib;
+
+fn fib_stack() -> Scheduler {
+    let mut scheduler = Scheduler::default();
+    scheduler.push_task(Box::new(Fib::new(8))).unwrap();
+    for _ in 0..30 {
+        scheduler.execute().unwrap();
+    }
+    scheduler
+}
+
+#[test]
+fn test_restored_fib_snapshot_is_valid() {
+    let scheduler = Scheduler::restore(&fib_stack().snapshot()).unwrap();
+
+    let report = scheduler.validate();
+    assert!(report.is_valid(), "{:?}", report.corruption);
+    assert!(report.front_frames > 0);
+    assert_eq!(
+        report.back_frames,
+        scheduler.stack().back_frames().unwrap().len()
+    );
+}
+
+#[test]
+fn test_random_corruption_never_panics() {
+    let scheduler = fib_stack();
+    let stack = scheduler.stack();
+    let used: Vec<usize> = (0..stack.front_index())
+        .chain(stack.back_index()..SCHEDULER_CAPACITY)
+        .collect();
+
+    // A fixed linear congruential generator keeps the test deterministic
+    let mut seed = 0x2545_f491_u64;
+    let mut next = || {
+        seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+        (seed >> 33) as usize
+    };
+
+    let mut corrupt = 0;
+    for _ in 0..500 {
+        let mut buffer = stack.as_bytes().to_vec();
+        let offset = used[next() % used.len()];
+        buffer[offset] ^= 1 << (next() % 8);
+
+        let flipped = BidirectionalStack::<SCHEDULER_CAPACITY, 2>::from_parts(
+            stack.front_index(),
+            stack.back_index(),
+            &buffer,
+        )
+        .unwrap();
+        let report = flipped.validate_with(
+            scheduler::validate::check_data_frame,
+            scheduler::validate::check_task_frame,
+        );
+        if let Some(corruption) = report.corruption {
+            assert!(corruption.offset <= SCHEDULER_CAPACITY);
+            corrupt += 1;
+        }
+    }
+    assert!(corrupt > 0);
+}